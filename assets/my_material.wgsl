@@ -5,22 +5,46 @@ struct VertexOutput {
        @location(2) uv: vec2<f32>,
 };
 
-struct MyMat {
+struct PlanetMaterial {
        color: vec4<f32>,
+       capture_color: vec4<f32>,
+       glow: f32,
+       capture: f32,
+       pulse: f32,
 };
 
 @group(1) @binding(0)
-var<uniform> uniform_data: MyMat;
+var<uniform> material: PlanetMaterial;
 
 @group(1) @binding(1)
 var texture: texture_2d<f32>;
 @group(1) @binding(2)
 var our_sampler: sampler;
 
+let TAU: f32 = 6.28318530718;
+
 @fragment
 fn fragment(input: VertexOutput) -> @location(0) vec4<f32> {
-   var output_color = vec4<f32>(1.0, 1.0, 1.0, 1.0);
-   output_color = output_color * textureSample(texture, our_sampler, input.uv);
-   output_color = output_color * uniform_data.color;
-   return output_color;
+   let texel = textureSample(texture, our_sampler, input.uv);
+   var rgb = texel.rgb * material.color.rgb;
+
+   // uv y grows downwards, so the top of the planet is at (0.5, 0)
+   let offset = input.uv - vec2<f32>(0.5, 0.5);
+   let edge = smoothstep(0.35, 0.5, length(offset));
+
+   // the captured share fills clockwise from the top, in the colour of the capturer
+   var angle = atan2(offset.x, -offset.y);
+   if (angle < 0.0) {
+      angle = angle + TAU;
+   }
+   if (angle / TAU < material.capture) {
+      rgb = mix(rgb, material.capture_color.rgb, 0.5);
+   }
+
+   // red rim while under attack, warm rim and a brighter surface while selected
+   rgb = mix(rgb, vec3<f32>(1.0, 0.15, 0.1), edge * material.pulse * 0.8);
+   rgb = mix(rgb, vec3<f32>(1.0, 0.95, 0.4), edge * material.glow * 0.7);
+   rgb = rgb + vec3<f32>(material.glow * 0.1);
+
+   return vec4<f32>(rgb, texel.a * material.color.a);
 }
@@ -0,0 +1,133 @@
+//! Measures `collision_avoidance` for 5,000 ships packed in fleets, run by a Bevy `App` the way
+//! the simulation runs it: ships move, `update_spatial_index` re-indexes them, then every ship
+//! steers from its neighbourhood. Compared against the 16.6 ms frame budget of 60 fps, and
+//! against an all-pairs neighbour search.
+//!
+//! Run with `cargo bench --bench flocking`.
+
+use std::f32::consts::PI;
+use std::time::{Duration, Instant};
+
+use bevy::{prelude::*, utils::Uuid};
+use bevy_rapier3d::prelude::Velocity;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use galactic_wars::game::components::{characteristics::*, players::Ownership};
+use galactic_wars::game::resources::game_obj_res::SpatialIndex;
+use galactic_wars::game::systems::movement::collision_avoidance;
+use galactic_wars::game::update_spatial_index;
+use galactic_wars::game::utils::flocking::{self, Boid, FlockWeights};
+use galactic_wars::selection::components::Selectable;
+
+const SHIPS: u32 = 5000;
+const FLEETS: u32 = 20;
+const FLEET_SPREAD: f32 = 30.;
+const GALAXY_RADIUS: f32 = 2000.;
+const FRAMES: u32 = 60;
+const FRAME_BUDGET: Duration = Duration::from_micros(16_667);
+const DT: f32 = 1. / 60.;
+
+const FIGHTER: FlockWeights = FlockWeights {
+    radius: 4.,
+    separation: 1.5,
+    alignment: 0.6,
+    cohesion: 0.4,
+};
+
+fn spawn_fleets(world: &mut World, rng: &mut StdRng) {
+    let per_fleet = SHIPS / FLEETS;
+    for fleet in 0..FLEETS {
+        let angle = rng.gen::<f32>() * PI * 2.;
+        let centre = Vec2::new(angle.cos(), angle.sin()) * rng.gen::<f32>() * GALAXY_RADIUS;
+        let heading = Vec2::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5).normalize() * 10.;
+        let owner = Uuid::from_u128((fleet % 4) as u128 + 1);
+        for i in 0..per_fleet {
+            let offset = Vec2::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5) * FLEET_SPREAD;
+            let pos = centre + offset;
+            world.spawn().insert_bundle((
+                NetId(fleet * per_fleet + i + 1),
+                Selectable,
+                Transform::from_translation(pos.extend(0.)),
+                Velocity::linear(heading.extend(0.)),
+                Avoidance {
+                    impulse: Vec3::ZERO,
+                },
+                Flocking(FIGHTER),
+                Ownership(Some(owner)),
+                Destination(DestinationEnum::Space((pos + heading * 100.).extend(0.))),
+            ));
+        }
+    }
+}
+
+/// Stands in for physics: ships drift along their heading and steering, so every one of them
+/// is re-indexed each frame.
+fn drift(mut ships: Query<(&mut Transform, &Velocity, &Avoidance)>) {
+    for (mut transform, vel, avoidance) in ships.iter_mut() {
+        transform.translation += (vel.linvel.normalize_or_zero() + avoidance.impulse) * DT;
+    }
+}
+
+fn all_pairs_frame(world: &mut World) -> f32 {
+    let boids: Vec<Boid> = world
+        .query::<(&Transform, &Velocity, &Ownership)>()
+        .iter(world)
+        .map(|(transform, vel, owner)| Boid {
+            pos: transform.translation.truncate(),
+            vel: vel.linvel.truncate(),
+            group: owner.0.map(|uuid| uuid.as_u128()).unwrap_or_default(),
+        })
+        .collect();
+    boids
+        .iter()
+        .map(|me| flocking::steer(me, boids.iter(), &FIGHTER).length())
+        .sum()
+}
+
+fn main() {
+    let mut app = App::new();
+    app.insert_resource(SpatialIndex::default())
+        .add_system_to_stage(CoreStage::PreUpdate, update_spatial_index)
+        .add_system(collision_avoidance)
+        .add_system_to_stage(CoreStage::PostUpdate, drift);
+    spawn_fleets(&mut app.world, &mut StdRng::seed_from_u64(7));
+    // the first frame indexes every ship from scratch
+    app.update();
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        app.update();
+    }
+    let per_frame = start.elapsed() / FRAMES;
+    let steering: f32 = app
+        .world
+        .query::<&Avoidance>()
+        .iter(&app.world)
+        .map(|avoidance| avoidance.impulse.length())
+        .sum();
+    println!(
+        "app: {} ships, {:.3?} per frame over {} frames (mean steering {:.3})",
+        SHIPS,
+        per_frame,
+        FRAMES,
+        steering / SHIPS as f32
+    );
+    println!(
+        "uses {:.1}% of the 60 fps frame budget",
+        per_frame.as_secs_f64() / FRAME_BUDGET.as_secs_f64() * 100.
+    );
+
+    let start = Instant::now();
+    let all_pairs = all_pairs_frame(&mut app.world);
+    let all_pairs_time = start.elapsed();
+    println!(
+        "all pairs: {:.3?} for a single frame (checksum {:.1}), {:.1}x slower",
+        all_pairs_time,
+        all_pairs,
+        all_pairs_time.as_secs_f64() / per_frame.as_secs_f64()
+    );
+
+    if per_frame > FRAME_BUDGET {
+        println!("WARNING: flocking alone exceeds the frame budget");
+    }
+}
@@ -0,0 +1,166 @@
+//! Compares the linear scans previously used for planet and click lookups with the spatial grid,
+//! on a `Galaxy::Ludicrous` sized board.
+//!
+//! Run with `cargo bench --bench spatial_index`.
+
+use std::f32::consts::PI;
+use std::time::{Duration, Instant};
+
+use bevy::prelude::{Entity, Vec2};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+#[allow(dead_code)]
+#[path = "../src/game/utils/spatial_grid.rs"]
+mod spatial_grid;
+use spatial_grid::SpatialGrid;
+
+const GALAXY_RADIUS: f32 = 5000.;
+const PLANETS: u32 = 500;
+const SHIPS: u32 = 5000;
+const QUERIES: usize = 10_000;
+const CLICK_DISTANCE: f32 = 3.2;
+
+struct Object {
+    entity: Entity,
+    pos: Vec2,
+    radius: f32,
+}
+
+fn random_pos(rng: &mut StdRng) -> Vec2 {
+    let dist = rng.gen::<f32>();
+    let angle = rng.gen::<f32>() * PI * 2.0;
+    Vec2::new(angle.cos(), angle.sin()) * dist * GALAXY_RADIUS
+}
+
+fn spawn(rng: &mut StdRng, first_id: u32, count: u32, radius: f32) -> Vec<Object> {
+    (first_id..first_id + count)
+        .map(|id| Object {
+            entity: Entity::from_raw(id),
+            pos: random_pos(rng),
+            radius,
+        })
+        .collect()
+}
+
+fn time<F: FnMut() -> usize>(label: &str, mut f: F) -> Duration {
+    let start = Instant::now();
+    let found = f();
+    let elapsed = start.elapsed();
+    println!("{:<32} {:>10.3?} ({} hits)", label, elapsed, found);
+    elapsed
+}
+
+fn report(name: &str, linear: Duration, grid: Duration) {
+    println!(
+        "{}: {:.1}x faster with the grid\n",
+        name,
+        linear.as_secs_f64() / grid.as_secs_f64()
+    );
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let planets = spawn(&mut rng, 0, PLANETS, 5.);
+    let ships = spawn(&mut rng, PLANETS, SHIPS, 0.);
+    let mut selectables: Vec<&Object> = planets.iter().chain(ships.iter()).collect();
+    selectables.sort_by_key(|o| o.entity);
+    // half the clicks land on a planet, like players aiming at one
+    let clicks: Vec<Vec2> = (0..QUERIES)
+        .map(|i| match i % 2 {
+            0 => planets[i % planets.len()].pos + Vec2::splat(1.),
+            _ => random_pos(&mut rng),
+        })
+        .collect();
+
+    let mut planet_grid = SpatialGrid::new(16.);
+    let mut selectable_grid = SpatialGrid::new(16.);
+    let build = Instant::now();
+    for o in planets.iter() {
+        planet_grid.insert(o.entity, o.entity.id(), o.pos, o.radius);
+    }
+    for o in selectables.iter() {
+        selectable_grid.insert(o.entity, o.entity.id(), o.pos, 0.);
+    }
+    println!(
+        "built grids for {} objects in {:.3?}\n",
+        selectables.len(),
+        build.elapsed()
+    );
+
+    let linear = time("find_planet, linear", || {
+        clicks
+            .iter()
+            .filter_map(|click| {
+                planets
+                    .iter()
+                    .find(|o| o.pos.distance(*click) < o.radius)
+                    .map(|o| o.entity)
+            })
+            .count()
+    });
+    let grid = time("find_planet, grid", || {
+        clicks
+            .iter()
+            .filter_map(|click| planet_grid.at_point(*click))
+            .count()
+    });
+    report("find_planet", linear, grid);
+
+    let linear = time("click select, linear", || {
+        clicks
+            .iter()
+            .filter_map(|click| {
+                let mut nearest = None;
+                let mut min_dist = CLICK_DISTANCE;
+                for o in selectables.iter() {
+                    let dist = o.pos.distance(*click);
+                    if dist <= min_dist {
+                        min_dist = dist;
+                        nearest = Some(o.entity);
+                    }
+                }
+                nearest
+            })
+            .count()
+    });
+    let grid = time("click select, grid", || {
+        clicks
+            .iter()
+            .filter_map(|click| selectable_grid.nearest(*click, CLICK_DISTANCE))
+            .count()
+    });
+    report("click select", linear, grid);
+
+    let boxes: Vec<(Vec2, Vec2)> = clicks
+        .iter()
+        .take(QUERIES / 10)
+        .map(|click| (*click - Vec2::splat(40.), *click + Vec2::splat(40.)))
+        .collect();
+    let linear = time("box select, linear", || {
+        boxes
+            .iter()
+            .map(|(min, max)| {
+                selectables
+                    .iter()
+                    .filter(|o| o.pos.cmpge(*min).all() && o.pos.cmple(*max).all())
+                    .count()
+            })
+            .sum()
+    });
+    let grid = time("box select, grid", || {
+        boxes
+            .iter()
+            .map(|(min, max)| selectable_grid.in_rect(*min, *max).len())
+            .sum()
+    });
+    report("box select", linear, grid);
+
+    // every ship moving a little each frame, as update_spatial_index sees it
+    let moved = time("move all ships, grid", || {
+        for o in ships.iter() {
+            selectable_grid.insert(o.entity, o.entity.id(), o.pos + Vec2::splat(0.5), 0.);
+        }
+        ships.len()
+    });
+    println!("re-indexing {} ships per frame costs {:.3?}", SHIPS, moved);
+}
@@ -1,21 +1,68 @@
 use bevy::{
-    prelude::{Color, Handle, Image, Material, Vec2},
+    prelude::{Color, Handle, Image, Material, Vec4},
     reflect::TypeUuid,
-    render::render_resource::AsBindGroup,
+    render::{
+        render_asset::RenderAssets,
+        render_resource::{AsBindGroup, AsBindGroupShaderType, ShaderType},
+    },
 };
 
+/// Material of a planet. Every planet owns its own, so selection, sieges and attacks only show
+/// on the planet they concern.
 #[derive(AsBindGroup, TypeUuid, Clone)]
 #[uuid = "33d2536b-8b55-4e8e-9d47-462c338dfc08"]
+#[uniform(0, PlanetMaterialUniform)]
 pub struct PlanetMaterial {
-    #[uniform(0)]
+    /// Tint of the owner, dimmed while the planet is out of sight.
     pub color: Color,
+    /// Colour of the player capturing the planet.
+    pub capture_color: Color,
+    /// Strength of the selection glow, 0 when not selected.
+    pub glow: f32,
+    /// Share of the capture done, from 0 to 1.
+    pub capture: f32,
+    /// Strength of the under-attack pulse, 0 when the planet is left alone.
+    pub pulse: f32,
 
     #[texture(1)]
     #[sampler(2)]
     pub image: Handle<Image>,
 }
+impl PlanetMaterial {
+    pub fn new(color: Color, image: Handle<Image>) -> Self {
+        PlanetMaterial {
+            color,
+            capture_color: Color::WHITE,
+            glow: 0.,
+            capture: 0.,
+            pulse: 0.,
+            image,
+        }
+    }
+}
 impl Material for PlanetMaterial {
     fn fragment_shader() -> bevy::render::render_resource::ShaderRef {
         "my_material.wgsl".into()
     }
 }
+
+/// Layout of the `PlanetMaterial` uniform in `my_material.wgsl`.
+#[derive(Clone, Default, ShaderType)]
+pub struct PlanetMaterialUniform {
+    pub color: Vec4,
+    pub capture_color: Vec4,
+    pub glow: f32,
+    pub capture: f32,
+    pub pulse: f32,
+}
+impl AsBindGroupShaderType<PlanetMaterialUniform> for PlanetMaterial {
+    fn as_bind_group_shader_type(&self, _images: &RenderAssets<Image>) -> PlanetMaterialUniform {
+        PlanetMaterialUniform {
+            color: self.color.as_linear_rgba_f32().into(),
+            capture_color: self.capture_color.as_linear_rgba_f32().into(),
+            glow: self.glow,
+            capture: self.capture,
+            pulse: self.pulse,
+        }
+    }
+}
@@ -4,38 +4,42 @@ pub use bevy::{
 };
 
 pub use super::components::*;
+use crate::game::resources::log_res::ChatInput;
 
 pub fn camera_system(
     time: Res<Time>,
     keyboard_input: Res<Input<KeyCode>>,
+    chat: Res<ChatInput>,
     mut ms_wheel_rdr: EventReader<MouseWheel>,
     mut query: Query<&mut Transform, With<MainCamera>>,
 ) {
     let mut transf = query.single_mut();
     let mut direction = Vec3::ZERO;
     let scale: f32 = transf.scale.x;
-    if keyboard_input.pressed(KeyCode::A) {
+    // keys are typed into the chat instead
+    let pressed = |key: KeyCode| !chat.active && keyboard_input.pressed(key);
+    if pressed(KeyCode::A) {
         direction -= Vec3::new(2.0, 0.0, 0.0);
     }
 
-    if keyboard_input.pressed(KeyCode::D) {
+    if pressed(KeyCode::D) {
         direction += Vec3::new(2.0, 0.0, 0.0);
     }
 
-    if keyboard_input.pressed(KeyCode::W) {
+    if pressed(KeyCode::W) {
         direction += Vec3::new(0.0, 2.0, 0.0);
     }
 
-    if keyboard_input.pressed(KeyCode::S) {
+    if pressed(KeyCode::S) {
         direction -= Vec3::new(0.0, 2.0, 0.0);
     }
 
-    if keyboard_input.pressed(KeyCode::Z) {
+    if pressed(KeyCode::Z) {
         let scale = scale + 0.5;
         transf.scale = Vec3::splat(scale);
     }
 
-    if keyboard_input.pressed(KeyCode::X) {
+    if pressed(KeyCode::X) {
         let scale = scale - 0.5;
         transf.scale = Vec3::splat(scale);
     }
@@ -68,13 +72,18 @@ fn cursor_to_world(
     let (transform, camera) = camera_query.single();
 
     let screen_size = Vec2::new(window.width() as f32, window.height() as f32);
-    let camera_position = transform.compute_matrix();
-    let projection_matrix = camera.projection_matrix();
 
     // Normalized device coordinate cursor position from (-1, -1, -1) to (1, 1, 1)
     let cursor_ndc = (cursor / screen_size) * 2.0 - Vec2::from([1.0, 1.0]);
-    // let cursor_pos_ndc_near = cursor_ndc.extend(-1.0);
-    let cursor_pos_ndc_far = cursor_ndc.extend(1.0);
+    ndc_to_world(cursor_ndc, transform, camera)
+}
+
+/// Projects a point in normalized device coordinates onto the game plane.
+pub fn ndc_to_world(ndc: Vec2, transform: &Transform, camera: &Camera) -> Vec2 {
+    let camera_position = transform.compute_matrix();
+    let projection_matrix = camera.projection_matrix();
+    // let cursor_pos_ndc_near = ndc.extend(-1.0);
+    let cursor_pos_ndc_far = ndc.extend(1.0);
 
     let ndc_to_world = camera_position * projection_matrix.inverse();
     // let cursor_pos_near = ndc_to_world.project_point3(cursor_pos_ndc_near);
@@ -1,15 +1,47 @@
-use bevy::{prelude::*, utils::Uuid};
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, Uuid},
+};
 use rand::{distributions::Standard, prelude::Distribution};
 
+use crate::game::utils::flocking::FlockWeights;
+use crate::game::utils::formation::Formation;
+
 // EVENTS
 pub struct TakeOwnership {
     pub entity: Entity,
     pub owner: Uuid,
 }
 
-pub struct ArrivedAtDestination(pub Vec3);
+pub struct ArrivedAtDestination {
+    pub ship: Entity,
+    pub loc: Vec3,
+}
+
+pub struct ShipDestroyed {
+    pub owner: Option<Uuid>,
+    pub by: Option<Uuid>,
+    pub is_trader: bool,
+}
+
+pub struct PlanetAttacked {
+    pub planet: Entity,
+    pub owner: Uuid,
+    pub attacker: Uuid,
+}
+
+pub struct ResearchCompleted {
+    pub player: Uuid,
+    pub tech: u8,
+}
 // COMPONENTS
 
+/// Identifier shared by every peer for the same game object, as `Entity` ids are process local.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NetId(pub u32);
+
 #[derive(Component)]
 pub struct Ship;
 
@@ -19,18 +51,40 @@ pub struct Fighter;
 #[derive(Component)]
 pub struct Trader;
 
+/// Heavy gunship, unlocked by research.
+#[derive(Component)]
+pub struct Dreadnought;
+
+/// What is left of a ship's hull. The ship is lost when it reaches 0.
+#[derive(Component)]
+pub struct Hull(pub f32);
+
+/// Distance up to which a ship sees. Planets see according to their type and buildings.
+#[derive(Component)]
+pub struct SensorRange(pub f32);
+
 #[derive(Component)]
 pub struct Bullet {
     pub origin: Vec3,
     pub distance: f32,
 }
 
+/// Base speed of a ship. Read `ShipStats` for the speed it actually flies at.
 #[cfg_attr(feature = "debug", derive(bevy_inspector_egui::Inspectable))]
 #[derive(Component)]
 pub struct Movement {
     pub speed: f32,
 }
 
+/// Effective stats of a ship, worked out from its base values and its owner's research by
+/// `update_ship_stats`.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct ShipStats {
+    pub speed: f32,
+    /// Weapon range, 0 for unarmed ships.
+    pub range: f32,
+}
+
 #[cfg_attr(feature = "debug", derive(bevy_inspector_egui::Inspectable))]
 #[derive(Component)]
 pub struct Avoidance {
@@ -38,35 +92,177 @@ pub struct Avoidance {
     // pub max_see_ahead: f32,
 }
 
+/// Steering weights of a ship, used by `collision_avoidance`.
+#[derive(Component)]
+pub struct Flocking(pub FlockWeights);
+
 #[derive(Component)]
 pub struct Destination(pub DestinationEnum);
 
+/// Ships moving together under one order. Lives on its own entity, members point back to it
+/// with `FleetMember`.
+#[derive(Component)]
+pub struct Fleet {
+    pub members: Vec<Entity>,
+    pub formation: Formation,
+    pub target: Vec3,
+    /// Speed of the slowest member, when the formation is held.
+    pub speed: Option<f32>,
+    /// Centre of the formation. A held formation moves it towards `target` at `speed`, and its
+    /// ships keep station around it.
+    pub anchor: Vec3,
+}
+
+#[derive(Component)]
+pub struct FleetMember(pub Entity);
+
+/// Offset of a fleet member's slot from the centre of its formation.
+#[derive(Component)]
+pub struct FormationSlot(pub Vec3);
+
+/// Waypoints around obstacles on the way to `Destination`, nearest first.
+/// The destination itself is not included.
+#[derive(Component, Default)]
+pub struct PlannedPath(pub Vec<Vec3>);
+
+/// Hyperlane taken at the end of the planned path, from the first planet to the second.
+#[derive(Component, Default)]
+pub struct LaneJump(pub Option<(Entity, Entity)>);
+
 #[derive(Clone, Debug)]
 pub enum DestinationEnum {
     None,
     Space(Vec3),
     Planet { planet: Entity, loc: Vec3 },
 }
+impl DestinationEnum {
+    pub fn loc(&self) -> Option<Vec3> {
+        match self {
+            DestinationEnum::None => None,
+            DestinationEnum::Space(loc) | DestinationEnum::Planet { loc, .. } => Some(*loc),
+        }
+    }
+}
 
-#[derive(Component)]
-pub struct TradeRoute {
-    pub route_loop: Option<Vec<DestinationEnum>>,
-    pub route_size: usize,
-    pub route_pos: usize,
+/// Orders that follow the current `Destination`, next first. A looping queue puts every order
+/// back at its end once started, which is how trade routes run.
+#[derive(Component, Default)]
+pub struct OrderQueue {
+    pub orders: VecDeque<DestinationEnum>,
+    pub looping: bool,
+}
+
+/// Standing order of a ship, deciding whether and where it fights.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub enum ShipOrder {
+    /// Plain movement, ignoring enemies.
+    Move,
+    /// Loops through its order queue, stopping to fight enemies met on the way.
+    Patrol,
+    /// Stays near a planet or ship and intercepts attackers coming close to it.
+    Guard(Entity),
+    /// Moves, stopping to fight anything hostile in range.
+    AttackMove,
+    /// Stays put and fires at enemies in range.
+    Hold,
 }
-impl Default for TradeRoute {
+impl Default for ShipOrder {
     fn default() -> Self {
-        Self {
-            route_loop: None,
-            route_size: 0,
-            route_pos: 0,
+        ShipOrder::Move
+    }
+}
+impl ShipOrder {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShipOrder::Move => "move",
+            ShipOrder::Patrol => "patrol",
+            ShipOrder::Guard(_) => "guard",
+            ShipOrder::AttackMove => "attack-move",
+            ShipOrder::Hold => "hold",
         }
     }
 }
 
+/// Enemy ship being fought, if any. Engaged ships leave their destination for later.
+#[derive(Component, Default)]
+pub struct Engagement {
+    pub target: Option<Entity>,
+    pub loc: Vec3,
+    /// Guards close in on their target, other orders fight from where they stopped.
+    pub chase: bool,
+}
+
+/// Automatic gun of a fighter or dreadnought.
+#[derive(Component)]
+pub struct Weapon {
+    /// Base range. Read `ShipStats` for the range the ship actually fires at.
+    pub range: f32,
+    /// Seconds between two shots.
+    pub reload: f32,
+    /// Seconds left before the next shot.
+    pub cooldown: f32,
+}
+
+/// Stored fighters of a planet flying in orbit around it. How many there are is still
+/// `Planet::fighters`; this only holds where they are along the orbit.
+#[derive(Component, Default)]
+pub struct Garrison {
+    /// Radians the orbit has turned.
+    pub phase: f32,
+    /// Seconds left before the next interception.
+    pub cooldown: f32,
+}
+
+/// Child mesh of a planet drawing its orbiting garrison.
+#[derive(Component)]
+pub struct GarrisonRing {
+    /// Number of fighters in the current mesh.
+    pub shown: u32,
+    /// Planet radius the current mesh was built for.
+    pub radius: f32,
+    pub neutral: Handle<StandardMaterial>,
+}
+
+/// Mesh showing the pending orders of the selected ships.
+#[derive(Component)]
+pub struct OrderLines;
+
+/// Mesh of the hyperlanes the local player can or cannot use.
+#[derive(Component)]
+pub struct HyperlaneLines {
+    pub open: bool,
+}
+
+/// Fight for a planet between its defenders and the fighters sitting in its orbit.
+#[derive(Component, Default)]
+pub struct Siege {
+    /// Player taking the planet once `progress` reaches 1.
+    pub capturer: Option<Uuid>,
+    pub progress: f32,
+    /// Fraction of a defender the planet has lost so far.
+    pub wear: f32,
+    /// Fractions of a fighter each attacking player has lost so far.
+    pub losses: HashMap<Uuid, f32>,
+}
+
+/// Fighter in orbit of an enemy or neutral planet, waiting at `post`.
+#[derive(Component)]
+pub struct Sieging {
+    pub planet: Entity,
+    pub post: Vec3,
+}
+
+/// Mesh covering the territory of `player`.
+#[derive(Component)]
+pub struct TerritoryMesh {
+    pub player: Uuid,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ShipType {
     Trade,
     Fighter,
+    Dreadnought,
 }
 
 #[cfg_attr(feature = "debug", derive(bevy_inspector_egui::Inspectable))]
@@ -76,7 +272,26 @@ pub struct Planet {
     pub planet_type: PlanetType,
 }
 
+/// Name of a planet, drawn from the match seed and its `NetId`.
+#[derive(Component)]
+pub struct PlanetName(pub String);
+
+/// Planet with a `PlanetMaterial` of its own, and the seconds left on its under-attack pulse.
+#[derive(Component, Default)]
+pub struct PlanetLook {
+    pub attacked: f32,
+}
+
+/// Text mesh with the planet name, above its fighter count.
+#[derive(Component)]
+pub struct PlanetLabel;
+
+/// Ids of the buildings on a planet, indexing `Balance::buildings`.
+#[derive(Component, Clone, Debug, Default, PartialEq)]
+pub struct Buildings(pub Vec<u8>);
+
 #[cfg_attr(feature = "debug", derive(bevy_inspector_egui::Inspectable))]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PlanetType {
     Outpost,
     Watch,
@@ -103,6 +318,75 @@ impl Distribution<PlanetType> for Standard {
     }
 }
 
+/// Planet type an upgrade turns `pt` into, if it can be upgraded.
+pub fn next_planet_type(pt: &PlanetType) -> Option<PlanetType> {
+    match pt {
+        PlanetType::Outpost => Some(PlanetType::Watch),
+        PlanetType::Watch => Some(PlanetType::Base),
+        PlanetType::Base => Some(PlanetType::Colony),
+        PlanetType::Colony | PlanetType::Capital => None,
+    }
+}
+
+/// Fighters an owned planet adds to its garrison every production tick, before buildings.
+pub fn planet_type_to_production(pt: &PlanetType) -> f32 {
+    match pt {
+        PlanetType::Outpost | PlanetType::Watch => 1.,
+        PlanetType::Base | PlanetType::Colony | PlanetType::Capital => 2.,
+    }
+}
+
+/// How hard a planet is to take: slows down its losses and its capture, and strengthens its
+/// defenders.
+pub fn planet_type_to_defense(pt: &PlanetType) -> f32 {
+    match pt {
+        PlanetType::Outpost => 1.,
+        PlanetType::Watch => 1.5,
+        PlanetType::Base => 2.,
+        PlanetType::Colony => 2.5,
+        PlanetType::Capital => 4.,
+    }
+}
+
+/// Distance from its centre up to which a planet sees, before buildings.
+pub fn planet_type_to_sensor(pt: &PlanetType) -> f32 {
+    match pt {
+        PlanetType::Outpost => 35.,
+        PlanetType::Watch => 100.,
+        PlanetType::Base => 45.,
+        PlanetType::Colony => 50.,
+        PlanetType::Capital => 60.,
+    }
+}
+
+/// Distance from its centre up to which an empty planet holds territory. Garrisons push it
+/// further out.
+pub fn planet_type_to_influence(pt: &PlanetType) -> f32 {
+    match pt {
+        PlanetType::Outpost => 20.,
+        PlanetType::Watch => 25.,
+        PlanetType::Base => 30.,
+        PlanetType::Colony => 40.,
+        PlanetType::Capital => 50.,
+    }
+}
+
+pub fn ship_type_to_hull(st: &ShipType) -> f32 {
+    match st {
+        ShipType::Fighter => 1.,
+        ShipType::Trade => 1.5,
+        ShipType::Dreadnought => 4.,
+    }
+}
+
+pub fn ship_type_to_sensor(st: &ShipType) -> f32 {
+    match st {
+        ShipType::Fighter => 35.,
+        ShipType::Trade => 30.,
+        ShipType::Dreadnought => 60.,
+    }
+}
+
 pub fn planet_type_to_radius(pt: &PlanetType) -> f32 {
     match pt {
         PlanetType::Outpost => 2.,
@@ -0,0 +1,147 @@
+use bevy::{prelude::*, utils::Uuid};
+
+use super::characteristics::{DestinationEnum, ShipType};
+use super::players::DiplomacyAction;
+use crate::game::utils::formation::Formation;
+
+// EVENTS
+/// Command issued by the local player. The net layer decides when it gets executed.
+pub struct IssueCommand(pub PlayerCommand);
+
+/// Command handed over by the net layer on behalf of `player`, not yet authorized.
+pub struct ReceivedCommand {
+    pub player: Uuid,
+    pub command: PlayerCommand,
+}
+
+/// Command that must be applied to the simulation on behalf of `player`.
+/// Every entity it orders is owned by `player`.
+pub struct ExecuteCommand {
+    pub player: Uuid,
+    pub command: PlayerCommand,
+}
+
+/// Everything a player can order. All simulation changes caused by input must go through here,
+/// so that every peer applies the same orders at the same turn.
+#[derive(Clone, Debug)]
+pub enum PlayerCommand {
+    MoveShips {
+        ships: Vec<Entity>,
+        destination: DestinationEnum,
+        formation: Formation,
+        hold: bool,
+    },
+    /// Same as `MoveShips`, but the ships stop to fight enemies in range.
+    AttackMove {
+        ships: Vec<Entity>,
+        destination: DestinationEnum,
+        formation: Formation,
+        hold: bool,
+    },
+    /// Loops through `points` and back to where each ship started.
+    Patrol {
+        ships: Vec<Entity>,
+        points: Vec<Vec3>,
+    },
+    Guard {
+        ships: Vec<Entity>,
+        target: Entity,
+    },
+    HoldPosition {
+        ships: Vec<Entity>,
+    },
+    /// Appends a destination to the order queue of the ships.
+    QueueDestination {
+        ships: Vec<Entity>,
+        destination: DestinationEnum,
+    },
+    /// Launches fighters from the planets. Each planet keeps a minimum garrison back.
+    DeployFighters {
+        planets: Vec<Entity>,
+        destination: DestinationEnum,
+        amount: DeployAmount,
+    },
+    SetTradeRoute {
+        ships: Vec<Entity>,
+        route: Vec<DestinationEnum>,
+    },
+    /// Upgrades each planet to the next planet type, as long as the money lasts.
+    UpgradePlanets {
+        planets: Vec<Entity>,
+    },
+    /// Builds the building with id `building` on each planet with a free slot, as long as the
+    /// money lasts.
+    Build {
+        planets: Vec<Entity>,
+        building: u8,
+    },
+    /// Buys a ship of `ship_type` at each planet, as long as the money lasts. Fighters are produced
+    /// and cannot be bought.
+    BuyShip {
+        planets: Vec<Entity>,
+        ship_type: ShipType,
+    },
+    /// Starts researching technology `tech`, if nothing else is being researched.
+    Research {
+        tech: u8,
+    },
+    /// Gathers the ships into the fleet holding most of them, or into a new fleet taking
+    /// `formation` where they are when none of them is in one.
+    MergeFleets {
+        ships: Vec<Entity>,
+        formation: Formation,
+        hold: bool,
+    },
+    /// Takes the ships out of their fleets.
+    SplitFleet {
+        ships: Vec<Entity>,
+    },
+    Chat {
+        text: String,
+    },
+    Diplomacy {
+        to: Uuid,
+        action: DiplomacyAction,
+    },
+}
+
+/// How many fighters a deployment sends.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeployAmount {
+    /// Percentage of what each planet can spare.
+    Share(u8),
+    /// Total over all the planets, split in proportion to what each can spare.
+    Exact(u32),
+}
+
+impl PlayerCommand {
+    /// Commands that do not touch the simulation. A dedicated server relays them to every client.
+    pub fn is_social(&self) -> bool {
+        matches!(
+            self,
+            PlayerCommand::Chat { .. } | PlayerCommand::Diplomacy { .. }
+        )
+    }
+
+    /// Ships or planets being ordered, if the command orders any.
+    pub fn units(&self) -> Option<&Vec<Entity>> {
+        match self {
+            PlayerCommand::MoveShips { ships, .. }
+            | PlayerCommand::AttackMove { ships, .. }
+            | PlayerCommand::Patrol { ships, .. }
+            | PlayerCommand::Guard { ships, .. }
+            | PlayerCommand::HoldPosition { ships }
+            | PlayerCommand::QueueDestination { ships, .. } => Some(ships),
+            PlayerCommand::DeployFighters { planets, .. }
+            | PlayerCommand::UpgradePlanets { planets }
+            | PlayerCommand::Build { planets, .. }
+            | PlayerCommand::BuyShip { planets, .. } => Some(planets),
+            PlayerCommand::SetTradeRoute { ships, .. }
+            | PlayerCommand::MergeFleets { ships, .. }
+            | PlayerCommand::SplitFleet { ships } => Some(ships),
+            PlayerCommand::Research { .. }
+            | PlayerCommand::Chat { .. }
+            | PlayerCommand::Diplomacy { .. } => None,
+        }
+    }
+}
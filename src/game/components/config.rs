@@ -9,6 +9,8 @@ impl Plugin for ConfigPlugin {
                 starting_resources: 500,
                 epoch_seconds: 3, // BUG: this is not linked to the fixed time system
                 galaxy_size: Galaxy::Tiny,
+                orbiting_garrison: false,
+                map: std::env::var("GALACTIC_WARS_MAP").ok(),
             });
     }
 }
@@ -49,6 +51,11 @@ pub struct InitGameSetup {
     pub starting_resources: u32,
     pub epoch_seconds: u32,
     pub galaxy_size: Galaxy,
+    /// Show stored fighters orbiting their planet, where they also intercept enemy ships.
+    pub orbiting_garrison: bool,
+    /// Map file listing the galaxy features, from `GALACTIC_WARS_MAP`. Every peer needs the
+    /// same file. Features are drawn from the match seed when there is none.
+    pub map: Option<String>,
 }
 
 #[derive(Default)]
@@ -1,3 +1,4 @@
 pub mod characteristics;
+pub mod commands;
 pub mod config;
 pub mod players;
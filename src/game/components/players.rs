@@ -8,6 +8,7 @@ pub struct Ownership(pub Option<Uuid>);
 
 pub struct PlayerDetails {
     pub name: String,
+    pub slot: u8,
     pub color: Handle<StandardMaterial>,
     pub new_color: Handle<PlanetMaterial>,
 }
@@ -21,6 +22,20 @@ pub struct Neutral;
 #[derive(Component)]
 pub struct Enemy;
 
+// EVENTS
+pub struct DiplomacyEvent {
+    pub from: Uuid,
+    pub to: Uuid,
+    pub action: DiplomacyAction,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiplomacyAction {
+    ProposeAlliance,
+    AcceptAlliance,
+    DeclareWar,
+}
+
 pub enum AllegianceStatus {
     Friend,
     Neutral,
@@ -34,3 +49,21 @@ pub enum AllegianceCollisionGroups {
 }
 
 pub const INTERACT: InteractionGroups = InteractionGroups::new(1, 1);
+
+pub fn slot_to_color(slot: u8) -> Color {
+    match slot {
+        0 => Color::BLUE,
+        1 => Color::RED,
+        2 => Color::GREEN,
+        _ => Color::YELLOW,
+    }
+}
+
+pub fn slot_to_planet_image(slot: u8) -> &'static str {
+    match slot {
+        0 => "img/Planet2_7.png",
+        1 => "img/Planet_59.png",
+        2 => "img/Planet2_15.png",
+        _ => "img/Planet2_40.png",
+    }
+}
@@ -1,41 +1,50 @@
 pub mod components;
 pub mod obj;
 pub mod resources;
-mod systems;
+pub mod systems;
 pub mod utils;
 
-use std::{f32::consts::PI, time::Duration};
+use std::f32::consts::PI;
 
 use bevy::{
-    pbr::RenderMaterials,
     prelude::*,
-    render::{
-        render_resource::{encase, OwnedBindingResource, ShaderType},
-        renderer::RenderQueue,
-        Extract, RenderApp, RenderStage,
-    },
     utils::{HashMap, Uuid},
 };
 use bevy_rapier3d::prelude::*;
 use bevy_text_mesh::prelude::*;
 use iyes_loopless::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
-use crate::{assets::materials::PlanetMaterial, selection::components::Selected, state::GameState};
+use crate::{
+    assets::materials::PlanetMaterial,
+    net::components::NetMode,
+    selection::components::Selectable,
+    state::GameState,
+};
 
 use components::{
     characteristics::*,
+    commands::*,
     config::*,
-    players::{AllegianceStatus, PlayerDetails},
+    players::{
+        slot_to_color, slot_to_planet_image, AllegianceStatus, DiplomacyEvent, PlayerDetails,
+    },
 };
-use obj::{spawn_planet, spawn_ship};
-use resources::{game_obj_res::*, game_status_res::*, player_res::*};
+use obj::{spawn_feature, spawn_planet, spawn_ship};
+use resources::{game_obj_res::*, game_status_res::*, log_res::*, player_res::*};
 use systems::*;
 
 use self::{
     components::config,
+    utils::balance::Balance,
+    utils::features::{self, Feature},
+    utils::influence::InfluenceMap,
     utils::layers_util::{get_z, Layers},
 };
 
+/// Random positions tried for a capital before features stop being avoided.
+const MAX_FEATURE_TRIES: u32 = 100;
+
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
@@ -48,64 +57,260 @@ impl Plugin for GamePlugin {
             .insert_resource(TotalTraders(0))
             .insert_resource(TotalDreadnoughts(0))
             .insert_resource(TotalPlanets(0))
-            .insert_resource(MovingFleets(HashMap::new()))
+            .insert_resource(NetIdAllocator::default())
+            .insert_resource(NetIdMap::default())
+            .insert_resource(SpatialIndex::default())
+            .insert_resource(InfluenceMap::new(TERRITORY_CELL_SIZE))
+            .insert_resource(GalaxyFeatures::default())
+            .insert_resource(Hyperlanes::default())
             // game global resources
             .insert_resource(GameStatus(GameStatusEnum::Uninitialized))
+            .insert_resource(MatchSetup::offline())
             .insert_resource(IsTradeRouting{ key_down: false, trade_route: Vec::new() })
+            .insert_resource(FormationChoice::default())
+            .insert_resource(PendingOrder::default())
+            .insert_resource(DeployChoice::default())
+            .insert_resource(Balance::default())
             // player resources
             .insert_resource(RegisteredPlayers(HashMap::new()))
             .insert_resource(AllegiancesToOthers(HashMap::new()))
             .insert_resource(PlayerMoney(HashMap::new()))
+            .insert_resource(PlayerResearch::default())
+            .insert_resource(ResearchView::default())
+            .insert_resource(PlayerVision::default())
+            .insert_resource(GameLog::default())
+            .insert_resource(ChatInput::default())
             .add_event::<TakeOwnership>()
+            .add_event::<ShipDestroyed>()
+            .add_event::<PlanetAttacked>()
+            .add_event::<ResearchCompleted>()
+            .add_event::<DiplomacyEvent>()
             .add_event::<ArrivedAtDestination>()
-            .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
-            .add_plugin(TextMeshPlugin)
-            .add_startup_system(setup)
-            .add_stage_before(
-                CoreStage::Update,
-                "fighter_producer_tick",
-                FixedTimestepStage::new(Duration::from_secs_f32(2.0)) //TODO: this should be configurable
-                    .with_stage(SystemStage::parallel().with_system(production::production_tick)),
+            .add_event::<IssueCommand>()
+            .add_event::<ReceivedCommand>()
+            .add_event::<ExecuteCommand>()
+            .insert_resource(SimClock::default())
+            .add_plugin(
+                RapierPhysicsPlugin::<NoUserData>::default().with_default_system_setup(false),
             )
+            .add_plugin(TextMeshPlugin)
+            .add_startup_system(fixed_physics_step)
+            .add_enter_system(GameState::InGame, setup)
+            .add_enter_system(GameState::InGame, event_log::log_match_start)
+            // ships spawned from snapshots, outside the simulation
+            .add_system(track_net_ids)
+            // player input, turned into commands
             .add_system_set(
                 ConditionSet::new()
                     .run_in_state(GameState::InGame)
-                    .with_system(production::fighter_enters_planet)
+                    .run_if(has_player_input)
                     .with_system(production::deploy_fighters)
-                    .with_system(production::update_count_mesh)
-                    .with_system(production::take_planet_ownership)
-                    .with_system(movement::turn_to_destination)
-                    .with_system(movement::move_to_destination)
+                    .with_system(production::choose_deploy_amount)
+                    .with_system(production::planet_orders)
+                    .with_system(research::research_hotkeys)
                     .with_system(movement::set_destination)
-                    .with_system(movement::remove_destination)
-                    .with_system(movement::damping_shift)
-                    .with_system(movement::collision_avoidance)
+                    .with_system(movement::choose_formation)
+                    .with_system(fleet::fleet_orders)
+                    .with_system(orders::order_hotkeys)
                     .with_system(movement::define_trade_route)
-                    .with_system(movement::update_trade_destination)
-                    .with_system(combat::bullet_hit)
-                    .with_system(combat::fire_bullet)
-                    .with_system(combat::despawn_bullet)
+                    .with_system(movement::draw_order_lines)
+                    .with_system(garrison::draw_garrisons)
+                    .with_system(vision::draw_fog)
+                    .with_system(planet_material::setup_planet_materials)
+                    .with_system(planet_material::draw_planets)
+                    .with_system(territory::draw_territory)
+                    .with_system(hyperlane::draw_hyperlanes)
+                    .with_system(event_log::chat_input)
+                    .with_system(event_log::scroll_log)
+                    .into(),
+            )
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::InGame)
+                    .with_system(production::update_count_mesh)
+                    .with_system(production::name_planets)
                     // this should be moved to a system set that runs at the end of frame
                     .with_system(production::count_fighters_deployed)
                     .with_system(production::count_fighters_stored)
                     .with_system(production::count_traders)
+                    .with_system(production::count_dreadnoughts)
                     .into(),
-            );
-
-        app.sub_app_mut(RenderApp)
-            .add_system_to_stage(RenderStage::Extract, extract_my_material)
-            .add_system_to_stage(RenderStage::Prepare, prepare_my_material);
+            )
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::InGame)
+                    .run_if_resource_exists::<LocalPlayer>()
+                    .with_system(event_log::apply_diplomacy)
+                    .with_system(event_log::log_chat)
+                    .with_system(event_log::log_diplomacy)
+                    .with_system(event_log::log_ownership_changes)
+                    .with_system(event_log::log_ships_destroyed)
+                    .with_system(event_log::log_planet_attacks)
+                    .with_system(event_log::log_research_completed)
+                    .with_system(research::update_research_view)
+                    .into(),
+            )
+            .add_stage_after(CoreStage::Update, "simulation", SimStage(sim_schedule()));
 
         #[cfg(feature = "debug")]
         app.add_plugin(RapierDebugRenderPlugin::default());
     }
 }
 
+/// Runs its schedule once per tick queued in `SimClock`, after handing the tick its commands.
+/// Peers that queue the same ticks with the same commands compute the same game, whatever
+/// their frame rate. Events are only swapped once per frame, so the simulation reads each of
+/// them in the tick that sends it.
+struct SimStage(Schedule);
+impl Stage for SimStage {
+    fn run(&mut self, world: &mut World) {
+        loop {
+            let next = world.resource_mut::<SimClock>().ticks.pop_front();
+            let commands = match next {
+                Some(commands) => commands,
+                None => return,
+            };
+            let mut received = world.resource_mut::<Events<ReceivedCommand>>();
+            for command in commands {
+                received.send(command);
+            }
+            self.0.run(world);
+            // every reader of these runs later in the tick that sends them, after which they
+            // must not leak into the next tick, whenever that one comes
+            world.resource_mut::<Events<ReceivedCommand>>().clear();
+            world.resource_mut::<Events<ArrivedAtDestination>>().clear();
+            world.resource_mut::<Events<CollisionEvent>>().clear();
+        }
+    }
+}
+
+/// Adds each system as a stage of its own, in the listed order: a parallel stage would run
+/// them in whatever order its executor picks, which differs from peer to peer.
+macro_rules! add_in_order {
+    ($schedule:expr, [$($system:path),* $(,)?]) => {
+        $(
+            $schedule.add_stage(
+                stringify!($system),
+                SystemStage::single($system.run_in_state(GameState::InGame)),
+            );
+        )*
+    };
+    ($schedule:expr, [$($system:path),* $(,)?], $condition:expr) => {
+        $(
+            $schedule.add_stage(
+                stringify!($system),
+                SystemStage::single($system.run_in_state(GameState::InGame).run_if($condition)),
+            );
+        )*
+    };
+}
+
+/// One simulation tick: the commands of the tick are authorized and applied, the game advances
+/// by `SIM_DT`, then physics steps by the same amount.
+fn sim_schedule() -> Schedule {
+    let physics = |stage| {
+        SystemStage::parallel()
+            .with_system_set(RapierPhysicsPlugin::<NoUserData>::get_systems(stage))
+    };
+    let mut schedule = Schedule::default();
+    schedule.add_stage("sim_spatial_index", SystemStage::single(update_spatial_index));
+    // received commands are checked against ownership before anything applies them
+    add_in_order!(schedule, [authorization::authorize_commands]);
+    // simulation, skipped by clients of a dedicated server
+    add_in_order!(
+        schedule,
+        [
+            production::apply_deploy_fighters,
+            production::apply_planet_orders,
+            production::apply_buy_ships,
+            production::production_tick,
+            research::apply_research,
+            research::advance_research,
+            fleet::apply_move_ships,
+            fleet::apply_merge_fleets,
+            fleet::apply_split_fleet,
+            orders::apply_orders,
+            movement::apply_trade_route,
+            movement::apply_queue_destination,
+            movement::advance_order_queue,
+            fleet::update_fleets,
+            movement::plan_paths,
+            hyperlane::travel_hyperlanes,
+            movement::turn_to_destination,
+            movement::move_to_destination,
+            movement::damping_shift,
+            movement::collision_avoidance,
+            orders::engage_hostiles,
+            combat::fire_at_targets,
+            garrison::intercept_attackers,
+            siege::siege_planets,
+            environment::hazard_damage,
+            combat::bullet_hit,
+            combat::despawn_bullet,
+        ],
+        simulates
+    );
+    // state every peer derives from the board, clients included
+    add_in_order!(
+        schedule,
+        [
+            production::resize_planets,
+            research::update_ship_stats,
+            hyperlane::link_hyperlanes,
+            garrison::setup_garrisons,
+            garrison::orbit_garrisons,
+        ]
+    );
+    schedule
+        // physics reads global transforms, which the frame only propagates once
+        .add_stage(
+            "sim_transforms",
+            SystemStage::single(bevy::transform::systems::transform_propagate_system),
+        )
+        .add_stage(PhysicsStages::SyncBackend, physics(PhysicsStages::SyncBackend))
+        .add_stage(PhysicsStages::StepSimulation, physics(PhysicsStages::StepSimulation))
+        .add_stage(PhysicsStages::Writeback, physics(PhysicsStages::Writeback));
+    // collisions are handled in the tick physics reports them, before it looks for despawns
+    add_in_order!(schedule, [production::fighter_enters_planet], simulates);
+    schedule.add_stage(PhysicsStages::DetectDespawn, physics(PhysicsStages::DetectDespawn));
+    // captures apply in the tick they happen, and vision follows the tick's board
+    add_in_order!(
+        schedule,
+        [
+            production::take_planet_ownership,
+            territory::update_territory,
+            vision::update_vision,
+        ]
+    );
+    // the next tick's commands resolve net ids against this tick's ships, whatever the frame
+    schedule.add_stage("sim_net_ids", SystemStage::single(track_net_ids));
+    schedule
+}
+
+/// Rapier steps once per tick, by the same amount as the rest of the simulation.
+fn fixed_physics_step(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.timestep_mode = TimestepMode::Fixed {
+        dt: SIM_DT,
+        substeps: 1,
+    };
+}
+
+fn simulates(net_mode: Res<NetMode>) -> bool {
+    net_mode.simulates()
+}
+
+fn has_player_input(net_mode: Res<NetMode>) -> bool {
+    !net_mode.is_server()
+}
+
 fn setup(
     asset_server: Res<AssetServer>,
     board_params: Res<InitGameSetup>,
+    match_setup: Res<MatchSetup>,
+    net_mode: Res<NetMode>,
     mut players: ResMut<RegisteredPlayers>,
     mut game_status: ResMut<GameStatus>,
+    mut net_ids: ResMut<NetIdAllocator>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
@@ -113,6 +318,12 @@ fn setup(
     mut money: ResMut<PlayerMoney>,
     mut allegiances_to_others: ResMut<AllegiancesToOthers>,
 ) {
+    // entering InGame again after a pause must not rebuild the board
+    if !matches!(game_status.0, GameStatusEnum::Uninitialized) {
+        return;
+    }
+    let mut rng = StdRng::seed_from_u64(match_setup.seed);
+
     // commands.spawn_bundle(MaterialMeshBundle {
     //     mesh: meshes.add(Mesh::from(shape::Circle::default())).into(),
     //     transform: Transform::from_xyz(-0.6, 0.0, 0.0).with_scale(Vec3 {
@@ -125,6 +336,7 @@ fn setup(
     // });
     // setup players
     setup_players(
+        &match_setup,
         &board_params,
         &mut players,
         &mut money,
@@ -133,13 +345,30 @@ fn setup(
         &mut allegiances_to_others,
         &asset_server,
     );
+    commands.insert_resource(LocalPlayer(match_setup.local));
+    game_status.0 = GameStatusEnum::Started(match_setup.seed);
+
+    // features are not networked, every peer builds the same ones from the map or the seed
+    let galaxy_features = galaxy_features(&board_params, match_setup.seed);
+    for feature in galaxy_features.iter() {
+        spawn_feature(&mut commands, &mut meshes, &mut materials, feature);
+    }
+    commands.insert_resource(GalaxyFeatures(galaxy_features.clone()));
+    // clients of a dedicated server receive the board from the server
+    if !net_mode.simulates() {
+        return;
+    }
 
-    // Set Player starting planets
+    // Set Player starting planets. Slot order keeps placement identical on every peer.
     let mut placed_planets: Vec<Vec3> = Vec::new();
-    for (pk, pd) in players.0.iter() {
+    for slot in match_setup.players.iter() {
+        let pk = &slot.uuid;
+        let pd = players.0.get(pk).expect("lobby player was not registered");
         let mut finding_space = true;
-        let mut transf = random_planet_pos(&board_params);
+        let mut transf = random_planet_pos(&board_params, &mut rng);
+        let mut tries = 0;
         while finding_space {
+            tries += 1;
             let mut conflict_planet = None;
             for planet in placed_planets.iter() {
                 if planet.distance(transf.translation)
@@ -149,16 +378,24 @@ fn setup(
                     break;
                 }
             }
-            match conflict_planet {
+            // capitals start clear of features, unless a map leaves no room for that
+            let pos = transf.translation.truncate();
+            let margin = 2. * planet_type_to_radius(&PlanetType::Capital);
+            let in_feature = tries <= MAX_FEATURE_TRIES
+                && galaxy_features
+                    .iter()
+                    .any(|f| f.centre.distance(pos) < f.radius + margin);
+            match conflict_planet.is_some() || in_feature {
                 // keep re-running random_planet_pos while there is conflict between planets
-                Some(_) => transf = random_planet_pos(&board_params),
-                None => finding_space = false,
+                true => transf = random_planet_pos(&board_params, &mut rng),
+                false => finding_space = false,
             }
         }
         placed_planets.push(transf.translation);
         spawn_planet(
             &mut commands,
             &mut meshes,
+            &mut net_ids,
             asset_server.load("fonts/ShareTechMono.ttf"),
             // Planet config
             PlanetType::Capital,
@@ -170,6 +407,7 @@ fn setup(
         spawn_ship(
             &mut commands,
             &mut meshes,
+            &mut net_ids,
             ShipType::Trade,
             transf.with_translation(transf.translation + Vec3::new(20., 0., 0.)),
             DestinationEnum::None,
@@ -187,8 +425,8 @@ fn setup(
 
     // for _ in 0..board_params.no_of_planets {
     //     let mut finding_space = true;
-    //     let mut transf = random_planet_pos(&board_params);
-    //     let planet_type = rand::random::<PlanetType>();
+    //     let mut transf = random_planet_pos(&board_params, &mut rng);
+    //     let planet_type = rng.gen::<PlanetType>();
     //     while finding_space {
     //         let mut conflict_planet = None;
     //         for planet in placed_planets.iter() {
@@ -199,7 +437,7 @@ fn setup(
     //         }
     //         match conflict_planet {
     //             // keep re-running random_planet_pos while there is conflict between planets
-    //             Some(_) => transf = random_planet_pos(&board_params),
+    //             Some(_) => transf = random_planet_pos(&board_params, &mut rng),
     //             None => finding_space = false,
     //         }
     //     }
@@ -207,6 +445,7 @@ fn setup(
     //     spawn_planet(
     //         &mut commands,
     //         &mut meshes,
+    //         &mut net_ids,
     //         asset_server.load("fonts/ShareTechMono.ttf"),
     //         // Planet config
     //         planet_type,
@@ -217,11 +456,11 @@ fn setup(
     //     );
     // }
 
-    // let seed = 3552441;
-    // game_status.0 = GameStatusEnum::Started(seed);
+    commands.insert_resource(GameRng(rng));
 }
 
 fn setup_players(
+    match_setup: &Res<MatchSetup>,
     board_params: &Res<InitGameSetup>,
     players: &mut ResMut<RegisteredPlayers>,
     money: &mut ResMut<PlayerMoney>,
@@ -230,19 +469,9 @@ fn setup_players(
     allegiances_to_others: &mut ResMut<AllegiancesToOthers>,
     assets: &Res<AssetServer>,
 ) {
-    register_players(players, materials, my_materials, assets);
-    let me = who_am_i("Caio", &players.0).expect("Could not find player"); // TODO: this won't work in real life.
+    register_players(match_setup, players, materials, my_materials, assets);
     setup_initial_resources(money, board_params.starting_resources, &players.0);
-    setup_allegiances(me, &players.0, allegiances_to_others);
-}
-
-fn who_am_i(name: &str, players_map: &HashMap<Uuid, PlayerDetails>) -> Option<Uuid> {
-    for (k, v) in players_map.into_iter() {
-        if name.to_string() == v.name {
-            return Some(*k);
-        }
-    }
-    None
+    setup_allegiances(match_setup.local, &players.0, allegiances_to_others);
 }
 
 fn setup_allegiances(
@@ -270,92 +499,99 @@ fn setup_initial_resources(
 }
 
 fn register_players(
+    match_setup: &Res<MatchSetup>,
     players: &mut ResMut<RegisteredPlayers>,
     materials: &mut ResMut<Assets<StandardMaterial>>,
     my_materials: &mut ResMut<Assets<PlanetMaterial>>,
     assets: &Res<AssetServer>,
 ) {
-    players.0.insert(
-        Uuid::new_v4(),
-        PlayerDetails {
-            name: "Caio".to_string(),
-            color: materials
-                .add(StandardMaterial {
-                    base_color: Color::BLUE,
-                    unlit: true,
-                    ..Default::default()
-                })
-                .into(),
-            new_color: my_materials.add(PlanetMaterial {
-                color: Color::BLUE,
-                image: assets.load("img/Planet2_7.png"),
-            }),
-        },
-    );
-    players.0.insert(
-        Uuid::new_v4(),
-        PlayerDetails {
-            name: "Bob".to_string(),
-            color: materials
-                .add(StandardMaterial {
-                    base_color: Color::RED,
-                    unlit: true,
-                    ..Default::default()
-                })
-                .into(),
-            new_color: my_materials.add(PlanetMaterial {
-                color: Color::RED,
-                image: assets.load("img/Planet_59.png"),
-            }),
-        },
-    );
+    for slot in match_setup.players.iter() {
+        let color = slot_to_color(slot.slot);
+        players.0.insert(
+            slot.uuid,
+            PlayerDetails {
+                name: slot.name.clone(),
+                slot: slot.slot,
+                color: materials
+                    .add(StandardMaterial {
+                        base_color: color,
+                        unlit: true,
+                        ..Default::default()
+                    })
+                    .into(),
+                new_color: my_materials.add(PlanetMaterial::new(
+                    color,
+                    assets.load(slot_to_planet_image(slot.slot)),
+                )),
+            },
+        );
+    }
+}
+
+/// Features of the map file when there is one that reads, otherwise drawn from the seed.
+fn galaxy_features(board_params: &InitGameSetup, seed: u64) -> Vec<Feature> {
+    if let Some(path) = &board_params.map {
+        let parsed = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| features::parse(&text));
+        match parsed {
+            Ok(features) => return features,
+            Err(e) => warn!("map {}: {}, generating features instead", path, e),
+        }
+    }
+    let radius = components::config::galaxy_size_to_radius(&board_params.galaxy_size);
+    // a seed of its own, so features do not move the planets around
+    features::generate(&mut StdRng::seed_from_u64(seed.wrapping_add(1)), radius)
 }
 
-fn random_planet_pos(game_config: &Res<InitGameSetup>) -> Transform {
+fn random_planet_pos(game_config: &Res<InitGameSetup>, rng: &mut StdRng) -> Transform {
     let z = get_z(Layers::Planets);
     let radius = components::config::galaxy_size_to_radius(&game_config.galaxy_size);
-    let dist = rand::random::<f32>();
-    let angle = rand::random::<f32>() * PI * 2.0;
+    let dist = rng.gen::<f32>();
+    let angle = rng.gen::<f32>() * PI * 2.0;
     let x = angle.cos() * dist * radius;
     let y = angle.sin() * dist * radius;
     Transform::from_xyz(x, y, z)
 }
 
-fn prepare_my_material(
-    materials: Res<RenderMaterials<PlanetMaterial>>,
-    selected_query: Query<(&Selected, &Handle<PlanetMaterial>)>,
-    render_queue: Res<RenderQueue>,
+/// Maps new net ids to their entity and forgets the ones whose entity is gone. Despawns are
+/// swept rather than read from `RemovedComponents`, which only covers the current frame.
+fn track_net_ids(
+    mut net_id_map: ResMut<NetIdMap>,
+    added: Query<(Entity, &NetId), Added<NetId>>,
+    alive: Query<(), With<NetId>>,
 ) {
-    for (_, handle) in &selected_query {
-        if let Some(material) = materials.get(handle) {
-            for binding in material.bindings.iter() {
-                if let OwnedBindingResource::Buffer(cur_buffer) = binding {
-                    let mut buffer = encase::UniformBuffer::new(Vec::new());
-                    buffer
-                        .write(&CoolMaterialUniformData {
-                            color: Color::YELLOW,
-                        })
-                        .unwrap();
-                    render_queue.write_buffer(cur_buffer, 0, buffer.as_ref());
-                }
-            }
-        }
+    for (entity, net_id) in added.iter() {
+        net_id_map.0.insert(*net_id, entity);
     }
+    net_id_map.0.retain(|_, entity| alive.contains(*entity));
 }
 
-fn extract_my_material(
-    mut commands: Commands,
-    health_query: Extract<Query<(Entity, &Selected, &Handle<PlanetMaterial>)>>,
+/// Re-indexes entities that spawned or moved since the last tick, and planets that may have
+/// changed size. Lookups are ordered by `NetId`, the same on every peer.
+pub fn update_spatial_index(
+    mut index: ResMut<SpatialIndex>,
+    planets: Query<
+        (Entity, &NetId, &Planet, &Transform),
+        Or<(Changed<Transform>, Changed<Planet>)>,
+    >,
+    moved: Query<(Entity, Option<&NetId>, &Transform), (With<Selectable>, Changed<Transform>)>,
+    selectables: Query<(), With<Selectable>>,
+    all_planets: Query<(), With<Planet>>,
 ) {
-    for (entity, selected, handle) in health_query.iter() {
-        commands
-            .get_or_spawn(entity)
-            .insert(*selected)
-            .insert(handle.clone());
+    // despawns are swept, `RemovedComponents` misses those of the previous frame's last tick
+    index.planets.retain(|entity| all_planets.contains(entity));
+    index.selectables.retain(|entity| selectables.contains(entity));
+    for (entity, net_id, planet, transf) in planets.iter() {
+        let radius = planet_type_to_radius(&planet.planet_type);
+        index
+            .planets
+            .insert(entity, net_id.0, transf.translation.truncate(), radius);
+    }
+    for (entity, net_id, transf) in moved.iter() {
+        let order = net_id.map_or(u32::MAX, |id| id.0);
+        index
+            .selectables
+            .insert(entity, order, transf.translation.truncate(), 0.);
     }
-}
-
-#[derive(Clone, ShaderType)]
-struct CoolMaterialUniformData {
-    color: Color,
 }
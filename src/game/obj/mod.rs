@@ -10,6 +10,10 @@ use super::components::{
     characteristics::*,
     players::{Ownership, PlayerDetails},
 };
+use super::resources::game_obj_res::NetIdAllocator;
+use super::utils::features::{Feature, FeatureKind};
+use super::utils::flocking::FlockWeights;
+use super::utils::formation::Formation;
 use super::utils::layers_util::{get_z, Layers};
 use crate::{assets::materials::PlanetMaterial, selection::components::Selectable};
 
@@ -17,6 +21,7 @@ use crate::{assets::materials::PlanetMaterial, selection::components::Selectable
 pub fn spawn_planet(
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
+    net_ids: &mut ResMut<NetIdAllocator>,
     font: Handle<TextMeshFont>,
     planet_type: PlanetType,
     transform: Transform,
@@ -34,8 +39,11 @@ pub fn spawn_planet(
             fighters: no_fighters,
             planet_type,
         })
+        .insert(Siege::default())
+        .insert(Buildings::default())
         .insert(Selectable)
         .insert(Ownership(ownership))
+        .insert(net_ids.next())
         // ADD TEXT3D OVERLAY WITH BEVY_TEXT_MESH: https://crates.io/crates/bevy_text_mesh
         .with_children(|parent| {
             parent.spawn_bundle(TextMeshBundle {
@@ -66,22 +74,70 @@ fn generate_planet_mesh(
     transform: Transform,
     meshes: &mut ResMut<Assets<Mesh>>,
 ) -> MaterialMeshBundle<PlanetMaterial> {
-    let mesh = Mesh::from(shape::Circle {
-        radius,
-        ..default()
-    });
     MaterialMeshBundle {
-        mesh: meshes.add(mesh),
+        mesh: meshes.add(planet_mesh(radius)),
         transform,
         material: planet_material,
         ..default()
     }
 }
 
+pub fn planet_mesh(radius: f32) -> Mesh {
+    Mesh::from(shape::Circle {
+        radius,
+        ..default()
+    })
+}
+
+/// Draws a galaxy feature. Black holes get a dark core inside their pull.
+pub fn spawn_feature(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    feature: &Feature,
+) -> Entity {
+    let color = match feature.kind {
+        FeatureKind::Nebula => Color::rgba(0.6, 0.3, 0.8, 0.25),
+        FeatureKind::AsteroidField => Color::rgba(0.55, 0.45, 0.35, 0.35),
+        FeatureKind::BlackHole => Color::rgba(0.2, 0.1, 0.3, 0.3),
+    };
+    let translation = feature.centre.extend(get_z(Layers::Features));
+    let entity = commands
+        .spawn_bundle(MaterialMeshBundle {
+            mesh: meshes.add(planet_mesh(feature.radius)),
+            material: materials.add(StandardMaterial {
+                base_color: color,
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            }),
+            transform: Transform::from_translation(translation),
+            ..default()
+        })
+        .insert(*feature)
+        .id();
+    if let (FeatureKind::BlackHole, Some(core)) = (feature.kind, feature.obstacle_radius()) {
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn_bundle(MaterialMeshBundle {
+                mesh: meshes.add(planet_mesh(core)),
+                material: materials.add(StandardMaterial {
+                    base_color: Color::BLACK,
+                    unlit: true,
+                    ..default()
+                }),
+                transform: Transform::from_xyz(0., 0., 0.1),
+                ..default()
+            });
+        });
+    }
+    entity
+}
+
 /// .
 pub fn spawn_ship(
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
+    net_ids: &mut ResMut<NetIdAllocator>,
     ship_type: ShipType,
     transform: Transform,
     set_destination: DestinationEnum,
@@ -110,8 +166,16 @@ pub fn spawn_ship(
             ..Default::default()
         })
         .insert(Destination(set_destination))
+        .insert(PlannedPath::default())
+        .insert(LaneJump::default())
+        .insert(OrderQueue::default())
+        .insert(ShipOrder::default())
+        .insert(Engagement::default())
+        .insert(SensorRange(ship_type_to_sensor(&ship_type)))
+        .insert(Hull(ship_type_to_hull(&ship_type)))
         .insert(Selectable)
         .insert(Ownership(Some(*player_uuid)))
+        .insert(net_ids.next())
         .insert(Ship)
         .id();
 
@@ -126,11 +190,26 @@ pub fn spawn_ship(
                     player_details,
                 ))
                 .insert(Fighter)
+                .insert(Weapon {
+                    range: 30.,
+                    reload: 1.,
+                    cooldown: 0.,
+                })
                 .insert(Avoidance {
                     impulse: Vec3::ZERO,
                     // max_see_ahead: 8.0,
                 })
-                .insert(Movement { speed: 35. });
+                .insert(Flocking(FlockWeights {
+                    radius: 4.,
+                    separation: 1.5,
+                    alignment: 0.6,
+                    cohesion: 0.4,
+                }))
+                .insert(Movement { speed: 35. })
+                .insert(ShipStats {
+                    speed: 35.,
+                    range: 30.,
+                });
         }
         ShipType::Trade => {
             commands
@@ -142,17 +221,81 @@ pub fn spawn_ship(
                     player_details,
                 ))
                 .insert(Trader)
-                .insert(TradeRoute::default())
                 .insert(Avoidance {
                     impulse: Vec3::ZERO,
                     // max_see_ahead: 4.0,
                 })
-                .insert(Movement { speed: 12. });
+                // traders travel alone, they mostly keep their distance
+                .insert(Flocking(FlockWeights {
+                    radius: 3.,
+                    separation: 1.,
+                    alignment: 0.2,
+                    cohesion: 0.,
+                }))
+                .insert(Movement { speed: 12. })
+                .insert(ShipStats {
+                    speed: 12.,
+                    range: 0.,
+                });
+        }
+        ShipType::Dreadnought => {
+            commands
+                .entity(entity)
+                .insert_bundle(generate_ship_mesh(
+                    ship_type,
+                    transform,
+                    meshes,
+                    player_details,
+                ))
+                .insert(Collider::ball(1.5))
+                .insert(Dreadnought)
+                .insert(Weapon {
+                    range: 45.,
+                    reload: 0.4,
+                    cooldown: 0.,
+                })
+                .insert(Avoidance {
+                    impulse: Vec3::ZERO,
+                })
+                .insert(Flocking(FlockWeights {
+                    radius: 8.,
+                    separation: 2.,
+                    alignment: 0.6,
+                    cohesion: 0.2,
+                }))
+                .insert(Movement { speed: 15. })
+                .insert(ShipStats {
+                    speed: 15.,
+                    range: 45.,
+                });
         }
     }
     entity
 }
 
+/// Groups `members` into a fleet heading for `target`, at `speed` if it is held together.
+pub fn spawn_fleet(
+    commands: &mut Commands,
+    members: Vec<Entity>,
+    formation: Formation,
+    target: Vec3,
+    speed: Option<f32>,
+    anchor: Vec3,
+) -> Entity {
+    let fleet = commands.spawn().id();
+    for e in members.iter() {
+        commands.entity(*e).insert(FleetMember(fleet));
+    }
+    commands.entity(fleet).insert(Fleet {
+        members,
+        formation,
+        target,
+        speed,
+        anchor,
+    });
+    fleet
+}
+
 fn generate_ship_mesh(
     ship_type: ShipType,
     transform: Transform,
@@ -162,6 +305,7 @@ fn generate_ship_mesh(
     let mesh = match ship_type {
         ShipType::Fighter => ship_fighter_mesh(),
         ShipType::Trade => ship_trader_mesh(),
+        ShipType::Dreadnought => ship_dreadnought_mesh(),
     };
 
     PbrBundle {
@@ -225,6 +369,33 @@ fn ship_trader_mesh() -> Mesh {
     mesh
 }
 
+fn ship_dreadnought_mesh() -> Mesh {
+    // points are (vec3[position], vec2[uvs])
+    let points = vec![
+        ([0.0, 3.0, 0.0], [1.0, 1.0]),
+        ([-1.2, 1.5, 0.0], [0., 0.]),
+        ([-1.5, -2.0, 0.0], [0., 0.]),
+        ([0.0, -1.5, 0.0], [0.5, 0.5]),
+        ([1.5, -2.0, 0.0], [0., 0.]),
+        ([1.2, 1.5, 0.0], [0., 0.]),
+    ];
+    let mut vertices = Vec::with_capacity(points.len());
+    let mut uvs = Vec::with_capacity(points.len());
+    let normals = vec![[0.0, 0.0, 1.0]; points.len()];
+
+    for (position, uv) in points.iter() {
+        vertices.push(*position);
+        uvs.push(*uv);
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(vec![0, 1, 2, 0, 2, 3, 0, 3, 4, 0, 4, 5])));
+    mesh
+}
+
 fn bullet_mesh() -> Mesh {
     Mesh::from(shape::Capsule {
         radius: 3.,
@@ -1,5 +1,9 @@
 use bevy::{prelude::*, utils::HashMap};
 
+use crate::game::components::characteristics::NetId;
+use crate::game::utils::features::Feature;
+use crate::game::utils::spatial_grid::SpatialGrid;
+
 #[derive(Clone, PartialEq)]
 pub struct FightersDeployed(pub u32);
 
@@ -15,4 +19,49 @@ pub struct TotalDreadnoughts(pub u32);
 #[derive(Clone, PartialEq)]
 pub struct TotalPlanets(pub u32);
 
-pub struct MovingFleets(pub HashMap<String, Vec<Entity>>);
+/// Hands out `NetId`s in spawn order. Spawns are driven by executed commands, so every peer
+/// allocates the same ids.
+#[derive(Default)]
+pub struct NetIdAllocator(pub u32);
+impl NetIdAllocator {
+    pub fn next(&mut self) -> NetId {
+        self.0 += 1;
+        NetId(self.0)
+    }
+}
+
+#[derive(Default)]
+pub struct NetIdMap(pub HashMap<NetId, Entity>);
+
+/// Side of a spatial index cell. Larger than any planet, a few times a ship's reach.
+pub const SPATIAL_CELL_SIZE: f32 = 16.;
+
+/// Nebulae, asteroid fields and black holes of the galaxy. They never move.
+#[derive(Default)]
+pub struct GalaxyFeatures(pub Vec<Feature>);
+
+/// Planet pairs joined by a hyperlane, the one with the lower `NetId` first.
+#[derive(Default)]
+pub struct Hyperlanes(pub Vec<(Entity, Entity)>);
+impl Hyperlanes {
+    pub fn joins(&self, a: Entity, b: Entity) -> bool {
+        self.0.contains(&(a, b)) || self.0.contains(&(b, a))
+    }
+}
+
+/// Side of a territory cell.
+pub const TERRITORY_CELL_SIZE: f32 = 8.;
+
+/// Positions of planets and selectable entities, kept up to date from their transforms.
+pub struct SpatialIndex {
+    pub planets: SpatialGrid,
+    pub selectables: SpatialGrid,
+}
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        SpatialIndex {
+            planets: SpatialGrid::new(SPATIAL_CELL_SIZE),
+            selectables: SpatialGrid::new(SPATIAL_CELL_SIZE),
+        }
+    }
+}
@@ -1,4 +1,10 @@
-use crate::game::components::characteristics::DestinationEnum;
+use std::collections::VecDeque;
+
+use bevy::{math::Vec3, utils::Uuid};
+use rand::rngs::StdRng;
+
+use crate::game::components::{characteristics::DestinationEnum, commands::ReceivedCommand};
+use crate::game::utils::formation::Formation;
 
 pub struct GameStatus(pub GameStatusEnum);
 pub enum GameStatusEnum {
@@ -11,3 +17,121 @@ pub struct IsTradeRouting {
     pub key_down: bool,
     pub trade_route: Vec<DestinationEnum>,
 }
+
+/// Formation used by the next move orders of the local player.
+pub struct FormationChoice {
+    pub formation: Formation,
+    /// Held formations move at the speed of their slowest ship.
+    pub hold: bool,
+}
+impl Default for FormationChoice {
+    fn default() -> Self {
+        FormationChoice {
+            formation: Formation::Box,
+            hold: false,
+        }
+    }
+}
+
+/// Exact number of fighters the next deployments send, instead of a share of the garrison.
+#[derive(Default)]
+pub struct DeployChoice {
+    pub exact: Option<u32>,
+}
+
+/// Order armed by a hotkey, given to the selected ships with the next right-click.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArmedOrder {
+    Patrol,
+    AttackMove,
+    Guard,
+}
+
+#[derive(Default)]
+pub struct PendingOrder {
+    pub order: Option<ArmedOrder>,
+    /// Patrol points placed so far with Shift+right-click.
+    pub patrol: Vec<Vec3>,
+}
+
+/// Research panel of the local player, toggled with F4.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResearchView {
+    pub open: bool,
+    pub lines: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayerSlot {
+    pub uuid: Uuid,
+    pub name: String,
+    pub slot: u8,
+}
+
+/// Who plays the match and which seed generates the galaxy. Filled by the lobby in network
+/// games, so that every peer builds the same board.
+pub struct MatchSetup {
+    pub players: Vec<PlayerSlot>,
+    pub local: Uuid,
+    pub seed: u64,
+}
+impl MatchSetup {
+    pub fn offline() -> Self {
+        let me = Uuid::new_v4();
+        Self {
+            players: vec![
+                PlayerSlot {
+                    uuid: me,
+                    name: "Caio".to_string(),
+                    slot: 0,
+                },
+                PlayerSlot {
+                    uuid: Uuid::new_v4(),
+                    name: "Bob".to_string(),
+                    slot: 1,
+                },
+            ],
+            local: me,
+            seed: rand::random(),
+        }
+    }
+}
+
+/// Seeded generator for everything that must be identical across peers.
+pub struct GameRng(pub StdRng);
+
+/// Time the simulation advances by in one tick, whatever the frame rate.
+pub const SIM_DT: f32 = 1. / 60.;
+/// Ticks real time may owe the simulation at most, so a long frame does not freeze the next ones.
+const MAX_CATCH_UP_TICKS: f32 = 10.;
+
+/// Ticks the simulation stage still has to run, each with the commands that take effect right
+/// before it. Lockstep peers queue whole turns once every batch arrived; everyone else queues
+/// ticks as real time passes.
+#[derive(Default)]
+pub struct SimClock {
+    pub ticks: VecDeque<Vec<ReceivedCommand>>,
+    /// Commands waiting for the next tick.
+    pub pending: Vec<ReceivedCommand>,
+    /// Real time not simulated yet.
+    pub elapsed: f32,
+}
+impl SimClock {
+    /// Queues one tick per `SIM_DT` of real time, the first one carrying the pending commands.
+    pub fn advance(&mut self, dt: f32) {
+        self.elapsed = (self.elapsed + dt).min(MAX_CATCH_UP_TICKS * SIM_DT);
+        while self.elapsed >= SIM_DT {
+            self.elapsed -= SIM_DT;
+            let commands = std::mem::take(&mut self.pending);
+            self.ticks.push_back(commands);
+        }
+    }
+
+    /// Queues `ticks` ticks, the first one carrying `commands`.
+    pub fn queue(&mut self, ticks: u32, commands: Vec<ReceivedCommand>) {
+        self.ticks.push_back(commands);
+        for _ in 1..ticks {
+            self.ticks.push_back(Vec::new());
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use bevy::prelude::*;
+
+pub const MAX_LOG_ENTRIES: usize = 100;
+
+pub struct LogEntry {
+    /// Seconds since the match started.
+    pub seconds: f64,
+    pub text: String,
+    pub color: Color,
+}
+
+/// Game event log shown in the in-game panel. Only game events and chat write to it.
+#[derive(Default)]
+pub struct GameLog {
+    pub entries: Vec<LogEntry>,
+    /// Lines scrolled up from the most recent entry.
+    pub scroll: usize,
+    pub started_at: Option<f64>,
+}
+impl GameLog {
+    pub fn push(&mut self, now: f64, text: String, color: Color) {
+        let seconds = now - self.started_at.unwrap_or(now);
+        self.entries.push(LogEntry {
+            seconds,
+            text,
+            color,
+        });
+        if self.entries.len() > MAX_LOG_ENTRIES {
+            self.entries.remove(0);
+        }
+    }
+}
+
+/// Chat line being typed. While active, keyboard shortcuts are ignored.
+#[derive(Default)]
+pub struct ChatInput {
+    pub active: bool,
+    pub text: String,
+}
@@ -1,3 +1,4 @@
 pub mod game_obj_res;
 pub mod game_status_res;
+pub mod log_res;
 pub mod player_res;
@@ -1,12 +1,59 @@
 use super::super::components::players::*;
 use bevy::{
     prelude::*,
-    utils::{HashMap, Uuid},
+    utils::{HashMap, HashSet, Uuid},
 };
 
 pub struct RegisteredPlayers(pub HashMap<Uuid, PlayerDetails>);
 pub struct PlayersColor(pub HashMap<Uuid, Handle<StandardMaterial>>);
 pub struct PlayerMoney(pub HashMap<Uuid, u32>);
 
+/// Technologies of each player, by ids indexing `Balance::techs`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Research {
+    pub done: Vec<u8>,
+    pub current: Option<u8>,
+    /// Seconds spent on `current` so far.
+    pub elapsed: f32,
+}
+#[derive(Default)]
+pub struct PlayerResearch(pub HashMap<Uuid, Research>);
+
+/// A planet as a player last saw it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlanetIntel {
+    pub owner: Option<Uuid>,
+    pub fighters: f32,
+}
+
+#[derive(Default)]
+pub struct Vision {
+    /// Entities in sight right now, own ones included.
+    pub visible: HashSet<Entity>,
+    pub planets: HashMap<Entity, PlanetIntel>,
+}
+
+/// What each player sees, worked out by `update_vision`. Players without vision, such as the
+/// dedicated server itself, see everything.
+#[derive(Default)]
+pub struct PlayerVision(pub HashMap<Uuid, Vision>);
+impl PlayerVision {
+    pub fn sees(&self, player: Uuid, entity: Entity) -> bool {
+        self.0
+            .get(&player)
+            .map_or(true, |v| v.visible.contains(&entity))
+    }
+
+    /// The planet as `player` last saw it, None while in sight or if it never was.
+    pub fn last_seen(&self, player: Uuid, planet: Entity) -> Option<PlanetIntel> {
+        let vision = self.0.get(&player)?;
+        match vision.visible.contains(&planet) {
+            true => None,
+            false => vision.planets.get(&planet).copied(),
+        }
+    }
+}
+
 // These need to be local
 pub struct AllegiancesToOthers(pub HashMap<Uuid, AllegianceStatus>);
+pub struct LocalPlayer(pub Uuid);
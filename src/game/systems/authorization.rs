@@ -0,0 +1,155 @@
+use bevy::{prelude::*, utils::Uuid};
+
+use crate::game::components::{characteristics::ShipType, commands::*, players::Ownership};
+use crate::game::resources::{log_res::GameLog, player_res::RegisteredPlayers};
+use crate::game::utils::balance::Balance;
+
+/// Turns received commands into executed ones, once every entity they order is known to belong
+/// to the issuing player. Entities destroyed in the meantime are dropped without complaint.
+pub fn authorize_commands(
+    time: Res<Time>,
+    players: Res<RegisteredPlayers>,
+    balance: Res<Balance>,
+    mut log: ResMut<GameLog>,
+    owners: Query<&Ownership>,
+    mut received: EventReader<ReceivedCommand>,
+    mut execute: EventWriter<ExecuteCommand>,
+) {
+    for cmd in received.iter() {
+        match authorize(cmd.player, &cmd.command, &players, &balance, &owners) {
+            Ok(Some(command)) => execute.send(ExecuteCommand {
+                player: cmd.player,
+                command,
+            }),
+            Ok(None) => {}
+            Err(reason) => {
+                let name = match players.0.get(&cmd.player) {
+                    Some(details) => details.name.clone(),
+                    None => cmd.player.to_string(),
+                };
+                warn!("rejected command from {}: {}", name, reason);
+                log.push(
+                    time.seconds_since_startup(),
+                    format!("Rejected order from {}: {}", name, reason),
+                    Color::ORANGE_RED,
+                );
+            }
+        }
+    }
+}
+
+fn authorize(
+    player: Uuid,
+    command: &PlayerCommand,
+    players: &RegisteredPlayers,
+    balance: &Balance,
+    owners: &Query<&Ownership>,
+) -> Result<Option<PlayerCommand>, String> {
+    let owned = |entities: &Vec<Entity>| -> Result<Vec<Entity>, String> {
+        let mut kept = Vec::new();
+        for e in entities.iter() {
+            match owners.get(*e) {
+                Ok(owner) if owner.0 == Some(player) => kept.push(*e),
+                Ok(_) => return Err("ordered units it does not own".to_string()),
+                Err(_) => {}
+            }
+        }
+        Ok(kept)
+    };
+    let command = match command {
+        PlayerCommand::MoveShips {
+            ships,
+            destination,
+            formation,
+            hold,
+        } => PlayerCommand::MoveShips {
+            ships: owned(ships)?,
+            destination: destination.clone(),
+            formation: *formation,
+            hold: *hold,
+        },
+        PlayerCommand::AttackMove {
+            ships,
+            destination,
+            formation,
+            hold,
+        } => PlayerCommand::AttackMove {
+            ships: owned(ships)?,
+            destination: destination.clone(),
+            formation: *formation,
+            hold: *hold,
+        },
+        PlayerCommand::Patrol { ships, points } => PlayerCommand::Patrol {
+            ships: owned(ships)?,
+            points: points.clone(),
+        },
+        // anything still around can be guarded, allies and neutral planets included
+        PlayerCommand::Guard { target, .. } if owners.get(*target).is_err() => return Ok(None),
+        PlayerCommand::Guard { ships, target } => PlayerCommand::Guard {
+            ships: owned(ships)?,
+            target: *target,
+        },
+        PlayerCommand::HoldPosition { ships } => PlayerCommand::HoldPosition {
+            ships: owned(ships)?,
+        },
+        PlayerCommand::QueueDestination { ships, destination } => PlayerCommand::QueueDestination {
+            ships: owned(ships)?,
+            destination: destination.clone(),
+        },
+        PlayerCommand::DeployFighters {
+            planets,
+            destination,
+            amount,
+        } => PlayerCommand::DeployFighters {
+            planets: owned(planets)?,
+            destination: destination.clone(),
+            amount: *amount,
+        },
+        PlayerCommand::SetTradeRoute { ships, route } => PlayerCommand::SetTradeRoute {
+            ships: owned(ships)?,
+            route: route.clone(),
+        },
+        PlayerCommand::UpgradePlanets { planets } => PlayerCommand::UpgradePlanets {
+            planets: owned(planets)?,
+        },
+        PlayerCommand::Build { building, .. }
+            if balance.buildings.get(*building as usize).is_none() =>
+        {
+            return Err("unknown building".to_string())
+        }
+        PlayerCommand::Build { planets, building } => PlayerCommand::Build {
+            planets: owned(planets)?,
+            building: *building,
+        },
+        PlayerCommand::BuyShip { ship_type, .. } if *ship_type == ShipType::Fighter => {
+            return Err("fighters cannot be bought".to_string())
+        }
+        PlayerCommand::BuyShip { planets, ship_type } => PlayerCommand::BuyShip {
+            planets: owned(planets)?,
+            ship_type: *ship_type,
+        },
+        PlayerCommand::Research { tech } if balance.techs.get(*tech as usize).is_none() => {
+            return Err("unknown technology".to_string())
+        }
+        PlayerCommand::MergeFleets {
+            ships,
+            formation,
+            hold,
+        } => PlayerCommand::MergeFleets {
+            ships: owned(ships)?,
+            formation: *formation,
+            hold: *hold,
+        },
+        PlayerCommand::SplitFleet { ships } => PlayerCommand::SplitFleet {
+            ships: owned(ships)?,
+        },
+        PlayerCommand::Diplomacy { to, .. } if *to == player || !players.0.contains_key(to) => {
+            return Err("diplomacy with an unknown player".to_string())
+        }
+        other => other.clone(),
+    };
+    match command.units() {
+        Some(units) if units.is_empty() => Ok(None),
+        _ => Ok(Some(command)),
+    }
+}
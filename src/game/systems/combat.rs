@@ -1,17 +1,21 @@
 use crate::game::{
     components::{characteristics::Ship, players::*, *},
     obj::spawn_bullet,
-    resources::player_res::RegisteredPlayers,
+    resources::{game_status_res::SIM_DT, player_res::RegisteredPlayers},
 };
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
-use characteristics::{Bullet, Fighter};
+use characteristics::{Bullet, Engagement, ShipDestroyed, ShipStats, Trader, Weapon};
+
+/// Largest angle between a ship's heading and its target at which it opens fire.
+const FIRE_ANGLE: f32 = 0.15;
 
 pub fn bullet_hit(
     mut commands: Commands,
     bullets: Query<(Entity, &Ownership), With<Bullet>>,
-    ships: Query<(Entity, &Ownership), With<Ship>>,
+    ships: Query<(Entity, &Ownership, Option<&Trader>), With<Ship>>,
     rapier_context: Res<RapierContext>,
+    mut ev_writer: EventWriter<ShipDestroyed>,
 ) {
     for (e_1, e_2, intersects) in rapier_context.intersection_pairs() {
         if intersects {
@@ -20,18 +24,28 @@ pub fn bullet_hit(
             let ship_e1 = ships.get(e_1);
             let ship_e2 = ships.get(e_2);
             if let Ok((b, b_owner)) = bullet_e1 {
-                if let Ok((s, s_owner)) = ship_e2 {
+                if let Ok((s, s_owner, trader)) = ship_e2 {
                     if b_owner.0 != s_owner.0 {
                         commands.entity(b).despawn_recursive();
                         commands.entity(s).despawn_recursive();
+                        ev_writer.send(ShipDestroyed {
+                            owner: s_owner.0,
+                            by: b_owner.0,
+                            is_trader: trader.is_some(),
+                        });
                     }
                 }
             }
             if let Ok((b, b_owner)) = bullet_e2 {
-                if let Ok((s, s_owner)) = ship_e1 {
+                if let Ok((s, s_owner, trader)) = ship_e1 {
                     if b_owner.0 != s_owner.0 {
                         commands.entity(b).despawn_recursive();
                         commands.entity(s).despawn_recursive();
+                        ev_writer.send(ShipDestroyed {
+                            owner: s_owner.0,
+                            by: b_owner.0,
+                            is_trader: trader.is_some(),
+                        });
                     }
                 }
             }
@@ -39,32 +53,50 @@ pub fn bullet_hit(
     }
 }
 
-pub fn fire_bullet(
+/// Engaged ships fire at their target once it is in range and ahead of them.
+pub fn fire_at_targets(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     players: Res<RegisteredPlayers>,
-    kb_input: Res<Input<KeyCode>>,
-    query: Query<(&Transform, &Ownership), With<Fighter>>,
+    mut query: Query<(&Transform, &Ownership, &Engagement, &ShipStats, &mut Weapon)>,
 ) {
-    if kb_input.just_pressed(KeyCode::F) {
-        for (transform, owner) in query.iter() {
-            let mut bullet_transform = transform.clone();
-            bullet_transform.translation += transform.up() * 1.5;
-            let owner_details = players
-                .0
-                .get(&owner.0.expect("ship with no owner attempted fire"))
-                .expect("failed to get details for ship owner");
-            spawn_bullet(
-                &mut commands,
-                &mut meshes,
-                &owner.0.unwrap(),
-                &owner_details,
-                bullet_transform.with_scale(Vec3::new(0.02, 0.04, 1.)),
-            );
+    for (transform, owner, engagement, stats, mut weapon) in query.iter_mut() {
+        weapon.cooldown = (weapon.cooldown - SIM_DT).max(0.);
+        if engagement.target.is_none() || weapon.cooldown > 0. {
+            continue;
+        }
+        let to_target = engagement.loc - transform.translation;
+        if to_target.length() > stats.range || transform.up().angle_between(to_target) > FIRE_ANGLE
+        {
+            continue;
         }
+        shoot(&mut commands, &mut meshes, &players, transform, owner);
+        weapon.cooldown = weapon.reload;
     }
 }
 
+fn shoot(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    players: &RegisteredPlayers,
+    transform: &Transform,
+    owner: &Ownership,
+) {
+    let mut bullet_transform = transform.clone();
+    bullet_transform.translation += transform.up() * 1.5;
+    let owner_details = players
+        .0
+        .get(&owner.0.expect("ship with no owner attempted fire"))
+        .expect("failed to get details for ship owner");
+    spawn_bullet(
+        commands,
+        meshes,
+        &owner.0.unwrap(),
+        &owner_details,
+        bullet_transform.with_scale(Vec3::new(0.02, 0.04, 1.)),
+    );
+}
+
 pub fn despawn_bullet(mut commands: Commands, query: Query<(Entity, &Bullet, &Transform)>) {
     for (e, bullet, transform) in query.iter() {
         if transform.translation.distance(bullet.origin) > bullet.distance {
@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::Velocity;
+
+use crate::game::components::characteristics::*;
+use crate::game::components::players::Ownership;
+use crate::game::resources::{game_obj_res::GalaxyFeatures, game_status_res::SIM_DT};
+use crate::game::utils::features::{FeatureKind, ASTEROID_DAMAGE, BLACK_HOLE_CORE};
+
+/// Asteroid fields wear down the hull of ships flying through them, the faster the worse, and
+/// black hole cores swallow ships whole. Lost ships have no killer.
+pub fn hazard_damage(
+    mut commands: Commands,
+    features: Res<GalaxyFeatures>,
+    mut ships: Query<(
+        Entity,
+        &Transform,
+        &Velocity,
+        &Ownership,
+        &mut Hull,
+        Option<&Trader>,
+    )>,
+    mut ev_writer: EventWriter<ShipDestroyed>,
+) {
+    if features.0.is_empty() {
+        return;
+    }
+    let dt = SIM_DT;
+    for (ship, transform, velocity, owner, mut hull, trader) in ships.iter_mut() {
+        let pos = transform.translation.truncate();
+        let mut swallowed = false;
+        for feature in features.0.iter().filter(|f| f.contains(pos)) {
+            match feature.kind {
+                FeatureKind::AsteroidField => {
+                    hull.0 -= velocity.linvel.length() * ASTEROID_DAMAGE * dt;
+                }
+                FeatureKind::BlackHole => {
+                    swallowed |= pos.distance(feature.centre) < feature.radius * BLACK_HOLE_CORE;
+                }
+                FeatureKind::Nebula => {}
+            }
+        }
+        if swallowed || hull.0 <= 0. {
+            commands.entity(ship).despawn_recursive();
+            ev_writer.send(ShipDestroyed {
+                owner: owner.0,
+                by: None,
+                is_trader: trader.is_some(),
+            });
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, Uuid},
+    window::ReceivedCharacter,
+};
+
+use crate::game::components::{characteristics::*, commands::*, players::*};
+use crate::game::resources::{
+    log_res::{ChatInput, GameLog},
+    player_res::{AllegiancesToOthers, LocalPlayer, RegisteredPlayers},
+};
+use crate::game::utils::balance::Balance;
+
+/// Attacks on the same planet are reported at most once per this many seconds.
+const ATTACK_REPORT_SECONDS: f64 = 10.;
+
+pub fn player_name(players: &RegisteredPlayers, local: &LocalPlayer, player: Uuid) -> String {
+    match (player == local.0, players.0.get(&player)) {
+        (true, _) => "You".to_string(),
+        (false, Some(details)) => details.name.clone(),
+        (false, None) => "Someone".to_string(),
+    }
+}
+
+pub fn player_color(players: &RegisteredPlayers, player: Option<Uuid>) -> Color {
+    match player.and_then(|p| players.0.get(&p)) {
+        Some(details) => slot_to_color(details.slot),
+        None => Color::GRAY,
+    }
+}
+
+pub fn planet_type_name(planet_type: PlanetType) -> &'static str {
+    match planet_type {
+        PlanetType::Outpost => "outpost",
+        PlanetType::Watch => "watch",
+        PlanetType::Base => "base",
+        PlanetType::Colony => "colony",
+        PlanetType::Capital => "capital",
+    }
+}
+
+pub fn log_match_start(time: Res<Time>, mut log: ResMut<GameLog>) {
+    if log.started_at.is_none() {
+        let now = time.seconds_since_startup();
+        log.started_at = Some(now);
+        log.push(now, "Match started".to_string(), Color::GRAY);
+    }
+}
+
+/// Enter opens the chat, Enter again sends it and Escape discards it. Lines starting with
+/// `/ally`, `/accept` or `/war` followed by a player name are diplomacy orders.
+pub fn chat_input(
+    kb_input: Res<Input<KeyCode>>,
+    mut chars: EventReader<ReceivedCharacter>,
+    mut chat: ResMut<ChatInput>,
+    players: Res<RegisteredPlayers>,
+    mut cmd_writer: EventWriter<IssueCommand>,
+) {
+    if !chat.active {
+        chars.clear();
+        if kb_input.just_pressed(KeyCode::Return) {
+            chat.active = true;
+        }
+        return;
+    }
+    if kb_input.just_pressed(KeyCode::Escape) {
+        chat.active = false;
+        chat.text.clear();
+        return;
+    }
+    if kb_input.just_pressed(KeyCode::Back) {
+        chat.text.pop();
+    }
+    for ev in chars.iter() {
+        if !ev.char.is_control() {
+            chat.text.push(ev.char);
+        }
+    }
+    if kb_input.just_pressed(KeyCode::Return) {
+        let text = std::mem::take(&mut chat.text);
+        chat.active = false;
+        if let Some(command) = parse_chat(text.trim(), &players) {
+            cmd_writer.send(IssueCommand(command));
+        }
+    }
+}
+
+fn parse_chat(text: &str, players: &RegisteredPlayers) -> Option<PlayerCommand> {
+    if text.is_empty() {
+        return None;
+    }
+    let (order, target) = text.split_once(' ').unwrap_or((text, ""));
+    let action = match order {
+        "/ally" => Some(DiplomacyAction::ProposeAlliance),
+        "/accept" => Some(DiplomacyAction::AcceptAlliance),
+        "/war" => Some(DiplomacyAction::DeclareWar),
+        _ => None,
+    };
+    match action {
+        Some(action) => players
+            .0
+            .iter()
+            .find(|(_, details)| details.name.eq_ignore_ascii_case(target.trim()))
+            .map(|(to, _)| PlayerCommand::Diplomacy { to: *to, action }),
+        None => Some(PlayerCommand::Chat {
+            text: text.to_string(),
+        }),
+    }
+}
+
+pub fn scroll_log(kb_input: Res<Input<KeyCode>>, mut log: ResMut<GameLog>) {
+    if kb_input.just_pressed(KeyCode::PageUp) {
+        log.scroll = (log.scroll + 1).min(log.entries.len().saturating_sub(1));
+    }
+    if kb_input.just_pressed(KeyCode::PageDown) {
+        log.scroll = log.scroll.saturating_sub(1);
+    }
+}
+
+/// Allegiances are a local view: only orders involving the local player change them.
+pub fn apply_diplomacy(
+    local: Res<LocalPlayer>,
+    mut allegiances: ResMut<AllegiancesToOthers>,
+    mut cmd_reader: EventReader<ExecuteCommand>,
+    mut ev_writer: EventWriter<DiplomacyEvent>,
+) {
+    for cmd in cmd_reader.iter() {
+        if let PlayerCommand::Diplomacy { to, action } = cmd.command {
+            let other = match (cmd.player == local.0, to == local.0) {
+                (true, _) => to,
+                (_, true) => cmd.player,
+                _ => Uuid::nil(),
+            };
+            match action {
+                DiplomacyAction::AcceptAlliance if !other.is_nil() => {
+                    allegiances.0.insert(other, AllegianceStatus::Friend);
+                }
+                DiplomacyAction::DeclareWar if !other.is_nil() => {
+                    allegiances.0.insert(other, AllegianceStatus::Enemy);
+                }
+                _ => {}
+            }
+            ev_writer.send(DiplomacyEvent {
+                from: cmd.player,
+                to,
+                action,
+            });
+        }
+    }
+}
+
+pub fn log_chat(
+    time: Res<Time>,
+    players: Res<RegisteredPlayers>,
+    local: Res<LocalPlayer>,
+    mut log: ResMut<GameLog>,
+    mut cmd_reader: EventReader<ExecuteCommand>,
+) {
+    for cmd in cmd_reader.iter() {
+        if let PlayerCommand::Chat { text } = &cmd.command {
+            let name = player_name(&players, &local, cmd.player);
+            log.push(
+                time.seconds_since_startup(),
+                format!("{}: {}", name, text),
+                player_color(&players, Some(cmd.player)),
+            );
+        }
+    }
+}
+
+pub fn log_diplomacy(
+    time: Res<Time>,
+    players: Res<RegisteredPlayers>,
+    local: Res<LocalPlayer>,
+    mut log: ResMut<GameLog>,
+    mut ev_reader: EventReader<DiplomacyEvent>,
+) {
+    for ev in ev_reader.iter() {
+        let from = player_name(&players, &local, ev.from);
+        let to = match ev.to == local.0 {
+            true => "you".to_string(),
+            false => player_name(&players, &local, ev.to),
+        };
+        let text = match ev.action {
+            DiplomacyAction::ProposeAlliance => format!("{} proposed an alliance to {}", from, to),
+            DiplomacyAction::AcceptAlliance => format!("{} accepted an alliance with {}", from, to),
+            DiplomacyAction::DeclareWar => format!("{} declared war on {}", from, to),
+        };
+        log.push(
+            time.seconds_since_startup(),
+            text,
+            player_color(&players, Some(ev.from)),
+        );
+    }
+}
+
+pub fn log_ownership_changes(
+    time: Res<Time>,
+    players: Res<RegisteredPlayers>,
+    local: Res<LocalPlayer>,
+    planets: Query<&Planet>,
+    mut log: ResMut<GameLog>,
+    mut ev_reader: EventReader<TakeOwnership>,
+) {
+    for ev in ev_reader.iter() {
+        if let Ok(planet) = planets.get(ev.entity) {
+            let name = player_name(&players, &local, ev.owner);
+            log.push(
+                time.seconds_since_startup(),
+                format!(
+                    "{} captured a {}",
+                    name,
+                    planet_type_name(planet.planet_type)
+                ),
+                player_color(&players, Some(ev.owner)),
+            );
+        }
+    }
+}
+
+/// Losses are summed per frame, a battle would otherwise flood the log.
+pub fn log_ships_destroyed(
+    time: Res<Time>,
+    players: Res<RegisteredPlayers>,
+    local: Res<LocalPlayer>,
+    mut log: ResMut<GameLog>,
+    mut ev_reader: EventReader<ShipDestroyed>,
+) {
+    let mut losses: HashMap<(Option<Uuid>, Option<Uuid>, bool), u32> = HashMap::new();
+    for ev in ev_reader.iter() {
+        *losses.entry((ev.owner, ev.by, ev.is_trader)).or_insert(0) += 1;
+    }
+    for ((owner, by, is_trader), count) in losses {
+        let ship = match (is_trader, count) {
+            (true, 1) => "trader",
+            (true, _) => "traders",
+            (false, 1) => "fighter",
+            (false, _) => "fighters",
+        };
+        let victim = match owner {
+            Some(o) if o == local.0 => "your".to_string(),
+            Some(o) => format!("{}'s", player_name(&players, &local, o)),
+            None => "neutral".to_string(),
+        };
+        let text = match by {
+            Some(b) => format!(
+                "{} destroyed {} {} {}",
+                player_name(&players, &local, b),
+                count,
+                victim,
+                ship
+            ),
+            // asteroids and black holes
+            None => format!("{} {} {} lost in space", count, victim, ship),
+        };
+        log.push(
+            time.seconds_since_startup(),
+            text,
+            player_color(&players, by),
+        );
+    }
+}
+
+pub fn log_planet_attacks(
+    time: Res<Time>,
+    local: Res<LocalPlayer>,
+    planets: Query<&Planet>,
+    mut last_reported: Local<HashMap<Entity, f64>>,
+    mut log: ResMut<GameLog>,
+    mut ev_reader: EventReader<PlanetAttacked>,
+) {
+    let now = time.seconds_since_startup();
+    for ev in ev_reader.iter() {
+        if ev.owner != local.0 {
+            continue;
+        }
+        let recently_reported = last_reported
+            .get(&ev.planet)
+            .map_or(false, |t| now - t < ATTACK_REPORT_SECONDS);
+        if let (Ok(planet), false) = (planets.get(ev.planet), recently_reported) {
+            last_reported.insert(ev.planet, now);
+            log.push(
+                now,
+                format!(
+                    "Your {} is under attack",
+                    planet_type_name(planet.planet_type)
+                ),
+                Color::ORANGE,
+            );
+        }
+    }
+}
+
+pub fn log_research_completed(
+    time: Res<Time>,
+    balance: Res<Balance>,
+    local: Res<LocalPlayer>,
+    mut log: ResMut<GameLog>,
+    mut ev_reader: EventReader<ResearchCompleted>,
+) {
+    for ev in ev_reader.iter().filter(|ev| ev.player == local.0) {
+        let tech = &balance.techs[ev.tech as usize];
+        log.push(
+            time.seconds_since_startup(),
+            format!("Research complete: {} ({})", tech.name, tech.effects()),
+            Color::CYAN,
+        );
+    }
+}
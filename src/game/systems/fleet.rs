@@ -0,0 +1,356 @@
+use bevy::prelude::*;
+
+use crate::game::components::characteristics::*;
+use crate::game::components::commands::*;
+use crate::game::obj::spawn_fleet;
+use crate::game::resources::game_status_res::{FormationChoice, SIM_DT};
+use crate::game::resources::log_res::ChatInput;
+use crate::game::utils::formation::{self, Formation};
+use crate::selection::components::Selected;
+
+/// Distance between neighbouring slots of a formation.
+const FORMATION_SPACING: f32 = 4.;
+/// A cluster whose centre gets this close to its target has arrived.
+const ARRIVAL_RADIUS: f32 = 3.;
+/// Share of members that must have reached their slot for the whole fleet to count as arrived.
+const ARRIVAL_SHARE: f32 = 0.75;
+
+type MemberQuery<'w, 's, 'a> = Query<
+    'w,
+    's,
+    (
+        &'a mut Destination,
+        &'a Transform,
+        &'a ShipStats,
+        Option<&'a FleetMember>,
+    ),
+>;
+
+/// Slot targets for ships at `positions` taking `formation` around `target`, oriented along the
+/// way from their centre to the target. Returned in the order of `positions`.
+pub fn formation_targets(positions: &[Vec2], formation: Formation, target: Vec3) -> Vec<Vec3> {
+    if positions.is_empty() {
+        return Vec::new();
+    }
+    let centre = positions.iter().sum::<Vec2>() / positions.len() as f32;
+    let forward = (target.truncate() - centre)
+        .try_normalize()
+        .unwrap_or(Vec2::Y);
+    let local: Vec<Vec2> = positions
+        .iter()
+        .map(|p| formation::to_local(*p - centre, forward))
+        .collect();
+    let slots = formation::assign_slots(
+        &local,
+        &formation::slots(formation, positions.len(), FORMATION_SPACING),
+    );
+    slots
+        .into_iter()
+        .map(|slot| target + formation::to_world(slot, forward).extend(0.))
+        .collect()
+}
+
+/// Centre of the ships among `members` that still exist.
+fn centre(query: &MemberQuery, members: &[Entity]) -> Vec3 {
+    let positions: Vec<Vec3> = members
+        .iter()
+        .filter_map(|e| query.get(*e).ok())
+        .map(|(_, transform, _, _)| transform.translation)
+        .collect();
+    positions.iter().sum::<Vec3>() / positions.len().max(1) as f32
+}
+
+/// Sends `members` to their slots around `target`. Returns the speed of the slowest one.
+fn arrange(
+    commands: &mut Commands,
+    query: &mut MemberQuery,
+    members: &[Entity],
+    formation: Formation,
+    target: Vec3,
+) -> f32 {
+    let members: Vec<Entity> = members
+        .iter()
+        .copied()
+        .filter(|e| query.contains(*e))
+        .collect();
+    let positions: Vec<Vec2> = members
+        .iter()
+        .map(|e| query.get(*e).unwrap().1.translation.truncate())
+        .collect();
+    let mut slowest = f32::MAX;
+    for (e, slot) in members
+        .iter()
+        .zip(formation_targets(&positions, formation, target))
+    {
+        let (mut dest, _, stats, _) = query.get_mut(*e).unwrap();
+        dest.0 = DestinationEnum::Space(slot);
+        commands.entity(*e).insert(FormationSlot(slot - target));
+        slowest = slowest.min(stats.speed);
+    }
+    slowest
+}
+
+/// Removes `ship` from `fleet`, disbanding the fleet once it has no members left.
+fn leave_fleet(
+    commands: &mut Commands,
+    fleets: &mut Query<&mut Fleet>,
+    fleet: Entity,
+    ship: Entity,
+) {
+    if let Ok(mut f) = fleets.get_mut(fleet) {
+        f.members.retain(|m| *m != ship);
+        if f.members.is_empty() {
+            commands.entity(fleet).despawn();
+        }
+    }
+}
+
+/// Ships ordered into open space become a new fleet, each ship heading for its own slot.
+/// Attack-moves travel the same way.
+pub fn apply_move_ships(
+    mut commands: Commands,
+    mut cmd_reader: EventReader<ExecuteCommand>,
+    mut query: MemberQuery,
+    mut orders: Query<(&mut OrderQueue, &mut ShipOrder)>,
+    mut fleets: Query<&mut Fleet>,
+) {
+    for cmd in cmd_reader.iter() {
+        let (ships, destination, formation, hold, order) = match &cmd.command {
+            PlayerCommand::MoveShips {
+                ships,
+                destination,
+                formation,
+                hold,
+            } => (ships, destination, formation, hold, ShipOrder::Move),
+            PlayerCommand::AttackMove {
+                ships,
+                destination,
+                formation,
+                hold,
+            } => (ships, destination, formation, hold, ShipOrder::AttackMove),
+            _ => continue,
+        };
+        let members: Vec<Entity> = ships
+            .iter()
+            .copied()
+            .filter(|e| query.contains(*e))
+            .collect();
+        if members.is_empty() {
+            continue;
+        }
+        for e in members.iter() {
+            if let Ok((_, _, _, Some(member))) = query.get(*e) {
+                leave_fleet(&mut commands, &mut fleets, member.0, *e);
+            }
+            // a new order replaces the queued ones, trade routes included
+            if let Ok((mut queue, mut ship_order)) = orders.get_mut(*e) {
+                queue.orders.clear();
+                queue.looping = false;
+                *ship_order = order;
+            }
+        }
+        match destination {
+            DestinationEnum::Space(target) => {
+                let anchor = centre(&query, &members);
+                let slowest = arrange(&mut commands, &mut query, &members, *formation, *target);
+                let speed = if *hold { Some(slowest) } else { None };
+                spawn_fleet(&mut commands, members, *formation, *target, speed, anchor);
+            }
+            other => {
+                for e in members.iter() {
+                    query.get_mut(*e).unwrap().0 .0 = other.clone();
+                    commands.entity(*e).remove::<FleetMember>();
+                }
+            }
+        }
+    }
+}
+
+/// G merges the selected ships into one fleet, H splits them off their fleets.
+pub fn fleet_orders(
+    kb_input: Res<Input<KeyCode>>,
+    chat: Res<ChatInput>,
+    choice: Res<FormationChoice>,
+    query: Query<Entity, (With<Selected>, With<Destination>)>,
+    mut cmd_writer: EventWriter<IssueCommand>,
+) {
+    if chat.active {
+        return;
+    }
+    let ships: Vec<Entity> = query.iter().collect();
+    if ships.is_empty() {
+        return;
+    }
+    if kb_input.just_pressed(KeyCode::G) {
+        cmd_writer.send(IssueCommand(PlayerCommand::MergeFleets {
+            ships,
+            formation: choice.formation,
+            hold: choice.hold,
+        }));
+    } else if kb_input.just_pressed(KeyCode::H) {
+        cmd_writer.send(IssueCommand(PlayerCommand::SplitFleet { ships }));
+    }
+}
+
+/// The fleet holding most of the ships absorbs the others, and everyone takes a new slot in its
+/// formation. Ships that are in no fleet at all form a new one where they stand.
+pub fn apply_merge_fleets(
+    mut commands: Commands,
+    mut cmd_reader: EventReader<ExecuteCommand>,
+    mut query: MemberQuery,
+    mut fleets: Query<&mut Fleet>,
+) {
+    for cmd in cmd_reader.iter() {
+        if let PlayerCommand::MergeFleets {
+            ships,
+            formation,
+            hold,
+        } = &cmd.command
+        {
+            let mut sources: Vec<(Entity, usize)> = Vec::new();
+            for e in ships.iter() {
+                if let Ok((_, _, _, Some(member))) = query.get(*e) {
+                    match sources.iter_mut().find(|(f, _)| *f == member.0) {
+                        Some((_, count)) => *count += 1,
+                        None => sources.push((member.0, 1)),
+                    }
+                }
+            }
+            let target_fleet = match sources.iter().max_by_key(|(_, count)| *count) {
+                Some((fleet, _)) => *fleet,
+                None => {
+                    let members: Vec<Entity> = ships
+                        .iter()
+                        .copied()
+                        .filter(|e| query.contains(*e))
+                        .collect();
+                    if members.is_empty() {
+                        continue;
+                    }
+                    let anchor = centre(&query, &members);
+                    let slowest = arrange(&mut commands, &mut query, &members, *formation, anchor);
+                    let speed = if *hold { Some(slowest) } else { None };
+                    spawn_fleet(&mut commands, members, *formation, anchor, speed, anchor);
+                    continue;
+                }
+            };
+            let mut joining = Vec::new();
+            for e in ships.iter() {
+                let member = match query.get(*e) {
+                    Ok((_, _, _, member)) => member.map(|m| m.0),
+                    Err(_) => continue,
+                };
+                if member == Some(target_fleet) {
+                    continue;
+                }
+                if let Some(fleet) = member {
+                    leave_fleet(&mut commands, &mut fleets, fleet, *e);
+                }
+                commands.entity(*e).insert(FleetMember(target_fleet));
+                joining.push(*e);
+            }
+            if let Ok(mut fleet) = fleets.get_mut(target_fleet) {
+                fleet.members.extend(joining);
+                let slowest = arrange(
+                    &mut commands,
+                    &mut query,
+                    &fleet.members,
+                    fleet.formation,
+                    fleet.target,
+                );
+                if fleet.speed.is_some() {
+                    fleet.speed = Some(slowest);
+                }
+            }
+        }
+    }
+}
+
+/// Split ships stop where they are, free for their own orders. The rest of each fleet closes
+/// ranks.
+pub fn apply_split_fleet(
+    mut commands: Commands,
+    mut cmd_reader: EventReader<ExecuteCommand>,
+    mut query: MemberQuery,
+    mut fleets: Query<&mut Fleet>,
+) {
+    for cmd in cmd_reader.iter() {
+        if let PlayerCommand::SplitFleet { ships } = &cmd.command {
+            let mut touched = Vec::new();
+            for e in ships.iter() {
+                if let Ok((mut dest, _, _, Some(member))) = query.get_mut(*e) {
+                    dest.0 = DestinationEnum::None;
+                    let fleet = member.0;
+                    leave_fleet(&mut commands, &mut fleets, fleet, *e);
+                    commands.entity(*e).remove::<FleetMember>();
+                    if !touched.contains(&fleet) {
+                        touched.push(fleet);
+                    }
+                }
+            }
+            for fleet_e in touched {
+                if let Ok(fleet) = fleets.get(fleet_e) {
+                    if !fleet.members.is_empty() {
+                        let members = fleet.members.clone();
+                        let (formation, target) = (fleet.formation, fleet.target);
+                        arrange(&mut commands, &mut query, &members, formation, target);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Drops members that died or left, moves the anchor of held formations, and ends fleets once
+/// they have arrived: once most members sit in their slot or, for a cluster, when its centre
+/// reaches the target since its ships all aim at the same point and cannot all get there.
+/// Stragglers stop where they are.
+pub fn update_fleets(
+    mut commands: Commands,
+    mut fleets: Query<(Entity, &mut Fleet)>,
+    mut ships: Query<(&mut Destination, &Transform, &FleetMember)>,
+) {
+    for (fleet_e, mut fleet) in fleets.iter_mut() {
+        fleet
+            .members
+            .retain(|e| matches!(ships.get(*e), Ok((_, _, member)) if member.0 == fleet_e));
+        if fleet.members.is_empty() {
+            commands.entity(fleet_e).despawn();
+            continue;
+        }
+        if let Some(speed) = fleet.speed {
+            let to_target = fleet.target - fleet.anchor;
+            let step = speed * SIM_DT;
+            fleet.anchor = match to_target.length() > step {
+                true => fleet.anchor + to_target.normalize() * step,
+                false => fleet.target,
+            };
+        }
+
+        let mut centre = Vec2::ZERO;
+        let mut arrived = 0;
+        for e in fleet.members.iter() {
+            let (dest, transform, _) = ships.get(*e).unwrap();
+            centre += transform.translation.truncate();
+            if !matches!(dest.0, DestinationEnum::Space(_)) {
+                arrived += 1;
+            }
+        }
+        let count = fleet.members.len() as f32;
+        centre /= count;
+        let near = fleet.formation == Formation::Cluster
+            && centre.distance(fleet.target.truncate()) < ARRIVAL_RADIUS;
+        if !near && (arrived as f32) < ARRIVAL_SHARE * count {
+            continue;
+        }
+
+        for e in fleet.members.iter() {
+            let (mut dest, _, _) = ships.get_mut(*e).unwrap();
+            if let DestinationEnum::Space(_) = dest.0 {
+                dest.0 = DestinationEnum::None;
+            }
+            commands.entity(*e).remove::<FleetMember>();
+        }
+        commands.entity(fleet_e).despawn();
+    }
+}
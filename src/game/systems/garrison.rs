@@ -0,0 +1,201 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+
+use crate::game::components::characteristics::*;
+use crate::game::components::config::InitGameSetup;
+use crate::game::components::players::Ownership;
+use crate::game::resources::game_obj_res::SpatialIndex;
+use crate::game::resources::game_status_res::SIM_DT;
+use crate::game::resources::player_res::{LocalPlayer, PlayerVision, RegisteredPlayers};
+
+/// Fighters on each orbit ring, before the next ring starts further out.
+const PER_RING: u32 = 24;
+/// Distance from the planet surface to the first ring.
+const ORBIT_GAP: f32 = 1.5;
+const RING_GAP: f32 = 1.;
+/// Radians per second.
+const ORBIT_SPEED: f32 = 0.6;
+/// Distance beyond the outer ring at which defenders intercept enemy ships.
+const INTERCEPT_RANGE: f32 = 4.;
+/// Seconds between two interceptions by the same planet.
+const INTERCEPT_RELOAD: f32 = 0.5;
+const FIGHTER_SIZE: f32 = 0.5;
+
+/// Offset from the planet centre of stored fighter `i` once the garrison has turned by `phase`.
+/// Slots keep their place as the garrison grows, so fighters never jump around.
+pub fn orbit_slot(i: u32, planet_radius: f32, phase: f32) -> Vec2 {
+    let ring = i / PER_RING;
+    let angle = phase + (i % PER_RING) as f32 * TAU / PER_RING as f32;
+    let radius = planet_radius + ORBIT_GAP + ring as f32 * RING_GAP;
+    Vec2::new(angle.cos(), angle.sin()) * radius
+}
+
+fn outer_radius(planet: &Planet) -> f32 {
+    let rings = (planet.fighters.max(0.) as u32 + PER_RING - 1) / PER_RING;
+    planet_type_to_radius(&planet.planet_type) + ORBIT_GAP + rings.max(1) as f32 * RING_GAP
+}
+
+/// Gives new planets an orbiting garrison when the match is set up for it.
+pub fn setup_garrisons(
+    mut commands: Commands,
+    setup: Res<InitGameSetup>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    planets: Query<Entity, Added<Planet>>,
+) {
+    if !setup.orbiting_garrison {
+        return;
+    }
+    for planet in planets.iter() {
+        let neutral = materials.add(StandardMaterial {
+            base_color: Color::GRAY,
+            unlit: true,
+            ..default()
+        });
+        commands
+            .entity(planet)
+            .insert(Garrison::default())
+            .with_children(|parent| {
+                parent
+                    .spawn_bundle(MaterialMeshBundle {
+                        material: neutral.clone(),
+                        ..default()
+                    })
+                    .insert(GarrisonRing {
+                        shown: 0,
+                        radius: 0.,
+                        neutral,
+                    });
+            });
+    }
+}
+
+pub fn orbit_garrisons(mut query: Query<&mut Garrison>) {
+    for mut garrison in query.iter_mut() {
+        garrison.phase = (garrison.phase + ORBIT_SPEED * SIM_DT) % TAU;
+        garrison.cooldown = (garrison.cooldown - SIM_DT).max(0.);
+    }
+}
+
+/// Defenders throw themselves at the nearest enemy ship coming close to their planet. Both are
+/// lost, the same trade as an attacker landing on a defended planet.
+pub fn intercept_attackers(
+    mut commands: Commands,
+    index: Res<SpatialIndex>,
+    mut planets: Query<(&NetId, &Transform, &Ownership, &mut Planet, &mut Garrison)>,
+    ships: Query<(&Transform, &Ownership, Option<&Trader>), With<Ship>>,
+    mut ev_writer: EventWriter<ShipDestroyed>,
+) {
+    let mut destroyed = Vec::new();
+    // in id order, so planets racing for the same ship take turns the same way on every peer
+    let mut planets: Vec<_> = planets.iter_mut().collect();
+    planets.sort_by_key(|(id, _, _, _, _)| **id);
+    for (_, transform, owner, mut planet, mut garrison) in planets {
+        if owner.0.is_none() || planet.fighters < 1. || garrison.cooldown > 0. {
+            continue;
+        }
+        let centre = transform.translation.truncate();
+        let radius = outer_radius(&planet) + INTERCEPT_RANGE;
+        let reach = Vec2::splat(radius);
+        let target = index
+            .selectables
+            .in_rect(centre - reach, centre + reach)
+            .into_iter()
+            .filter(|e| !destroyed.contains(e))
+            .filter_map(|e| {
+                let (t, ship_owner, trader) = ships.get(e).ok()?;
+                let dist = t.translation.truncate().distance(centre);
+                (ship_owner.0 != owner.0 && dist <= radius)
+                    .then(|| (e, ship_owner.0, trader.is_some(), dist))
+            })
+            .min_by(|a, b| a.3.total_cmp(&b.3));
+        if let Some((ship, ship_owner, is_trader, _)) = target {
+            commands.entity(ship).despawn_recursive();
+            destroyed.push(ship);
+            planet.fighters -= 1.;
+            garrison.cooldown = INTERCEPT_RELOAD;
+            ev_writer.send(ShipDestroyed {
+                owner: ship_owner,
+                by: owner.0,
+                is_trader,
+            });
+        }
+    }
+}
+
+/// Redraws the ring of stored fighters when their number changes, and spins it. Rings of planets
+/// out of sight are hidden.
+pub fn draw_garrisons(
+    local: Option<Res<LocalPlayer>>,
+    vision: Res<PlayerVision>,
+    players: Res<RegisteredPlayers>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    planets: Query<(&Planet, &Garrison, &Ownership)>,
+    mut rings: Query<(
+        &Parent,
+        &mut GarrisonRing,
+        &mut Handle<Mesh>,
+        &mut Handle<StandardMaterial>,
+        &mut Transform,
+        &mut Visibility,
+    )>,
+) {
+    for (parent, mut ring, mut mesh, mut material, mut transform, mut visibility) in
+        rings.iter_mut()
+    {
+        let (planet, garrison, owner) = match planets.get(**parent) {
+            Ok(planet) => planet,
+            Err(_) => continue,
+        };
+        transform.rotation = Quat::from_rotation_z(garrison.phase);
+
+        let color = match owner.0.and_then(|p| players.0.get(&p)) {
+            Some(details) => details.color.clone(),
+            None => ring.neutral.clone(),
+        };
+        if *material != color {
+            *material = color;
+        }
+
+        let shown = planet.fighters.max(0.) as u32;
+        let radius = planet_type_to_radius(&planet.planet_type);
+        if ring.shown != shown || ring.radius != radius {
+            *mesh = meshes.add(garrison_mesh(shown, radius));
+            ring.shown = shown;
+            ring.radius = radius;
+        }
+        let seen = local.as_ref().map_or(true, |l| vision.sees(l.0, **parent));
+        if visibility.is_visible != (seen && shown > 0) {
+            visibility.is_visible = seen && shown > 0;
+        }
+    }
+}
+
+/// One small triangle per stored fighter, pointing along its orbit.
+fn garrison_mesh(count: u32, planet_radius: f32) -> Mesh {
+    let mut positions: Vec<[f32; 3]> = Vec::with_capacity(count as usize * 3);
+    for i in 0..count {
+        let pos = orbit_slot(i, planet_radius, 0.);
+        let ahead = pos.perp().normalize() * FIGHTER_SIZE;
+        let side = pos.normalize() * FIGHTER_SIZE * 0.5;
+        // counter-clockwise seen from the camera
+        for corner in [
+            pos + ahead,
+            pos - ahead * 0.5 - side,
+            pos - ahead * 0.5 + side,
+        ] {
+            positions.push([corner.x, corner.y, 0.1]);
+        }
+    }
+    let normals = vec![[0.0, 0.0, 1.0]; positions.len()];
+    let uvs = vec![[0.0, 0.0]; positions.len()];
+    let indices = (0..positions.len() as u32).collect();
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh
+}
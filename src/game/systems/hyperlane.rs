@@ -0,0 +1,219 @@
+use bevy::prelude::*;
+use bevy::render::render_resource::PrimitiveTopology;
+use bevy::utils::{HashMap, Uuid};
+
+use crate::game::components::characteristics::*;
+use crate::game::components::players::Ownership;
+use crate::game::resources::game_obj_res::Hyperlanes;
+use crate::game::resources::player_res::LocalPlayer;
+use crate::game::utils::layers_util::{get_z, Layers};
+
+/// Planets closer than this are not worth a hyperlane, so small galaxies have none.
+const HYPERLANE_MIN_LENGTH: f32 = 800.;
+const MAX_LANES_PER_PLANET: usize = 2;
+/// Distance a hyperlane trip is worth, however long the lane.
+const LANE_COST: f32 = 60.;
+/// Distance from the entry planet surface at which ships jump.
+const LANE_REACH: f32 = 5.;
+/// Distance from the exit planet surface at which ships come out.
+const EXIT_GAP: f32 = 4.;
+
+/// Whether `player` may use a lane between planets owned by `ends`. Holding either end closes
+/// the lane to everyone else.
+pub fn open_to(player: Option<Uuid>, ends: [Option<Uuid>; 2]) -> bool {
+    ends.iter().all(|end| end.is_none() || *end == player)
+}
+
+/// Joins distant planets with hyperlanes, shortest first, each planet taking only a few. Only
+/// planet positions and ids count, so every peer links the same planets once it knows them all.
+pub fn link_hyperlanes(
+    mut lanes: ResMut<Hyperlanes>,
+    added: Query<(), Added<Planet>>,
+    removed: RemovedComponents<Planet>,
+    planets: Query<(Entity, &NetId, &Transform), With<Planet>>,
+) {
+    if added.is_empty() && removed.iter().next().is_none() {
+        return;
+    }
+    let mut sorted: Vec<(NetId, Entity, Vec2)> = planets
+        .iter()
+        .map(|(e, id, t)| (*id, e, t.translation.truncate()))
+        .collect();
+    sorted.sort_by_key(|(id, _, _)| *id);
+
+    let mut pairs = Vec::new();
+    for (i, (_, a, a_pos)) in sorted.iter().enumerate() {
+        for (_, b, b_pos) in sorted[i + 1..].iter() {
+            let dist = a_pos.distance(*b_pos);
+            if dist >= HYPERLANE_MIN_LENGTH {
+                pairs.push((dist, *a, *b));
+            }
+        }
+    }
+    // stable, so equally long pairs stay in id order
+    pairs.sort_by(|x, y| x.0.total_cmp(&y.0));
+    let mut taken: HashMap<Entity, usize> = HashMap::default();
+    lanes.0.clear();
+    for (_, a, b) in pairs {
+        if taken.get(&a).copied().unwrap_or(0) < MAX_LANES_PER_PLANET
+            && taken.get(&b).copied().unwrap_or(0) < MAX_LANES_PER_PLANET
+        {
+            *taken.entry(a).or_default() += 1;
+            *taken.entry(b).or_default() += 1;
+            lanes.0.push((a, b));
+        }
+    }
+}
+
+/// Hyperlane open to `player` that shortens the trip from `from` to `to` the most, as the entry
+/// and exit planets and their positions.
+pub fn best_lane(
+    lanes: &Hyperlanes,
+    planets: &Query<(&Transform, &Planet, &Ownership), Without<Ship>>,
+    player: Option<Uuid>,
+    from: Vec2,
+    to: Vec2,
+) -> Option<((Entity, Vec2), (Entity, Vec2))> {
+    let end = |planet: Entity| {
+        let (transform, _, owner) = planets.get(planet).ok()?;
+        Some((planet, transform.translation.truncate(), owner.0))
+    };
+    lanes
+        .0
+        .iter()
+        .filter_map(|(a, b)| Some((end(*a)?, end(*b)?)))
+        .filter(|((_, _, a_owner), (_, _, b_owner))| open_to(player, [*a_owner, *b_owner]))
+        .flat_map(|(a, b)| [(a, b), (b, a)])
+        .map(|((entry, entry_pos, _), (exit, exit_pos, _))| {
+            let trip = from.distance(entry_pos) + LANE_COST + exit_pos.distance(to);
+            (trip, (entry, entry_pos), (exit, exit_pos))
+        })
+        .filter(|(trip, _, _)| *trip < from.distance(to))
+        .min_by(|x, y| x.0.total_cmp(&y.0))
+        .map(|(_, entry, exit)| (entry, exit))
+}
+
+/// Ships reaching the entry planet of their lane come out at the other end, on the side of
+/// their destination, and plan the rest of the way from there. A lane closed on the way makes
+/// them plan again.
+pub fn travel_hyperlanes(
+    lanes: Res<Hyperlanes>,
+    planets: Query<(&Transform, &Planet, &Ownership), Without<Ship>>,
+    mut ships: Query<
+        (
+            &mut Transform,
+            &mut Destination,
+            &mut PlannedPath,
+            &mut LaneJump,
+            &Ownership,
+        ),
+        With<Ship>,
+    >,
+) {
+    for (mut transform, mut dest, mut path, mut jump, owner) in ships.iter_mut() {
+        let (entry, exit) = match jump.0 {
+            Some(lane) => lane,
+            None => continue,
+        };
+        let usable = match (planets.get(entry), planets.get(exit)) {
+            (Ok(a), Ok(b)) if lanes.joins(entry, exit) && open_to(owner.0, [a.2 .0, b.2 .0]) => {
+                Some((a, b))
+            }
+            _ => None,
+        };
+        let ((entry_transform, entry_planet, _), (exit_transform, exit_planet, _)) = match usable {
+            Some(ends) => ends,
+            None => {
+                jump.0 = None;
+                dest.set_changed();
+                continue;
+            }
+        };
+        let pos = transform.translation.truncate();
+        let entry_pos = entry_transform.translation.truncate();
+        if pos.distance(entry_pos) > planet_type_to_radius(&entry_planet.planet_type) + LANE_REACH {
+            continue;
+        }
+        let exit_pos = exit_transform.translation.truncate();
+        let goal = dest.0.loc().map_or(exit_pos, |loc| loc.truncate());
+        let out = match (goal - exit_pos).try_normalize() {
+            Some(out) => out,
+            // the exit planet is the destination, come out facing away from the entry
+            None => (exit_pos - entry_pos).normalize_or_zero(),
+        };
+        let gap = planet_type_to_radius(&exit_planet.planet_type) + EXIT_GAP;
+        transform.translation = (exit_pos + out * gap).extend(transform.translation.z);
+        path.0.clear();
+        jump.0 = None;
+        dest.set_changed();
+    }
+}
+
+/// Draws the hyperlanes, in one colour when the local player can use them and another when a
+/// rival holds an end.
+pub fn draw_hyperlanes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    local: Option<Res<LocalPlayer>>,
+    lanes: Res<Hyperlanes>,
+    planets: Query<(&Transform, &Ownership), With<Planet>>,
+    changed: Query<(), (With<Planet>, Changed<Ownership>)>,
+    mut lines: Query<(&HyperlaneLines, &mut Handle<Mesh>, &mut Visibility)>,
+) {
+    if !lanes.is_changed() && changed.is_empty() {
+        return;
+    }
+    let player = local.map(|l| l.0);
+    let z = get_z(Layers::Hyperlanes);
+    // blocked lanes first, then open ones
+    let mut points: [Vec<[f32; 3]>; 2] = [Vec::new(), Vec::new()];
+    for (a, b) in lanes.0.iter() {
+        if let (Ok((a_transform, a_owner)), Ok((b_transform, b_owner))) =
+            (planets.get(*a), planets.get(*b))
+        {
+            let open = open_to(player, [a_owner.0, b_owner.0]);
+            for t in [a_transform, b_transform] {
+                points[open as usize].push([t.translation.x, t.translation.y, z]);
+            }
+        }
+    }
+
+    for (open, points) in [false, true].into_iter().zip(points) {
+        let existing = lines.iter_mut().find(|(l, _, _)| l.open == open);
+        if points.is_empty() {
+            if let Some((_, _, mut visibility)) = existing {
+                visibility.is_visible = false;
+            }
+            continue;
+        }
+        let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; points.len()]);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; points.len()]);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, points);
+        match existing {
+            Some((_, mut handle, mut visibility)) => {
+                *handle = meshes.add(mesh);
+                visibility.is_visible = true;
+            }
+            None => {
+                let color = match open {
+                    true => Color::rgba(0.4, 0.7, 1.0, 0.5),
+                    false => Color::rgba(0.8, 0.2, 0.2, 0.35),
+                };
+                commands
+                    .spawn_bundle(MaterialMeshBundle {
+                        mesh: meshes.add(mesh),
+                        material: materials.add(StandardMaterial {
+                            base_color: color,
+                            unlit: true,
+                            alpha_mode: AlphaMode::Blend,
+                            ..default()
+                        }),
+                        ..default()
+                    })
+                    .insert(HyperlaneLines { open });
+            }
+        }
+    }
+}
@@ -1,3 +1,15 @@
+pub mod authorization;
 pub mod combat;
+pub mod environment;
+pub mod event_log;
+pub mod fleet;
+pub mod garrison;
+pub mod hyperlane;
 pub mod movement;
+pub mod orders;
+pub mod planet_material;
 pub mod production;
+pub mod research;
+pub mod siege;
+pub mod territory;
+pub mod vision;
@@ -1,32 +1,113 @@
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    render::render_resource::PrimitiveTopology,
+    utils::{HashMap, Uuid},
+};
 use bevy_rapier3d::prelude::*;
 
 use crate::camera::MouseWorldPos;
 use crate::game::components::characteristics::*;
-use crate::game::resources::game_obj_res::MovingFleets;
-use crate::game::resources::game_status_res::IsTradeRouting;
+use crate::game::components::commands::*;
+use crate::game::components::players::Ownership;
+use crate::game::resources::game_obj_res::{GalaxyFeatures, Hyperlanes, SpatialIndex};
+use crate::game::resources::game_status_res::{
+    FormationChoice, IsTradeRouting, PendingOrder, SIM_DT,
+};
+use crate::game::resources::log_res::ChatInput;
+use crate::game::systems::{hyperlane, orders};
+use crate::game::utils::features::{self, Feature, FeatureKind, NEBULA_SPEED};
+use crate::game::utils::flocking::{self, Boid};
+use crate::game::utils::influence::InfluenceMap;
 use crate::game::utils::layers_util::*;
+use crate::game::utils::pathfinding::{self, Obstacle, VisibilityGraph, CORRIDOR_WIDTH};
+use crate::minimap::components::MiniMap;
 use crate::selection::components::Selected;
 
 use crate::math_util;
 
+/// Distance to a planet surface at which ships start steering around it.
+const PLANET_MARGIN: f32 = 3.;
+/// Largest planet radius, so nearby planets are found from their centre.
+const MAX_PLANET_RADIUS: f32 = 12.;
+const PLANET_AVOIDANCE: f32 = 2.;
+/// Distance at which a path waypoint counts as passed.
+const WAYPOINT_REACHED: f32 = 2.;
+/// Ships ordered together from within the same cell share a planned path.
+const PATH_SHARING_CELL: f32 = 8.;
+/// Distance at which guards stop closing in on the enemy they intercept.
+const CHASE_DISTANCE: f32 = 12.;
+/// Extra cost of a path leg lying entirely in the territory of another player.
+const FOREIGN_TERRITORY_COST: f32 = 0.5;
+/// Extra cost of a path leg lying entirely in a nebula, where ships fly at half speed.
+const NEBULA_COST: f32 = 1.;
+/// Distance between two points checked for nebulae along a path leg.
+const NEBULA_SAMPLE: f32 = 5.;
+/// Visibility graphs kept for reuse; past this many the cache starts over.
+const MAX_PATH_GRAPHS: usize = 256;
+
+/// Where a ship of a held formation should be right now, its slot around the moving anchor,
+/// along with the speed of the formation. `None` for any other ship.
+fn station(
+    dest: &DestinationEnum,
+    member: Option<&FleetMember>,
+    slot: Option<&FormationSlot>,
+    fleets: &Query<&Fleet>,
+) -> Option<(Vec3, f32)> {
+    let fleet = fleets.get(member?.0).ok()?;
+    match (dest, fleet.speed) {
+        (DestinationEnum::Space(_), Some(speed)) => Some((fleet.anchor + slot?.0, speed)),
+        _ => None,
+    }
+}
+
+/// Ships in a held formation face their station until they sit on it, then their slot.
 pub fn turn_to_destination(
-    time: Res<Time>,
-    mut query: Query<(&Transform, &Destination, &mut Velocity)>,
+    mut query: Query<(
+        &Transform,
+        &Destination,
+        &PlannedPath,
+        &Engagement,
+        &mut Velocity,
+        Option<&FleetMember>,
+        Option<&FormationSlot>,
+    )>,
+    fleets: Query<&Fleet>,
 ) {
-    for (transform, destination, mut vel) in query.iter_mut() {
+    for (transform, destination, path, engagement, mut vel, member, slot) in query.iter_mut() {
+        // fighting ships face their enemy
+        if engagement.target.is_some() {
+            let angle_diff =
+                turn_to_dest_math(engagement.loc, transform.translation, transform.up());
+            if angle_diff.abs() > 0.005 {
+                let max_angvel = 10.0_f32.min(angle_diff * SIM_DT * 250.0);
+                vel.angvel = Vec3::new(0.0, 0.0, max_angvel);
+            }
+            continue;
+        }
+        if let Some(waypoint) = path.0.first().copied() {
+            let angle_diff = turn_to_dest_math(waypoint, transform.translation, transform.up());
+            if angle_diff.abs() > 0.005 {
+                let max_angvel = 10.0_f32.min(angle_diff * SIM_DT * 250.0);
+                vel.angvel = Vec3::new(0.0, 0.0, max_angvel);
+            }
+            continue;
+        }
         match destination.0 {
             DestinationEnum::Space(d) => {
+                let d = match station(&destination.0, member, slot, &fleets) {
+                    Some((station, _)) if transform.translation.distance(station) > 1.0 => station,
+                    _ => d,
+                };
                 let angle_diff = turn_to_dest_math(d, transform.translation, transform.up());
                 if angle_diff.abs() > 0.005 {
-                    let max_angvel = 10.0_f32.min(angle_diff * time.delta_seconds() * 250.0);
+                    let max_angvel = 10.0_f32.min(angle_diff * SIM_DT * 250.0);
                     vel.angvel = Vec3::new(0.0, 0.0, max_angvel);
                 }
             }
             DestinationEnum::Planet { planet: _, loc } => {
                 let angle_diff = turn_to_dest_math(loc, transform.translation, transform.up());
                 if angle_diff.abs() > 0.005 {
-                    let max_angvel = 10.0_f32.min(angle_diff * time.delta_seconds() * 250.0);
+                    let max_angvel = 10.0_f32.min(angle_diff * SIM_DT * 250.0);
                     vel.angvel = Vec3::new(0.0, 0.0, max_angvel);
                 }
             }
@@ -42,122 +123,327 @@ fn turn_to_dest_math(target: Vec3, pos: Vec3, up_pos: Vec3) -> f32 {
     math_util::get_angle_difference(target_angle, cur_angle)
 }
 
+/// Steers ships along their path. Ships in a held formation move at its speed, faster while
+/// they catch up with their station. Nebulae slow them down, and black holes pull them in
+/// whatever they are doing. Fighters sent to a planet land or lay siege on contact, other ships
+/// arrive once they reach its surface.
 pub fn move_to_destination(
-    time: Res<Time>,
+    features: Res<GalaxyFeatures>,
     mut query: Query<(
+        Entity,
         &mut Destination,
+        &mut PlannedPath,
         &mut ExternalImpulse,
         &Avoidance,
         &Transform,
-        &Movement,
+        &ShipStats,
+        &Engagement,
+        Option<&FleetMember>,
+        Option<&FormationSlot>,
+        Option<&Fighter>,
     )>,
+    fleets: Query<&Fleet>,
+    planets: Query<(&Transform, &Planet), Without<Ship>>,
     mut arrived_ev_writer: EventWriter<ArrivedAtDestination>,
 ) {
-    for (mut dest, mut impulse, avoid, transform, mov) in query.iter_mut() {
-        match dest.0 {
-            DestinationEnum::Space(loc) => {
-                let dist = transform.translation.distance(loc);
-                let accel = 4.0_f32.max(dist.min(mov.speed));
-                if dist < 1.0 {
-                    dest.0 = DestinationEnum::None;
-                    arrived_ev_writer.send(ArrivedAtDestination(loc));
-                } else {
-                    let force =
-                        (transform.up() + avoid.impulse).normalize() * accel * time.delta_seconds();
-                    impulse.impulse = force;
-                }
+    let dt = SIM_DT;
+    for (
+        ship,
+        mut dest,
+        mut path,
+        mut impulse,
+        avoid,
+        transform,
+        stats,
+        engagement,
+        member,
+        slot,
+        fighter,
+    ) in query.iter_mut()
+    {
+        let pos = transform.translation.truncate();
+        let station = station(&dest.0, member, slot, &fleets);
+        let mut speed = match station {
+            Some((station, fleet_speed)) => stats
+                .speed
+                .min(fleet_speed + transform.translation.distance(station)),
+            None => stats.speed,
+        };
+        if features::inside(&features.0, FeatureKind::Nebula, pos) {
+            speed *= NEBULA_SPEED;
+        }
+        let heading = (transform.up() + avoid.impulse).normalize_or_zero();
+        let force = if engagement.target.is_some() {
+            // engaged ships hold still to fight, guards close in first
+            let dist = transform.translation.distance(engagement.loc);
+            match engagement.chase && dist > CHASE_DISTANCE {
+                true => Some(heading * speed * dt),
+                false => Some(Vec3::ZERO),
             }
-            DestinationEnum::Planet { planet: _, loc } => {
-                let dist = transform.translation.distance(loc);
-                let accel = 4.0_f32.max(dist.min(mov.speed));
-                let force =
-                    (transform.up() + avoid.impulse).normalize() * accel * time.delta_seconds();
-                impulse.impulse = force;
+        } else if let Some(waypoint) = path.0.first().copied() {
+            if transform.translation.distance(waypoint) < WAYPOINT_REACHED {
+                path.0.remove(0);
             }
-            DestinationEnum::None => {
-                if avoid.impulse != Vec3::ZERO {
-                    let force = avoid.impulse.normalize() * time.delta_seconds();
-                    impulse.impulse = force;
+            Some(heading * speed * dt)
+        } else {
+            match dest.0 {
+                DestinationEnum::Space(loc) => {
+                    let dist = transform.translation.distance(loc);
+                    let accel = match station {
+                        Some(_) => speed,
+                        None => 4.0_f32.max(dist.min(speed)),
+                    };
+                    if dist < 1.0 {
+                        dest.0 = DestinationEnum::None;
+                        arrived_ev_writer.send(ArrivedAtDestination { ship, loc });
+                        None
+                    } else {
+                        Some(heading * accel * dt)
+                    }
+                }
+                DestinationEnum::Planet { planet, loc } => {
+                    let dist = transform.translation.distance(loc);
+                    let reached = fighter.is_none()
+                        && match planets.get(planet) {
+                            Ok((planet_transform, planet)) => {
+                                let surface = planet_type_to_radius(&planet.planet_type);
+                                pos.distance(planet_transform.translation.truncate())
+                                    < surface + PLANET_MARGIN
+                            }
+                            Err(_) => dist < 1.0,
+                        };
+                    if reached {
+                        dest.0 = DestinationEnum::None;
+                        arrived_ev_writer.send(ArrivedAtDestination { ship, loc });
+                        None
+                    } else {
+                        let accel = 4.0_f32.max(dist.min(speed));
+                        Some(heading * accel * dt)
+                    }
                 }
+                // idle ships drift apart gently instead of jumping at full strength
+                DestinationEnum::None if avoid.impulse != Vec3::ZERO => Some(avoid.impulse * dt),
+                DestinationEnum::None => None,
             }
+        };
+        let pull = (features::pull(&features.0, pos) * dt).extend(0.);
+        match force {
+            Some(force) => impulse.impulse = force + pull,
+            None if pull != Vec3::ZERO => impulse.impulse = pull,
+            None => {}
+        }
+    }
+}
+
+/// V cycles the formation of the next move orders and B toggles holding it, both shown in the
+/// selection panel.
+pub fn choose_formation(
+    kb_input: Res<Input<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut choice: ResMut<FormationChoice>,
+) {
+    if chat.active {
+        return;
+    }
+    if kb_input.just_pressed(KeyCode::V) {
+        choice.formation = choice.formation.next();
+    } else if kb_input.just_pressed(KeyCode::B) {
+        choice.hold = !choice.hold;
+    }
+}
+
+/// Right-click order for `ships`. Holding Shift queues it after their current orders.
+pub fn move_order(
+    kb_input: &Input<KeyCode>,
+    choice: &FormationChoice,
+    ships: Vec<Entity>,
+    destination: DestinationEnum,
+) -> PlayerCommand {
+    if kb_input.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+        PlayerCommand::QueueDestination { ships, destination }
+    } else {
+        PlayerCommand::MoveShips {
+            ships,
+            destination,
+            formation: choice.formation,
+            hold: choice.hold,
         }
     }
 }
 
 pub fn set_destination(
+    kb_input: Res<Input<KeyCode>>,
     ms_input: Res<Input<MouseButton>>,
     mouse_pos: Res<MouseWorldPos>,
     is_trade_routing: Res<IsTradeRouting>,
-    mut fleets_context: ResMut<MovingFleets>,
-    mut query: Query<(Entity, &mut Destination), With<Selected>>,
-    planet_query: Query<(Entity, &Planet, &Transform)>,
+    choice: Res<FormationChoice>,
+    mut pending: ResMut<PendingOrder>,
+    minimap: Res<MiniMap>,
+    query: Query<Entity, (With<Selected>, With<Destination>)>,
+    index: Res<SpatialIndex>,
+    mut cmd_writer: EventWriter<IssueCommand>,
 ) {
-    if !is_trade_routing.key_down {
-        if ms_input.pressed(MouseButton::Right) {
-            let planet_dest = vec2_to_vec3(mouse_pos.0, Layers::Planets);
-            let ship_dest = vec2_to_vec3(mouse_pos.0, Layers::Ships);
-            let target_planet = find_planet(planet_query, planet_dest);
-            match target_planet {
-                Some(e) => {
-                    for (_, mut destination) in query.iter_mut() {
-                        destination.0 = DestinationEnum::Planet {
-                            planet: e,
-                            loc: ship_dest,
-                        };
-                    }
-                }
-                None => {
-                    let mut moving_fleet = Vec::new();
-                    for (e, mut destination) in query.iter_mut() {
-                        destination.0 = DestinationEnum::Space(ship_dest);
-                        moving_fleet.push(e);
+    // orders given on the minimap are handled by minimap_orders
+    if !is_trade_routing.key_down && !minimap.hovered {
+        if ms_input.just_pressed(MouseButton::Right) {
+            let ships: Vec<Entity> = query.iter().collect();
+            if !ships.is_empty() {
+                let command = match pending.order {
+                    Some(_) => orders::give_armed_order(
+                        &mut pending,
+                        &kb_input,
+                        &choice,
+                        &index,
+                        mouse_pos.0,
+                        ships,
+                    ),
+                    None => {
+                        let destination = destination_at(&index, mouse_pos.0);
+                        Some(move_order(&kb_input, &choice, ships, destination))
                     }
-                    fleets_context.0.insert(ship_dest.to_string(), moving_fleet);
+                };
+                if let Some(command) = command {
+                    cmd_writer.send(IssueCommand(command));
                 }
             }
         }
     }
 }
 
-fn find_planet(
-    planet_query: Query<(Entity, &Planet, &Transform)>,
-    planet_dest: Vec3,
-) -> Option<Entity> {
-    for (e, planet, transform) in planet_query.iter() {
-        if planet_dest.distance(transform.translation) < planet_type_to_radius(&planet.planet_type)
-        {
-            return Some(e);
-        }
+/// Destination for a click at `mouse_pos`: the planet under the cursor, if any, or open space.
+pub fn destination_at(index: &SpatialIndex, mouse_pos: Vec2) -> DestinationEnum {
+    let planet_dest = vec2_to_vec3(mouse_pos, Layers::Planets);
+    let ship_dest = vec2_to_vec3(mouse_pos, Layers::Ships);
+    match find_planet(index, planet_dest) {
+        Some(e) => DestinationEnum::Planet {
+            planet: e,
+            loc: ship_dest,
+        },
+        None => DestinationEnum::Space(ship_dest),
     }
-    return None;
 }
 
-pub fn remove_destination(
-    mut fleets_context: ResMut<MovingFleets>,
-    mut query: Query<(Entity, &mut Destination)>,
-    mut arrived_ev_reader: EventReader<ArrivedAtDestination>,
+pub fn find_planet(index: &SpatialIndex, planet_dest: Vec3) -> Option<Entity> {
+    index.planets.at_point(planet_dest.truncate())
+}
+
+/// Plans a route around planets whenever a ship gets a new destination. Routes avoid the
+/// territory of other players where they can, and take a hyperlane when it saves time.
+/// The visibility graph of each set of obstacles is kept, so a new order only searches it.
+pub fn plan_paths(
+    index: Res<SpatialIndex>,
+    territory: Res<InfluenceMap>,
+    features: Res<GalaxyFeatures>,
+    lanes: Res<Hyperlanes>,
+    mut graphs: Local<HashMap<Vec<[u32; 3]>, VisibilityGraph>>,
+    planets: Query<(&Transform, &Planet, &Ownership), Without<Ship>>,
+    mut ships: Query<
+        (
+            &Transform,
+            &Destination,
+            &Ownership,
+            &mut PlannedPath,
+            &mut LaneJump,
+        ),
+        Changed<Destination>,
+    >,
 ) {
-    for arrived_msg in arrived_ev_reader.iter() {
-        let key = arrived_msg.0.to_string();
-        let fleet = fleets_context.0.remove(&key);
-        if let Some(fleet_entities) = fleet {
-            for e in fleet_entities {
-                let dest_res = query.get_component_mut::<Destination>(e);
-                if let Ok(mut dest) = dest_res {
-                    match dest.0 {
-                        DestinationEnum::Space(loc) => {
-                            if loc == arrived_msg.0 {
-                                dest.0 = DestinationEnum::None;
-                            }
-                        }
-                        _ => {}
-                    }
-                }
+    type Route = (Vec<Vec3>, Option<(Entity, Entity)>);
+    let mut planned: HashMap<(IVec2, IVec2, Option<Uuid>), Route> = HashMap::default();
+    for (transf, dest, owner, mut path, mut jump) in ships.iter_mut() {
+        let goal = match dest.0 {
+            DestinationEnum::Space(loc) => loc,
+            DestinationEnum::Planet { planet: _, loc } => loc,
+            DestinationEnum::None => {
+                path.0.clear();
+                jump.0 = None;
+                continue;
             }
-        }
+        };
+        let from = transf.translation.truncate();
+        let to = goal.truncate();
+        let key = (
+            (from / PATH_SHARING_CELL).floor().as_ivec2(),
+            (to * 10.).round().as_ivec2(),
+            owner.0,
+        );
+        (path.0, jump.0) = planned
+            .entry(key)
+            .or_insert_with(|| {
+                let mut leg = |from: Vec2, to: Vec2| {
+                    let reach = Vec2::splat(CORRIDOR_WIDTH + MAX_PLANET_RADIUS);
+                    let candidates = index
+                        .planets
+                        .in_rect(from.min(to) - reach, from.max(to) + reach)
+                        .into_iter()
+                        .filter_map(|planet| {
+                            Some(Obstacle {
+                                centre: index.planets.position(planet)?,
+                                radius: index.planets.radius(planet)?,
+                            })
+                        })
+                        .chain(features.0.iter().filter_map(|f| {
+                            Some(Obstacle {
+                                centre: f.centre,
+                                radius: f.obstacle_radius()?,
+                            })
+                        }));
+                    let obstacles = pathfinding::corridor(from, to, candidates);
+                    let cost = |a: Vec2, b: Vec2| {
+                        1. + FOREIGN_TERRITORY_COST * foreign_share(&territory, owner.0, a, b)
+                            + NEBULA_COST * nebula_share(&features.0, a, b)
+                    };
+                    let key = VisibilityGraph::key(&obstacles);
+                    if !graphs.contains_key(&key) && graphs.len() >= MAX_PATH_GRAPHS {
+                        graphs.clear();
+                    }
+                    graphs
+                        .entry(key)
+                        .or_insert_with(|| VisibilityGraph::new(obstacles))
+                        .plan(from, to, cost)
+                };
+                // a hyperlane trip ends at the entry planet, the rest is planned after the jump
+                let (waypoints, lane) =
+                    match hyperlane::best_lane(&lanes, &planets, owner.0, from, to) {
+                        Some(((entry, entry_pos), (exit, _))) => {
+                            (leg(from, entry_pos), Some((entry, exit)))
+                        }
+                        None => {
+                            let mut waypoints = leg(from, to);
+                            waypoints.pop();
+                            (waypoints, None)
+                        }
+                    };
+                let waypoints = waypoints
+                    .into_iter()
+                    .map(|w| vec2_to_vec3(w, Layers::Ships))
+                    .collect();
+                (waypoints, lane)
+            })
+            .clone();
     }
 }
 
+/// Share of the leg from `a` to `b` inside a nebula.
+fn nebula_share(features: &[Feature], a: Vec2, b: Vec2) -> f32 {
+    let samples = (a.distance(b) / NEBULA_SAMPLE).ceil().max(1.) as u32;
+    let inside = (0..samples)
+        .map(|i| a.lerp(b, (i as f32 + 0.5) / samples as f32))
+        .filter(|p| features::inside(features, FeatureKind::Nebula, *p))
+        .count();
+    inside as f32 / samples as f32
+}
+
+/// Share of the leg from `a` to `b` lying in the territory of a player other than `owner`.
+fn foreign_share(territory: &InfluenceMap, owner: Option<Uuid>, a: Vec2, b: Vec2) -> f32 {
+    let samples = (a.distance(b) / territory.cell_size()).ceil().max(1.) as u32;
+    let foreign = (0..samples)
+        .map(|i| a.lerp(b, (i as f32 + 0.5) / samples as f32))
+        .filter(|p| territory.owner_at(*p).map_or(false, |o| Some(o) != owner))
+        .count();
+    foreign as f32 / samples as f32
+}
+
 pub fn damping_shift(mut query: Query<(&Destination, &mut Damping)>) {
     for (destination, mut damping) in query.iter_mut() {
         match destination.0 {
@@ -171,94 +457,250 @@ pub fn damping_shift(mut query: Query<(&Destination, &mut Damping)>) {
     }
 }
 
+/// Flocking over the spatial index: separation from every ship around, alignment and cohesion
+/// with the owner's ships, and a push away from planets that are not the destination.
 pub fn collision_avoidance(
-    mut ships: Query<(Entity, &mut Avoidance, &Transform)>,
-    other_ships: Query<(Entity, &Transform), With<Ship>>,
-    rapier_context: Res<RapierContext>,
+    index: Res<SpatialIndex>,
+    mut ships: Query<(
+        Entity,
+        &mut Avoidance,
+        &Flocking,
+        &Transform,
+        &Velocity,
+        &Ownership,
+        &Destination,
+    )>,
 ) {
-    for (ship_e, mut avoidance, transform) in ships.iter_mut() {
-        avoidance.impulse = Vec3::ZERO;
-        for (col_1, col_2, intersects) in rapier_context.intersections_with(ship_e) {
-            if intersects {
-                if ship_e == col_1 {
-                    let other_ship = other_ships.get(col_2);
-                    if let Ok((_, o_transf)) = other_ship {
-                        let dist = o_transf.translation - transform.translation;
-                        let repel = dist.try_normalize();
-                        if let Some(r) = repel {
-                            avoidance.impulse = -r
-                        }
-                    }
-                } else {
-                    let other_ship = other_ships.get(col_1);
-                    if let Ok((_, o_transf)) = other_ship {
-                        let dist = o_transf.translation - transform.translation;
-                        let repel = dist.try_normalize();
-                        if let Some(r) = repel {
-                            avoidance.impulse = -r
-                        }
-                    }
-                }
-                break;
+    let boids: HashMap<Entity, Boid> = ships
+        .iter()
+        .map(|(e, _, _, transf, vel, owner, _)| {
+            let boid = Boid {
+                pos: transf.translation.truncate(),
+                vel: vel.linvel.truncate(),
+                group: owner.0.map(|uuid| uuid.as_u128()).unwrap_or_default(),
+            };
+            (e, boid)
+        })
+        .collect();
+    for (e, mut avoidance, flocking, _, _, _, dest) in ships.iter_mut() {
+        let me = &boids[&e];
+        let reach = Vec2::splat(flocking.0.radius);
+        let neighbours = index
+            .selectables
+            .in_rect(me.pos - reach, me.pos + reach)
+            .into_iter()
+            .filter_map(|other| boids.get(&other));
+        let mut steering = flocking::steer(me, neighbours, &flocking.0);
+
+        let target = match dest.0 {
+            DestinationEnum::Planet { planet, loc: _ } => Some(planet),
+            _ => None,
+        };
+        let reach = Vec2::splat(MAX_PLANET_RADIUS + PLANET_MARGIN);
+        for planet in index.planets.in_rect(me.pos - reach, me.pos + reach) {
+            if Some(planet) == target {
+                continue;
+            }
+            if let (Some(centre), Some(radius)) =
+                (index.planets.position(planet), index.planets.radius(planet))
+            {
+                steering += flocking::avoid_circle(me.pos, centre, radius, PLANET_MARGIN)
+                    * PLANET_AVOIDANCE;
             }
         }
+        let impulse = steering.extend(0.);
+        if avoidance.impulse != impulse {
+            avoidance.impulse = impulse;
+        }
     }
 }
 
+/// Holding R and right-clicking planets, in the galaxy or on the minimap, lays out a trade route
+/// for the selected traders, sent when R is released. Ctrl is left to control groups and
+/// selection.
 pub fn define_trade_route(
     kb_input: Res<Input<KeyCode>>,
     ms_input: Res<Input<MouseButton>>,
     ms_pos: Res<MouseWorldPos>,
+    minimap: Res<MiniMap>,
     mut is_trade_routing: ResMut<IsTradeRouting>,
-    planet_query: Query<(Entity, &Planet, &Transform)>,
-    mut trade_ships: Query<(&mut Destination, &mut TradeRoute), With<Selected>>,
+    chat: Res<ChatInput>,
+    index: Res<SpatialIndex>,
+    trade_ships: Query<Entity, (With<Selected>, With<Trader>)>,
+    mut cmd_writer: EventWriter<IssueCommand>,
 ) {
-    if kb_input.just_pressed(KeyCode::LControl) {
+    if kb_input.just_pressed(KeyCode::R) && !chat.active {
         is_trade_routing.key_down = true;
         is_trade_routing.trade_route = Vec::new();
     }
 
     if is_trade_routing.key_down {
         if ms_input.just_pressed(MouseButton::Right) {
-            let ship_dest = vec2_to_vec3(ms_pos.0, Layers::Ships);
-            let planet_dest = vec2_to_vec3(ms_pos.0, Layers::Planets);
-            let target_planet = find_planet(planet_query, planet_dest);
-            if let Some(_) = target_planet {
+            let pos = minimap.cursor.unwrap_or(ms_pos.0);
+            let ship_dest = vec2_to_vec3(pos, Layers::Ships);
+            let planet_dest = vec2_to_vec3(pos, Layers::Planets);
+            let target_planet = find_planet(&index, planet_dest);
+            if let Some(planet) = target_planet {
                 // TODO: not all planets are valid trade route destinations. implement this here before pushing to vector
-                is_trade_routing
-                    .trade_route
-                    .push(DestinationEnum::Space(ship_dest));
+                // stops keep their planet, so traders arrive at its surface and the info panel
+                // can tell which routes call there
+                is_trade_routing.trade_route.push(DestinationEnum::Planet {
+                    planet,
+                    loc: ship_dest,
+                });
                 dbg!("Added {:?} to route", ship_dest);
             }
         }
     }
 
-    if kb_input.just_released(KeyCode::LControl) {
+    if kb_input.just_released(KeyCode::R) && is_trade_routing.key_down {
         is_trade_routing.key_down = false;
-        for (mut dest, mut trade_route) in trade_ships.iter_mut() {
-            dest.0 = is_trade_routing.trade_route[0].clone();
-            *trade_route = TradeRoute {
-                route_loop: Some(is_trade_routing.trade_route.clone()),
-                route_size: is_trade_routing.trade_route.len(),
-                route_pos: 0,
+        let ships: Vec<Entity> = trade_ships.iter().collect();
+        if !ships.is_empty() && !is_trade_routing.trade_route.is_empty() {
+            cmd_writer.send(IssueCommand(PlayerCommand::SetTradeRoute {
+                ships,
+                route: is_trade_routing.trade_route.clone(),
+            }));
+        }
+    }
+}
+
+/// Starts `route` as a looping queue: its first stop becomes the destination.
+pub fn start_loop(dest: &mut Destination, queue: &mut OrderQueue, route: &[DestinationEnum]) {
+    if let Some(first) = route.first() {
+        dest.0 = first.clone();
+        queue.orders = route.iter().skip(1).chain(&route[..1]).cloned().collect();
+        queue.looping = true;
+    }
+}
+
+/// Trade routes are looping order queues.
+pub fn apply_trade_route(
+    mut commands: Commands,
+    mut cmd_reader: EventReader<ExecuteCommand>,
+    mut trade_ships: Query<(&mut Destination, &mut OrderQueue, &mut ShipOrder), With<Trader>>,
+) {
+    for cmd in cmd_reader.iter() {
+        if let PlayerCommand::SetTradeRoute { ships, route } = &cmd.command {
+            if route.is_empty() {
+                continue;
+            }
+            for e in ships.iter() {
+                if let Ok((mut dest, mut queue, mut order)) = trade_ships.get_mut(*e) {
+                    commands.entity(*e).remove::<FleetMember>();
+                    start_loop(&mut dest, &mut queue, route);
+                    *order = ShipOrder::Move;
+                }
             }
         }
     }
 }
 
-pub fn update_trade_destination(mut trade_ships: Query<(&mut Destination, &mut TradeRoute)>) {
-    for (mut dest, mut route) in trade_ships.iter_mut() {
-        if let Some(trade_route) = &route.route_loop {
-            match dest.0 {
-                DestinationEnum::None => {
-                    let next_planet = (route.route_pos + 1) % route.route_size;
-                    let new_dest = &trade_route[next_planet];
-                    dest.0 = new_dest.clone();
-                    route.route_pos = next_planet;
+/// Idle ships start their next queued order. Fleet members wait for the fleet to arrive.
+pub fn advance_order_queue(
+    mut query: Query<(&mut Destination, &mut OrderQueue), Without<FleetMember>>,
+) {
+    for (mut dest, mut queue) in query.iter_mut() {
+        if let DestinationEnum::None = dest.0 {
+            if let Some(next) = queue.orders.pop_front() {
+                if queue.looping {
+                    queue.orders.push_back(next.clone());
                 }
-                DestinationEnum::Space(_) => {}
-                DestinationEnum::Planet { planet: _, loc: _ } => {}
-            };
+                dest.0 = next;
+            }
+        }
+    }
+}
+
+/// Appends the destination to each ship's queue. Ships in a fleet keep their place in the
+/// formation, so the fleet arrives in shape.
+pub fn apply_queue_destination(
+    mut cmd_reader: EventReader<ExecuteCommand>,
+    mut query: Query<(&mut Destination, &mut OrderQueue, Option<&FleetMember>)>,
+    fleets: Query<&Fleet>,
+) {
+    for cmd in cmd_reader.iter() {
+        if let PlayerCommand::QueueDestination { ships, destination } = &cmd.command {
+            for e in ships.iter() {
+                if let Ok((mut dest, mut queue, member)) = query.get_mut(*e) {
+                    let mut order = destination.clone();
+                    if let (
+                        DestinationEnum::Space(loc),
+                        DestinationEnum::Space(slot),
+                        Some(fleet),
+                    ) = (
+                        &mut order,
+                        &dest.0,
+                        member.and_then(|m| fleets.get(m.0).ok()),
+                    ) {
+                        *loc += *slot - fleet.target;
+                    }
+                    match dest.0 {
+                        DestinationEnum::None if queue.orders.is_empty() => dest.0 = order,
+                        _ => queue.orders.push_back(order),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Lines from the selected ships along their planned path and queued orders.
+pub fn draw_order_lines(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    ships: Query<(&Transform, &Destination, &PlannedPath, &OrderQueue), With<Selected>>,
+    mut lines: Query<(&mut Handle<Mesh>, &mut Visibility), With<OrderLines>>,
+) {
+    let z = get_z(Layers::OrderLines);
+    let mut points: Vec<[f32; 3]> = Vec::new();
+    for (transform, dest, path, queue) in ships.iter() {
+        let first = match dest.0.loc() {
+            Some(loc) => loc,
+            None => continue,
+        };
+        let mut stops = vec![transform.translation];
+        stops.extend(path.0.iter().copied());
+        stops.push(first);
+        stops.extend(queue.orders.iter().filter_map(|o| o.loc()));
+        if queue.looping {
+            stops.push(first);
+        }
+        for pair in stops.windows(2) {
+            points.push([pair[0].x, pair[0].y, z]);
+            points.push([pair[1].x, pair[1].y, z]);
+        }
+    }
+    if points.is_empty() {
+        for (_, mut visibility) in lines.iter_mut() {
+            visibility.is_visible = false;
+        }
+        return;
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; points.len()]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; points.len()]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, points);
+    match lines.get_single_mut() {
+        Ok((mut handle, mut visibility)) => {
+            *handle = meshes.add(mesh);
+            visibility.is_visible = true;
+        }
+        Err(_) => {
+            commands
+                .spawn_bundle(MaterialMeshBundle {
+                    mesh: meshes.add(mesh),
+                    material: materials.add(StandardMaterial {
+                        base_color: Color::rgba(0.2, 0.8, 0.2, 0.6),
+                        unlit: true,
+                        alpha_mode: AlphaMode::Blend,
+                        ..default()
+                    }),
+                    ..default()
+                })
+                .insert(OrderLines);
         }
     }
 }
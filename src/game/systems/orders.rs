@@ -0,0 +1,208 @@
+use bevy::prelude::*;
+
+use crate::game::components::characteristics::*;
+use crate::game::components::commands::*;
+use crate::game::components::players::Ownership;
+use crate::game::resources::game_obj_res::SpatialIndex;
+use crate::game::resources::game_status_res::{ArmedOrder, FormationChoice, PendingOrder};
+use crate::game::resources::log_res::ChatInput;
+use crate::game::systems::movement;
+use crate::game::utils::layers_util::*;
+use crate::selection::components::Selected;
+
+/// Distance from the guarded entity within which guards intercept enemies.
+const GUARD_RADIUS: f32 = 25.;
+/// Distance from the surface of the guarded entity at which guards wait.
+const GUARD_DISTANCE: f32 = 5.;
+/// How far from a ship a click still picks it as the one to guard.
+const PICK_DISTANCE: f32 = 3.2;
+
+/// P arms a patrol, E an attack-move and Q a guard order for the next right-click. Pressing the
+/// same key again disarms it. T orders the selected ships to hold position right away.
+pub fn order_hotkeys(
+    kb_input: Res<Input<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut pending: ResMut<PendingOrder>,
+    query: Query<Entity, (With<Selected>, With<Destination>)>,
+    mut cmd_writer: EventWriter<IssueCommand>,
+) {
+    if chat.active {
+        return;
+    }
+    let armed = if kb_input.just_pressed(KeyCode::P) {
+        ArmedOrder::Patrol
+    } else if kb_input.just_pressed(KeyCode::E) {
+        ArmedOrder::AttackMove
+    } else if kb_input.just_pressed(KeyCode::Q) {
+        ArmedOrder::Guard
+    } else {
+        if kb_input.just_pressed(KeyCode::T) {
+            let ships: Vec<Entity> = query.iter().collect();
+            if !ships.is_empty() {
+                cmd_writer.send(IssueCommand(PlayerCommand::HoldPosition { ships }));
+            }
+            *pending = PendingOrder::default();
+        }
+        return;
+    };
+    pending.patrol.clear();
+    pending.order = match pending.order == Some(armed) || query.is_empty() {
+        true => None,
+        false => Some(armed),
+    };
+}
+
+/// Command for a right-click at `pos` while an order is armed. Patrol points placed with Shift
+/// keep the order armed, and so does a guard click on empty space.
+pub fn give_armed_order(
+    pending: &mut PendingOrder,
+    kb_input: &Input<KeyCode>,
+    choice: &FormationChoice,
+    index: &SpatialIndex,
+    pos: Vec2,
+    ships: Vec<Entity>,
+) -> Option<PlayerCommand> {
+    let command = match pending.order? {
+        ArmedOrder::Patrol => {
+            pending.patrol.push(vec2_to_vec3(pos, Layers::Ships));
+            if kb_input.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+                return None;
+            }
+            PlayerCommand::Patrol {
+                ships,
+                points: std::mem::take(&mut pending.patrol),
+            }
+        }
+        ArmedOrder::AttackMove => PlayerCommand::AttackMove {
+            ships,
+            destination: DestinationEnum::Space(vec2_to_vec3(pos, Layers::Ships)),
+            formation: choice.formation,
+            hold: choice.hold,
+        },
+        ArmedOrder::Guard => {
+            let target = index
+                .planets
+                .at_point(pos)
+                .or_else(|| index.selectables.nearest(pos, PICK_DISTANCE))?;
+            PlayerCommand::Guard { ships, target }
+        }
+    };
+    pending.order = None;
+    Some(command)
+}
+
+/// Patrol, guard and hold orders. The ships leave their fleet and drop their queued orders.
+pub fn apply_orders(
+    mut commands: Commands,
+    mut cmd_reader: EventReader<ExecuteCommand>,
+    mut query: Query<(
+        &Transform,
+        &mut Destination,
+        &mut OrderQueue,
+        &mut ShipOrder,
+    )>,
+) {
+    for cmd in cmd_reader.iter() {
+        let (ships, order) = match &cmd.command {
+            PlayerCommand::Patrol { ships, .. } => (ships, ShipOrder::Patrol),
+            PlayerCommand::Guard { ships, target } => (ships, ShipOrder::Guard(*target)),
+            PlayerCommand::HoldPosition { ships } => (ships, ShipOrder::Hold),
+            _ => continue,
+        };
+        for e in ships.iter() {
+            if let Ok((transform, mut dest, mut queue, mut ship_order)) = query.get_mut(*e) {
+                commands.entity(*e).remove::<FleetMember>();
+                *ship_order = match order {
+                    ShipOrder::Guard(target) if target == *e => ShipOrder::Hold,
+                    _ => order,
+                };
+                dest.0 = DestinationEnum::None;
+                queue.orders.clear();
+                queue.looping = false;
+                if let PlayerCommand::Patrol { points, .. } = &cmd.command {
+                    let mut route: Vec<DestinationEnum> =
+                        points.iter().map(|p| DestinationEnum::Space(*p)).collect();
+                    route.push(DestinationEnum::Space(transform.translation));
+                    movement::start_loop(&mut dest, &mut queue, &route);
+                }
+            }
+        }
+    }
+}
+
+/// Picks the nearest enemy ship each armed ship should fight, depending on its order. Idle
+/// guards go back to their post next to the guarded entity.
+pub fn engage_hostiles(
+    index: Res<SpatialIndex>,
+    mut fighters: Query<(
+        &Transform,
+        &Ownership,
+        &ShipStats,
+        &mut ShipOrder,
+        &mut Engagement,
+        &mut Destination,
+    ), With<Weapon>>,
+    ships: Query<(&Transform, &Ownership), With<Ship>>,
+    guarded: Query<&Transform>,
+) {
+    for (transform, owner, stats, mut order, mut engagement, mut dest) in fighters.iter_mut() {
+        let pos = transform.translation.truncate();
+        let guard = match *order {
+            ShipOrder::Move => {
+                if engagement.target.is_some() {
+                    *engagement = Engagement::default();
+                }
+                continue;
+            }
+            ShipOrder::Guard(target) => match guarded.get(target) {
+                Ok(t) => Some((target, t.translation.truncate())),
+                Err(_) => {
+                    *order = ShipOrder::Hold;
+                    None
+                }
+            },
+            _ => None,
+        };
+        let (centre, radius) = match guard {
+            Some((_, guarded_pos)) => (guarded_pos, GUARD_RADIUS),
+            None => (pos, stats.range),
+        };
+
+        let reach = Vec2::splat(radius);
+        let enemy = index
+            .selectables
+            .in_rect(centre - reach, centre + reach)
+            .into_iter()
+            .filter_map(|e| {
+                let (t, other) = ships.get(e).ok()?;
+                let dist = t.translation.truncate().distance(centre);
+                (other.0 != owner.0 && dist <= radius).then(|| (e, t.translation, dist))
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2));
+
+        match enemy {
+            Some((target, loc, _)) => {
+                *engagement = Engagement {
+                    target: Some(target),
+                    loc,
+                    chase: guard.is_some(),
+                };
+            }
+            None => {
+                if engagement.target.is_some() {
+                    *engagement = Engagement::default();
+                }
+                if let (Some((target, guarded_pos)), DestinationEnum::None) = (guard, &dest.0) {
+                    let post = index.planets.radius(target).unwrap_or(0.) + GUARD_DISTANCE;
+                    if pos.distance(guarded_pos) > post * 2. {
+                        let away = (pos - guarded_pos).try_normalize().unwrap_or(Vec2::Y);
+                        dest.0 = DestinationEnum::Space(vec2_to_vec3(
+                            guarded_pos + away * post,
+                            Layers::Ships,
+                        ));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use bevy::prelude::*;
+
+use crate::assets::materials::PlanetMaterial;
+use crate::game::components::{characteristics::*, players::Ownership};
+use crate::game::resources::player_res::{LocalPlayer, PlayerVision, RegisteredPlayers};
+use crate::selection::components::Selected;
+
+/// Share of their colour kept by planets out of sight.
+const FOG_DIM: f32 = 0.35;
+/// Seconds a planet keeps pulsing after an attack.
+const PULSE_SECONDS: f32 = 3.;
+/// Radians per second of the pulse.
+const PULSE_RATE: f32 = 8.;
+
+/// Gives every new planet a copy of its material, so the uniforms below only change that
+/// planet.
+pub fn setup_planet_materials(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<PlanetMaterial>>,
+    mut planets: Query<(Entity, &mut Handle<PlanetMaterial>), (With<Planet>, Without<PlanetLook>)>,
+) {
+    for (entity, mut handle) in planets.iter_mut() {
+        if let Some(material) = materials.get(&*handle).cloned() {
+            *handle = materials.add(material);
+            commands.entity(entity).insert(PlanetLook::default());
+        }
+    }
+}
+
+/// Keeps the material of each planet in line with what the local player knows about it: the
+/// colour of its owner, dimmed and as last seen when out of sight, a glow while selected, the
+/// progress of a capture and a pulse while it is attacked.
+pub fn draw_planets(
+    time: Res<Time>,
+    local: Option<Res<LocalPlayer>>,
+    vision: Res<PlayerVision>,
+    players: Res<RegisteredPlayers>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<PlanetMaterial>>,
+    mut neutral_image: Local<Option<Handle<Image>>>,
+    mut attacks: EventReader<PlanetAttacked>,
+    mut planets: Query<(
+        Entity,
+        &Ownership,
+        &Siege,
+        Option<&Selected>,
+        &mut PlanetLook,
+        &Handle<PlanetMaterial>,
+    )>,
+) {
+    let dt = time.delta_seconds();
+    let attacked: Vec<Entity> = attacks.iter().map(|ev| ev.planet).collect();
+    let local = local.map(|l| l.0);
+    let pulse_wave = 0.5 + 0.5 * (time.seconds_since_startup() as f32 * PULSE_RATE).sin();
+    for (entity, owner, siege, selected, mut look, handle) in planets.iter_mut() {
+        let seen = local.map_or(true, |l| vision.sees(l, entity));
+        let shown_owner = match (seen, local) {
+            (false, Some(l)) => vision.last_seen(l, entity).and_then(|intel| intel.owner),
+            _ => owner.0,
+        };
+        let player_material = |player: Option<_>| {
+            let details = players.0.get(&player?)?;
+            materials.get(&details.new_color)
+        };
+        let (color, image) = match player_material(shown_owner) {
+            Some(m) => (m.color, m.image.clone()),
+            None => (
+                Color::GRAY,
+                neutral_image
+                    .get_or_insert_with(|| asset_server.load("img/Planet2_40.png"))
+                    .clone(),
+            ),
+        };
+        let color = match seen {
+            true => color,
+            false => Color::rgb(
+                color.r() * FOG_DIM,
+                color.g() * FOG_DIM,
+                color.b() * FOG_DIM,
+            ),
+        };
+        let capture_color = player_material(siege.capturer).map_or(Color::WHITE, |m| m.color);
+
+        look.attacked = match attacked.contains(&entity) {
+            true => PULSE_SECONDS,
+            false => (look.attacked - dt).max(0.),
+        };
+        // sieges keep the planet pulsing for as long as they last
+        let strength = match siege.progress > 0. || !siege.losses.is_empty() {
+            true => 1.,
+            false => look.attacked / PULSE_SECONDS,
+        };
+        let (capture, pulse) = match seen {
+            true => (siege.progress.clamp(0., 1.), strength * pulse_wave),
+            false => (0., 0.),
+        };
+        let glow = match selected {
+            Some(_) => 1.,
+            None => 0.,
+        };
+
+        let current = match materials.get(handle) {
+            Some(material) => material,
+            None => continue,
+        };
+        if current.color != color
+            || current.image != image
+            || current.capture_color != capture_color
+            || current.glow != glow
+            || current.capture != capture
+            || current.pulse != pulse
+        {
+            if let Some(material) = materials.get_mut(handle) {
+                material.color = color;
+                material.image = image;
+                material.capture_color = capture_color;
+                material.glow = glow;
+                material.capture = capture;
+                material.pulse = pulse;
+            }
+        }
+    }
+}
@@ -1,19 +1,50 @@
 use std::f32::consts::PI;
 
-use crate::assets::materials::PlanetMaterial;
 use crate::camera::MouseWorldPos;
-use crate::game::components::{characteristics::*, players::Ownership};
-use crate::game::utils::layers_util::{vec2_to_vec3, Layers};
+use crate::game::components::{characteristics::*, commands::*, players::Ownership};
+use crate::game::systems::{garrison, movement, siege};
+use crate::game::utils::formation::Formation;
+use crate::game::utils::layers_util::Layers;
+use crate::game::utils::{balance::Balance, stats::Stat};
 use crate::game::{
-    self,
-    resources::{game_obj_res::*, player_res},
-    utils::layers_util,
+    self, obj,
+    resources::{
+        game_obj_res::*,
+        game_status_res::{DeployChoice, MatchSetup, SIM_DT},
+        log_res::ChatInput,
+        player_res,
+    },
+    utils::{layers_util, names},
 };
+use crate::minimap::components::MiniMap;
 use crate::selection::components::Selected;
 use bevy::prelude::*;
+use bevy::utils::{HashMap, Uuid};
 use bevy_rapier3d::prelude::*;
 use bevy_text_mesh::prelude::*;
 
+/// Fighters a planet always keeps back when deploying.
+pub const MIN_GARRISON: u32 = 1;
+/// Seconds between two production ticks, before research.
+pub const PRODUCTION_SECONDS: f32 = 2.;
+/// Width of a planet name character, to centre the label.
+const LABEL_CHAR_WIDTH: f32 = 0.35;
+/// Distance from the planet surface to its name.
+const LABEL_GAP: f32 = 1.;
+
+/// Alt with one of these builds the building with the same position in the balance file.
+const BUILD_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
 pub fn count_fighters_deployed(query: Query<&Fighter>, mut res: ResMut<FightersDeployed>) {
     let deployed_fighters = query.iter().count() as u32;
     res.0 = deployed_fighters;
@@ -33,42 +64,256 @@ pub fn count_traders(query: Query<&Trader>, mut res: ResMut<TotalTraders>) {
     res.0 = query.iter().count() as u32;
 }
 
-pub fn production_tick(mut query: Query<(&mut Planet, &Ownership)>) {
-    for (mut planet, owner) in query.iter_mut() {
-        if let Some(_) = owner.0 {
-            planet.fighters += 1.;
+pub fn count_dreadnoughts(query: Query<&Dreadnought>, mut res: ResMut<TotalDreadnoughts>) {
+    res.0 = query.iter().count() as u32;
+}
+
+/// Every player has its own production clock, as research can make its ticks faster.
+pub fn production_tick(
+    balance: Res<Balance>,
+    players: Res<player_res::RegisteredPlayers>,
+    research: Res<player_res::PlayerResearch>,
+    mut clocks: Local<HashMap<Uuid, f32>>,
+    mut money: ResMut<player_res::PlayerMoney>,
+    mut query: Query<(&mut Planet, &Buildings, &Ownership)>,
+) {
+    let mut due = Vec::new();
+    for player in players.0.keys() {
+        let interval = balance.stat(
+            research.0.get(player),
+            Stat::ProductionInterval,
+            PRODUCTION_SECONDS,
+        );
+        let clock = clocks.entry(*player).or_default();
+        *clock += SIM_DT;
+        if *clock >= interval {
+            *clock -= interval;
+            due.push(*player);
+        }
+    }
+    if due.is_empty() {
+        return;
+    }
+    for (mut planet, buildings, owner) in query.iter_mut() {
+        if let Some(player) = owner.0.filter(|p| due.contains(p)) {
+            planet.fighters += balance.production(&planet, buildings);
+            *money.0.entry(player).or_default() += balance.income(buildings);
+        }
+    }
+}
+
+/// U upgrades the selected planets, Alt with a number key builds the building with that number.
+/// Y buys a trader at each of them, J a dreadnought.
+pub fn planet_orders(
+    kb_input: Res<Input<KeyCode>>,
+    chat: Res<ChatInput>,
+    selected_planets: Query<Entity, (With<Planet>, With<Selected>)>,
+    mut cmd_writer: EventWriter<IssueCommand>,
+) {
+    if chat.active {
+        return;
+    }
+    let planets: Vec<Entity> = selected_planets.iter().collect();
+    if planets.is_empty() {
+        return;
+    }
+    if kb_input.just_pressed(KeyCode::U) {
+        cmd_writer.send(IssueCommand(PlayerCommand::UpgradePlanets { planets }));
+    } else if kb_input.just_pressed(KeyCode::Y) {
+        cmd_writer.send(IssueCommand(PlayerCommand::BuyShip {
+            planets,
+            ship_type: ShipType::Trade,
+        }));
+    } else if kb_input.just_pressed(KeyCode::J) {
+        cmd_writer.send(IssueCommand(PlayerCommand::BuyShip {
+            planets,
+            ship_type: ShipType::Dreadnought,
+        }));
+    } else if kb_input.any_pressed([KeyCode::LAlt, KeyCode::RAlt]) {
+        if let Some(building) = BUILD_KEYS.iter().position(|k| kb_input.just_pressed(*k)) {
+            cmd_writer.send(IssueCommand(PlayerCommand::Build {
+                planets,
+                building: building as u8,
+            }));
+        }
+    }
+}
+
+/// Pays for upgrades and buildings, planet by planet until the money runs out. Planets that cannot
+/// be upgraded or have no free slot are skipped.
+pub fn apply_planet_orders(
+    balance: Res<Balance>,
+    mut money: ResMut<player_res::PlayerMoney>,
+    mut cmd_reader: EventReader<ExecuteCommand>,
+    mut planets: Query<(&mut Planet, &mut Buildings)>,
+) {
+    for cmd in cmd_reader.iter() {
+        let funds = money.0.entry(cmd.player).or_default();
+        match &cmd.command {
+            PlayerCommand::UpgradePlanets { planets: targets } => {
+                for e in targets.iter() {
+                    if let Ok((mut planet, _)) = planets.get_mut(*e) {
+                        let next = match next_planet_type(&planet.planet_type) {
+                            Some(next) => next,
+                            None => continue,
+                        };
+                        let cost = balance.planet(planet.planet_type).upgrade_cost;
+                        if cost <= *funds {
+                            *funds -= cost;
+                            planet.planet_type = next;
+                        }
+                    }
+                }
+            }
+            PlayerCommand::Build {
+                planets: targets,
+                building,
+            } => {
+                let cost = balance.buildings[*building as usize].cost;
+                for e in targets.iter() {
+                    if let Ok((planet, mut buildings)) = planets.get_mut(*e) {
+                        let slots = balance.planet(planet.planet_type).slots;
+                        if buildings.0.len() < slots && cost <= *funds {
+                            *funds -= cost;
+                            buildings.0.push(*building);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Buys ships next to each planet as long as the money lasts. Ships still locked by research are
+/// not sold.
+pub fn apply_buy_ships(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut net_ids: ResMut<NetIdAllocator>,
+    balance: Res<Balance>,
+    players: Res<player_res::RegisteredPlayers>,
+    research: Res<player_res::PlayerResearch>,
+    mut money: ResMut<player_res::PlayerMoney>,
+    mut cmd_reader: EventReader<ExecuteCommand>,
+    planets: Query<(&Planet, &GlobalTransform)>,
+) {
+    for cmd in cmd_reader.iter() {
+        if let PlayerCommand::BuyShip {
+            planets: targets,
+            ship_type,
+        } = &cmd.command
+        {
+            let player_research = research.0.get(&cmd.player);
+            let cost = match balance.ship_cost(player_research, *ship_type) {
+                Some(cost) if balance.unlocked(player_research, *ship_type) => cost,
+                _ => continue,
+            };
+            let details = match players.0.get(&cmd.player) {
+                Some(details) => details,
+                None => continue,
+            };
+            let funds = money.0.entry(cmd.player).or_default();
+            for e in targets.iter() {
+                if let Ok((planet, transform)) = planets.get(*e) {
+                    if cost > *funds {
+                        break;
+                    }
+                    *funds -= cost;
+                    let radius = planet_type_to_radius(&planet.planet_type);
+                    game::spawn_ship(
+                        &mut commands,
+                        &mut meshes,
+                        &mut net_ids,
+                        *ship_type,
+                        compute_ship_spawn_position(0, transform.translation(), radius),
+                        DestinationEnum::None,
+                        &cmd.player,
+                        details,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Gives planets whose type changed a mesh and a collider of their new size.
+pub fn resize_planets(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut query: Query<
+        (&Planet, &mut Collider, &mut Handle<Mesh>, Option<&Children>),
+        Changed<Planet>,
+    >,
+    mut labels: Query<&mut Transform, With<PlanetLabel>>,
+) {
+    for (planet, mut collider, mut mesh, children) in query.iter_mut() {
+        let radius = planet_type_to_radius(&planet.planet_type);
+        if collider
+            .as_ball()
+            .map_or(true, |ball| ball.radius() != radius)
+        {
+            *collider = Collider::ball(radius);
+            *mesh = meshes.add(obj::planet_mesh(radius));
+            // keep the name above the grown planet
+            for child in children.into_iter().flat_map(|c| c.iter()) {
+                if let Ok(mut transform) = labels.get_mut(*child) {
+                    transform.translation.y = radius + LABEL_GAP;
+                }
+            }
         }
     }
 }
 
 pub fn fighter_enters_planet(
-    mut query: Query<(Entity, &mut Planet, &Ownership)>,
-    query_ships: Query<(Entity, &Fighter, &Destination, &Ownership)>,
+    mut query: Query<(Entity, &mut Planet, &Ownership, &Transform)>,
+    mut query_ships: Query<
+        (&mut Destination, &Ownership, &Transform),
+        (With<Fighter>, Without<Planet>),
+    >,
     mut commands: Commands,
     mut collision_events: EventReader<CollisionEvent>,
-    mut ev_writer: EventWriter<TakeOwnership>,
+    mut attack_writer: EventWriter<PlanetAttacked>,
 ) {
     for collision_event in collision_events.iter() {
         match collision_event {
             CollisionEvent::Started(planet, ship, _) => {
-                if let Ok((_, _, dest, ship_owner)) = query_ships.get(*ship) {
+                if let Ok((mut dest, ship_owner, ship_transform)) = query_ships.get_mut(*ship) {
                     if let DestinationEnum::Planet { planet: p, loc: _ } = dest.0 {
                         if *planet == p {
-                            commands.entity(*ship).despawn_recursive();
-                            if let Ok((entity, mut planet, planet_owner)) = query.get_mut(*planet) {
+                            if let Ok((entity, mut planet, planet_owner, transform)) =
+                                query.get_mut(*planet)
+                            {
                                 // IF planet and owner matches, "store" ship in planet
                                 if ship_owner.0 == planet_owner.0 {
+                                    commands.entity(*ship).despawn_recursive();
                                     planet.fighters += 1.;
                                 }
-                                // ELSE, it is neutral or enemy planet. Take ownership if planet has zero ships or destroy local ships
+                                // ELSE, it is neutral or enemy planet. Stay in orbit and lay siege
                                 else {
-                                    if planet.fighters == 0. {
-                                        ev_writer.send(TakeOwnership {
-                                            entity,
-                                            owner: ship_owner.0.unwrap(),
+                                    let centre = transform.translation.truncate();
+                                    let away = (ship_transform.translation.truncate() - centre)
+                                        .try_normalize()
+                                        .unwrap_or(Vec2::Y);
+                                    let post = layers_util::vec2_to_vec3(
+                                        centre
+                                            + away
+                                                * (planet_type_to_radius(&planet.planet_type)
+                                                    + siege::SIEGE_GAP),
+                                        Layers::Ships,
+                                    );
+                                    dest.0 = DestinationEnum::Space(post);
+                                    commands.entity(*ship).insert(Sieging {
+                                        planet: entity,
+                                        post,
+                                    });
+                                    if let (Some(owner), Some(attacker)) =
+                                        (planet_owner.0, ship_owner.0)
+                                    {
+                                        attack_writer.send(PlanetAttacked {
+                                            planet: entity,
+                                            owner,
+                                            attacker,
                                         });
-                                    } else {
-                                        planet.fighters -= 1.;
                                     }
                                 }
                             }
@@ -81,69 +326,193 @@ pub fn fighter_enters_planet(
     }
 }
 
+/// Right-click launches fighters from the selected planets: all they can spare, half with Shift,
+/// a quarter with Alt, or the exact number chosen with `choose_deploy_amount`.
 pub fn deploy_fighters(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut set: ParamSet<(
-        Query<(Entity, &Planet, &Transform)>,
-        Query<(&mut Planet, &Ownership, &GlobalTransform), With<Selected>>,
-    )>,
-    mut fleets_context: ResMut<MovingFleets>,
-    players: Res<player_res::RegisteredPlayers>,
+    kb_input: Res<Input<KeyCode>>,
     mouse: Res<Input<MouseButton>>,
     mouse_pos: Res<MouseWorldPos>,
+    minimap: Res<MiniMap>,
+    index: Res<SpatialIndex>,
+    choice: Res<DeployChoice>,
+    selected_planets: Query<Entity, (With<Planet>, With<Selected>)>,
+    mut cmd_writer: EventWriter<IssueCommand>,
 ) {
-    if mouse.just_pressed(MouseButton::Right) {
-        let mut find_destination = None;
-        let planet_dest = vec2_to_vec3(mouse_pos.0, Layers::Planets);
-        let ship_dest = vec2_to_vec3(mouse_pos.0, Layers::Ships);
-        for (e, planet, transform) in set.p0().iter() {
-            if planet_dest.distance(transform.translation)
-                < planet_type_to_radius(&planet.planet_type)
-            {
-                find_destination = Some(e);
-                break;
+    if mouse.just_pressed(MouseButton::Right) && !minimap.hovered {
+        let planets: Vec<Entity> = selected_planets.iter().collect();
+        if !planets.is_empty() {
+            cmd_writer.send(IssueCommand(PlayerCommand::DeployFighters {
+                planets,
+                destination: movement::destination_at(&index, mouse_pos.0),
+                amount: deploy_amount(&kb_input, &choice),
+            }));
+        }
+    }
+}
+
+/// Amount the next right-click deploys, given the modifiers held.
+pub fn deploy_amount(kb_input: &Input<KeyCode>, choice: &DeployChoice) -> DeployAmount {
+    if kb_input.any_pressed([KeyCode::LAlt, KeyCode::RAlt]) {
+        DeployAmount::Share(25)
+    } else if kb_input.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+        DeployAmount::Share(50)
+    } else {
+        match choice.exact {
+            Some(count) => DeployAmount::Exact(count),
+            None => DeployAmount::Share(100),
+        }
+    }
+}
+
+/// With planets selected, + and - set an exact number of fighters to deploy, by ten with Shift.
+/// 0 goes back to deploying shares of the garrison.
+pub fn choose_deploy_amount(
+    kb_input: Res<Input<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut choice: ResMut<DeployChoice>,
+    selected_planets: Query<(), (With<Planet>, With<Selected>)>,
+) {
+    if chat.active || selected_planets.is_empty() {
+        return;
+    }
+    let step = match kb_input.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+        true => 10,
+        false => 1,
+    };
+    if kb_input.just_pressed(KeyCode::Equals) {
+        choice.exact = Some(choice.exact.unwrap_or(0) + step);
+    } else if kb_input.just_pressed(KeyCode::Minus) {
+        choice.exact = choice.exact.map(|count| count.saturating_sub(step).max(1));
+    } else if kb_input.just_pressed(KeyCode::Key0) {
+        choice.exact = None;
+    }
+}
+
+/// Fighters a planet holding `fighters` can send, keeping its minimum garrison.
+pub fn spare_fighters(fighters: f32) -> u32 {
+    (fighters.max(0.) as u32).saturating_sub(MIN_GARRISON)
+}
+
+/// Fighters each planet sends for `amount`, given what each can spare. Shares are rounded to
+/// the nearest fighter. Exact amounts are split in proportion to what each planet can spare,
+/// the fighters left over by rounding down going to the largest remainders.
+pub fn split_deployment(spare: &[u32], amount: DeployAmount) -> Vec<u32> {
+    match amount {
+        DeployAmount::Share(percent) => {
+            let percent = percent.min(100) as u32;
+            spare.iter().map(|s| (s * percent + 50) / 100).collect()
+        }
+        DeployAmount::Exact(count) => {
+            let total: u32 = spare.iter().sum();
+            if total == 0 {
+                return vec![0; spare.len()];
+            }
+            let count = count.min(total) as u64;
+            let share = |s: u32| s as u64 * count;
+            let mut sent: Vec<u32> = spare
+                .iter()
+                .map(|s| (share(*s) / total as u64) as u32)
+                .collect();
+            let mut left = count as u32 - sent.iter().sum::<u32>();
+            // stable sort, so ties go to the planet listed first on every peer
+            let mut by_remainder: Vec<usize> = (0..spare.len()).collect();
+            by_remainder.sort_by_key(|i| std::cmp::Reverse(share(spare[*i]) % total as u64));
+            for i in by_remainder {
+                if left == 0 {
+                    break;
+                }
+                if sent[i] < spare[i] {
+                    sent[i] += 1;
+                    left -= 1;
+                }
             }
+            sent
         }
-        let dest = match find_destination {
-            Some(e) => DestinationEnum::Planet {
-                planet: e,
-                loc: ship_dest,
-            },
-            None => DestinationEnum::Space(ship_dest),
-        };
-
-        let mut moving_fleet = Vec::new();
-        for (mut planet, owner, transform) in set.p1().iter_mut() {
-            if let Some(p_uuid) = owner.0 {
-                let player_details = players.0.get(&p_uuid).unwrap();
-                for i in 0..planet.fighters as i32 {
-                    let ship_pos = compute_ship_spawn_position(
-                        i,
-                        transform.translation(),
-                        planet_type_to_radius(&planet.planet_type),
-                    );
-                    let entity = game::spawn_ship(
-                        &mut commands,
-                        &mut meshes,
-                        ShipType::Fighter,
-                        ship_pos,
-                        dest.clone(),
-                        &p_uuid,
-                        player_details,
-                    );
-                    match dest {
-                        DestinationEnum::Space(_) => {
-                            moving_fleet.push(entity);
+    }
+}
+
+pub fn apply_deploy_fighters(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut net_ids: ResMut<NetIdAllocator>,
+    mut cmd_reader: EventReader<ExecuteCommand>,
+    mut planets: Query<(&mut Planet, &Ownership, &GlobalTransform, Option<&Garrison>)>,
+    players: Res<player_res::RegisteredPlayers>,
+) {
+    for cmd in cmd_reader.iter() {
+        if let PlayerCommand::DeployFighters {
+            planets: sources,
+            destination: dest,
+            amount,
+        } = &cmd.command
+        {
+            let sources: Vec<Entity> = sources
+                .iter()
+                .copied()
+                .filter(|e| planets.contains(*e))
+                .collect();
+            let spare: Vec<u32> = sources
+                .iter()
+                .map(|e| spare_fighters(planets.get(*e).unwrap().0.fighters))
+                .collect();
+            let mut moving_fleet = Vec::new();
+            let mut launched_from = Vec3::ZERO;
+            for (source, sent) in sources.iter().zip(split_deployment(&spare, *amount)) {
+                if let Ok((mut planet, owner, transform, garrison)) = planets.get_mut(*source) {
+                    if let Some(p_uuid) = owner.0 {
+                        let player_details = players.0.get(&p_uuid).unwrap();
+                        let radius = planet_type_to_radius(&planet.planet_type);
+                        let stored = planet.fighters.max(0.) as u32;
+                        for i in 0..sent as i32 {
+                            let ship_pos = match garrison {
+                                // the outermost defenders break orbit, where they already fly
+                                Some(garrison) => {
+                                    let slot = stored.saturating_sub(1 + i as u32);
+                                    let pos = transform.translation().truncate()
+                                        + garrison::orbit_slot(slot, radius, garrison.phase);
+                                    let z = layers_util::get_z(Layers::Ships);
+                                    Transform::from_xyz(pos.x, pos.y, z)
+                                }
+                                None => {
+                                    compute_ship_spawn_position(i, transform.translation(), radius)
+                                }
+                            };
+                            let entity = game::spawn_ship(
+                                &mut commands,
+                                &mut meshes,
+                                &mut net_ids,
+                                ShipType::Fighter,
+                                ship_pos,
+                                dest.clone(),
+                                &p_uuid,
+                                player_details,
+                            );
+                            match dest {
+                                DestinationEnum::Space(_) => {
+                                    moving_fleet.push(entity);
+                                    launched_from += ship_pos.translation;
+                                }
+                                _ => {}
+                            }
                         }
-                        _ => {}
+                        planet.fighters -= sent as f32;
                     }
                 }
-                planet.fighters = 0.0;
             }
-        }
-        if !moving_fleet.is_empty() {
-            fleets_context.0.insert(ship_dest.to_string(), moving_fleet);
+            if let DestinationEnum::Space(ship_dest) = dest {
+                if !moving_fleet.is_empty() {
+                    let anchor = launched_from / moving_fleet.len() as f32;
+                    // freshly launched fighters all aim at the same point
+                    obj::spawn_fleet(
+                        &mut commands,
+                        moving_fleet,
+                        Formation::Cluster,
+                        *ship_dest,
+                        None,
+                        anchor,
+                    );
+                }
+            }
         }
     }
 }
@@ -157,12 +526,24 @@ fn compute_ship_spawn_position(i: i32, translation: Vec3, size: f32) -> Transfor
     Transform::from_xyz(x, y, z)
 }
 
-pub fn update_count_mesh(mut q_child: Query<(&Parent, &mut TextMesh)>, q_parent: Query<&Planet>) {
+/// Planets out of sight of the local player show their garrison when last seen, or `?`.
+pub fn update_count_mesh(
+    local: Option<Res<player_res::LocalPlayer>>,
+    vision: Res<player_res::PlayerVision>,
+    mut q_child: Query<(&Parent, &mut TextMesh), Without<PlanetLabel>>,
+    q_parent: Query<&Planet>,
+) {
     // TODO: CHECK IF QUERYING ALL TEXTMESHES IS OK OR WE NEED TO ADD A COMPONENT TO LIMIT FILTER.
     for (parent, mut text_mesh) in q_child.iter_mut() {
         let parent_planet = q_parent.get(**parent);
         if let Ok(planet) = parent_planet {
-            let updated_text = format!("{}", planet.fighters);
+            let updated_text = match local.as_ref().map(|l| l.0) {
+                Some(l) if !vision.sees(l, **parent) => match vision.last_seen(l, **parent) {
+                    Some(intel) => format!("{}", intel.fighters),
+                    None => "?".to_string(),
+                },
+                _ => format!("{}", planet.fighters),
+            };
             if text_mesh.text != updated_text {
                 text_mesh.text = updated_text;
             }
@@ -170,16 +551,106 @@ pub fn update_count_mesh(mut q_child: Query<(&Parent, &mut TextMesh)>, q_parent:
     }
 }
 
+/// Names new planets and writes the name above their fighter count. Replicas get their server
+/// id before this runs, so every peer picks the same name.
+pub fn name_planets(
+    mut commands: Commands,
+    match_setup: Res<MatchSetup>,
+    asset_server: Res<AssetServer>,
+    planets: Query<(Entity, &NetId, &Planet), Without<PlanetName>>,
+) {
+    for (entity, net_id, planet) in planets.iter() {
+        let name = names::planet_name(match_setup.seed, net_id.0);
+        let width = name.len() as f32 * LABEL_CHAR_WIDTH;
+        let top = planet_type_to_radius(&planet.planet_type) + LABEL_GAP;
+        commands.entity(entity).with_children(|parent| {
+            parent
+                .spawn_bundle(TextMeshBundle {
+                    text_mesh: TextMesh {
+                        text: name.clone(),
+                        style: TextMeshStyle {
+                            font: asset_server.load("fonts/ShareTechMono.ttf"),
+                            font_size: SizeUnit::NonStandard(60.),
+                            color: Color::rgb(0.8, 0.8, 0.8),
+                            mesh_quality: Quality::Custom(128),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    transform: Transform::from_xyz(
+                        -width / 2.,
+                        top,
+                        layers_util::get_z(Layers::Text),
+                    ),
+                    ..Default::default()
+                })
+                .insert(PlanetLabel);
+        });
+        commands.entity(entity).insert(PlanetName(name));
+    }
+}
+
+/// Hands captured planets over. Their material follows the new owner in `draw_planets`.
 pub fn take_planet_ownership(
     mut ev_reader: EventReader<TakeOwnership>,
-    players: Res<player_res::RegisteredPlayers>,
-    mut query: Query<(With<Planet>, &mut Ownership, &mut Handle<PlanetMaterial>)>,
+    mut query: Query<&mut Ownership, With<Planet>>,
 ) {
     for event in ev_reader.iter() {
-        let player_details = players.0.get(&event.owner).unwrap();
-        if let Ok((_, mut p_owner, mut p_material)) = query.get_mut(event.entity) {
+        if let Ok(mut p_owner) = query.get_mut(event.entity) {
             p_owner.0 = Some(event.owner);
-            *p_material = player_details.new_color.clone();
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shares_round_to_the_nearest_fighter() {
+        let spare = [3, 5, 1];
+        assert_eq!(
+            split_deployment(&spare, DeployAmount::Share(100)),
+            [3, 5, 1]
+        );
+        assert_eq!(split_deployment(&spare, DeployAmount::Share(50)), [2, 3, 1]);
+        assert_eq!(split_deployment(&spare, DeployAmount::Share(25)), [1, 1, 0]);
+        // more than everything is everything
+        assert_eq!(
+            split_deployment(&spare, DeployAmount::Share(200)),
+            [3, 5, 1]
+        );
+    }
+
+    #[test]
+    fn exact_amounts_follow_what_each_planet_spares() {
+        assert_eq!(
+            split_deployment(&[10, 20, 30], DeployAmount::Exact(6)),
+            [1, 2, 3]
+        );
+        // 2.5, 1.5 and 1 fighters: the tie on the remainder goes to the first planet
+        assert_eq!(
+            split_deployment(&[5, 3, 2], DeployAmount::Exact(5)),
+            [3, 1, 1]
+        );
+        assert_eq!(
+            split_deployment(&[1, 1, 1], DeployAmount::Exact(2)),
+            [1, 1, 0]
+        );
+    }
+
+    #[test]
+    fn exact_amounts_above_the_total_send_everything() {
+        assert_eq!(split_deployment(&[2, 3], DeployAmount::Exact(100)), [2, 3]);
+        assert_eq!(split_deployment(&[0, 0], DeployAmount::Exact(5)), [0, 0]);
+    }
+
+    #[test]
+    fn planets_keep_their_minimum_garrison() {
+        assert_eq!(spare_fighters(MIN_GARRISON as f32), 0);
+        assert_eq!(spare_fighters(10.), 10 - MIN_GARRISON);
+        assert_eq!(spare_fighters(5.7), 5 - MIN_GARRISON);
+        assert_eq!(spare_fighters(0.), 0);
+        assert_eq!(spare_fighters(-3.), 0);
+    }
+}
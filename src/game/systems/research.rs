@@ -0,0 +1,157 @@
+use bevy::prelude::*;
+
+use crate::game::components::{characteristics::*, commands::*, players::Ownership};
+use crate::game::resources::{
+    game_status_res::{ResearchView, SIM_DT},
+    log_res::ChatInput,
+    player_res::{LocalPlayer, PlayerMoney, PlayerResearch},
+};
+use crate::game::utils::{balance::Balance, stats::Stat};
+
+/// Each key starts the technology with the same position in the balance file.
+const RESEARCH_KEYS: [KeyCode; 8] = [
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+];
+
+/// F4 opens the research panel, F5 to F12 start researching a technology.
+pub fn research_hotkeys(
+    kb_input: Res<Input<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut view: ResMut<ResearchView>,
+    mut cmd_writer: EventWriter<IssueCommand>,
+) {
+    if chat.active {
+        return;
+    }
+    if kb_input.just_pressed(KeyCode::F4) {
+        view.open = !view.open;
+    }
+    if let Some(tech) = RESEARCH_KEYS.iter().position(|k| kb_input.just_pressed(*k)) {
+        cmd_writer.send(IssueCommand(PlayerCommand::Research { tech: tech as u8 }));
+    }
+}
+
+/// Pays for and starts research, if the player has the money and researches nothing else.
+pub fn apply_research(
+    balance: Res<Balance>,
+    mut money: ResMut<PlayerMoney>,
+    mut research: ResMut<PlayerResearch>,
+    mut cmd_reader: EventReader<ExecuteCommand>,
+) {
+    for cmd in cmd_reader.iter() {
+        if let PlayerCommand::Research { tech } = cmd.command {
+            let progress = research.0.entry(cmd.player).or_default();
+            let funds = money.0.entry(cmd.player).or_default();
+            let cost = balance.techs[tech as usize].cost;
+            if balance.can_research(Some(progress), tech) && cost <= *funds {
+                *funds -= cost;
+                progress.current = Some(tech);
+                progress.elapsed = 0.;
+            }
+        }
+    }
+}
+
+pub fn advance_research(
+    balance: Res<Balance>,
+    mut research: ResMut<PlayerResearch>,
+    mut ev_writer: EventWriter<ResearchCompleted>,
+) {
+    for (player, progress) in research.0.iter_mut() {
+        let tech = match progress.current {
+            Some(tech) => tech,
+            None => continue,
+        };
+        progress.elapsed += SIM_DT;
+        if progress.elapsed >= balance.techs[tech as usize].seconds {
+            progress.done.push(tech);
+            progress.current = None;
+            progress.elapsed = 0.;
+            ev_writer.send(ResearchCompleted {
+                player: *player,
+                tech,
+            });
+        }
+    }
+}
+
+/// Works out the effective stats of new ships, and of every ship once a technology completes.
+/// Clients of a dedicated server run it too, on the research sent along with snapshots.
+pub fn update_ship_stats(
+    balance: Res<Balance>,
+    research: Res<PlayerResearch>,
+    mut researched: Local<usize>,
+    mut query: Query<(
+        ChangeTrackers<ShipStats>,
+        &mut ShipStats,
+        &Movement,
+        Option<&Weapon>,
+        Option<&Fighter>,
+        &Ownership,
+    )>,
+) {
+    let done: usize = research.0.values().map(|r| r.done.len()).sum();
+    let refresh = done != *researched;
+    *researched = done;
+    for (tracker, mut stats, movement, weapon, fighter, owner) in query.iter_mut() {
+        if !refresh && !tracker.is_added() {
+            continue;
+        }
+        let player_research = owner.0.and_then(|p| research.0.get(&p));
+        stats.speed = match fighter {
+            Some(_) => balance.stat(player_research, Stat::FighterSpeed, movement.speed),
+            None => movement.speed,
+        };
+        stats.range = weapon.map_or(0., |w| {
+            balance.stat(player_research, Stat::WeaponRange, w.range)
+        });
+    }
+}
+
+/// Lists every technology with its key, cost and effects, and how far the current one is.
+pub fn update_research_view(
+    balance: Res<Balance>,
+    research: Res<PlayerResearch>,
+    money: Res<PlayerMoney>,
+    local: Res<LocalPlayer>,
+    mut view: ResMut<ResearchView>,
+) {
+    let progress = research.0.get(&local.0);
+    let mut lines = vec![format!(
+        "Research, money {}",
+        money.0.get(&local.0).copied().unwrap_or(0)
+    )];
+    for (i, tech) in balance.techs.iter().enumerate() {
+        let i = i as u8;
+        let state = if progress.map_or(false, |r| r.done.contains(&i)) {
+            "done".to_string()
+        } else if let Some(r) = progress.filter(|r| r.current == Some(i)) {
+            format!("{:.0}%", r.elapsed / tech.seconds * 100.)
+        } else if balance.can_research(progress, i) {
+            format!("{} money, {}s", tech.cost, tech.seconds)
+        } else {
+            "locked".to_string()
+        };
+        let key = match RESEARCH_KEYS.get(i as usize) {
+            Some(_) => format!("F{}", i + 5),
+            None => "-".to_string(),
+        };
+        lines.push(format!(
+            "{} {}: {} ({})",
+            key,
+            tech.name,
+            tech.effects(),
+            state
+        ));
+    }
+    if view.lines != lines {
+        view.lines = lines;
+    }
+}
@@ -0,0 +1,145 @@
+use bevy::prelude::*;
+use bevy::utils::{HashMap, Uuid};
+
+use crate::game::components::characteristics::*;
+use crate::game::components::players::Ownership;
+use crate::game::resources::game_status_res::SIM_DT;
+use crate::game::utils::balance::Balance;
+
+/// Distance from the planet surface at which besieging fighters wait.
+pub const SIEGE_GAP: f32 = 2.;
+/// Fighters a single fighter destroys per second, before planet defense is applied.
+const SIEGE_RATE: f32 = 0.5;
+/// Seconds an undefended Outpost takes to fall; better planets take longer.
+const CAPTURE_SECONDS: f32 = 4.;
+
+/// Attackers in orbit and the planet defenders wear each other down, defense in favour of the
+/// planet. Once no defender is left, the only player still in orbit captures the planet over
+/// time, and its surviving fighters land as the new garrison. Several players in orbit fight each
+/// other too and hold the capture back; a new capturer first has to undo the progress of the
+/// previous one.
+pub fn siege_planets(
+    mut commands: Commands,
+    balance: Res<Balance>,
+    mut planets: Query<(Entity, &mut Planet, &mut Siege, &Buildings, &Ownership)>,
+    attackers: Query<(Entity, &NetId, &Sieging, &Destination, &Ownership)>,
+    mut ev_writer: EventWriter<TakeOwnership>,
+    mut destroyed_writer: EventWriter<ShipDestroyed>,
+) {
+    let dt = SIM_DT;
+    let mut orbits: HashMap<Entity, Vec<(Uuid, Vec<Entity>)>> = HashMap::default();
+    let mut landing: HashMap<Entity, f32> = HashMap::default();
+    // in id order, so every peer loses the same ships
+    let mut attackers: Vec<_> = attackers.iter().collect();
+    attackers.sort_by_key(|(_, id, _, _, _)| **id);
+    for (ship, _, sieging, dest, owner) in attackers {
+        let in_orbit = match dest.0 {
+            DestinationEnum::None => true,
+            DestinationEnum::Space(loc) => loc == sieging.post,
+            _ => false,
+        };
+        let (owner, planet_owner) = match (owner.0, planets.get(sieging.planet)) {
+            (Some(owner), Ok((_, _, _, _, planet_owner))) if in_orbit => (owner, planet_owner.0),
+            _ => {
+                // ordered away, or the planet is gone
+                commands.entity(ship).remove::<Sieging>();
+                continue;
+            }
+        };
+        // the planet changed hands, these fighters now defend it
+        if planet_owner == Some(owner) {
+            commands.entity(ship).despawn_recursive();
+            *landing.entry(sieging.planet).or_default() += 1.;
+            continue;
+        }
+        let players = orbits.entry(sieging.planet).or_default();
+        match players.iter_mut().find(|(p, _)| *p == owner) {
+            Some((_, ships)) => ships.push(ship),
+            None => players.push((owner, vec![ship])),
+        }
+    }
+
+    // planets are only written to when something happens, so `Changed<Planet>` stays meaningful
+    for (entity, mut planet, mut siege, buildings, owner) in planets.iter_mut() {
+        if let Some(landed) = landing.remove(&entity) {
+            planet.fighters += landed;
+        }
+        let mut players = orbits.remove(&entity).unwrap_or_default();
+        if players.is_empty() && siege.progress <= 0. && siege.losses.is_empty() {
+            continue;
+        }
+        let defense = balance.defense(&planet, buildings);
+        let sizes: Vec<f32> = players
+            .iter()
+            .map(|(_, ships)| ships.len() as f32)
+            .collect();
+        let total: f32 = sizes.iter().sum();
+        let defenders = planet.fighters.max(0.);
+
+        siege.wear += total * SIEGE_RATE / defense * dt;
+        if siege.wear >= 1. {
+            planet.fighters = (defenders - siege.wear.floor()).max(0.);
+        }
+        siege.wear = match planet.fighters > 0. {
+            true => siege.wear.fract(),
+            false => 0.,
+        };
+        for (i, (player, ships)) in players.iter_mut().enumerate() {
+            let own = sizes[i];
+            let from_planet = defenders * SIEGE_RATE * defense * own / total;
+            // every rival spreads its fire over everyone else in orbit
+            let from_rivals: f32 = sizes
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, other)| other * SIEGE_RATE * own / (total - other))
+                .sum();
+            let lost = siege.losses.entry(*player).or_default();
+            *lost += (from_planet + from_rivals) * dt;
+            let dead = (*lost as usize).min(ships.len());
+            for ship in ships.drain(ships.len() - dead..) {
+                commands.entity(ship).despawn_recursive();
+                destroyed_writer.send(ShipDestroyed {
+                    owner: Some(*player),
+                    by: owner.0,
+                    is_trader: false,
+                });
+            }
+            *lost = lost.fract();
+        }
+        players.retain(|(_, ships)| !ships.is_empty());
+        siege
+            .losses
+            .retain(|p, _| players.iter().any(|(other, _)| other == p));
+
+        let step = dt / (CAPTURE_SECONDS * defense);
+        match players.as_slice() {
+            [(player, ships)] if planet.fighters <= 0. => {
+                if siege.capturer.map_or(true, |c| c == *player) || siege.progress <= 0. {
+                    siege.capturer = Some(*player);
+                    siege.progress += step;
+                } else {
+                    siege.progress -= step;
+                }
+                if siege.progress >= 1. && siege.capturer == Some(*player) {
+                    for ship in ships.iter() {
+                        commands.entity(*ship).despawn_recursive();
+                    }
+                    planet.fighters = ships.len() as f32;
+                    ev_writer.send(TakeOwnership {
+                        entity,
+                        owner: *player,
+                    });
+                    *siege = Siege::default();
+                }
+            }
+            // contested, or defenders still fighting
+            [_, ..] => {}
+            [] => siege.progress -= step,
+        }
+        if siege.progress <= 0. {
+            siege.progress = 0.;
+            siege.capturer = None;
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::utils::{HashMap, Uuid};
+
+use crate::game::components::{characteristics::*, players::Ownership};
+use crate::game::resources::player_res::RegisteredPlayers;
+use crate::game::systems::event_log::player_color;
+use crate::game::utils::influence::{InfluenceMap, InfluenceSource};
+use crate::game::utils::layers_util::{get_z, Layers};
+
+/// Extra reach given by the square root of the garrison, so that big garrisons do not swallow
+/// the map.
+const REACH_PER_FIGHTER: f32 = 3.;
+/// Reach changes are rounded to this, so that every new fighter does not redraw the territory.
+const REACH_STEP: f32 = 4.;
+const TERRITORY_ALPHA: f32 = 0.12;
+
+fn influence(
+    planet: &Planet,
+    transform: &Transform,
+    owner: Option<Uuid>,
+) -> Option<InfluenceSource> {
+    let reach = planet_type_to_influence(&planet.planet_type)
+        + planet.fighters.max(0.).sqrt() * REACH_PER_FIGHTER;
+    Some(InfluenceSource {
+        centre: transform.translation.truncate(),
+        owner: owner?,
+        reach: (reach / REACH_STEP).floor() * REACH_STEP,
+    })
+}
+
+/// Keeps the influence map up to date with the planets whose type or garrison changed, and with
+/// the ones changing hands.
+pub fn update_territory(
+    mut map: ResMut<InfluenceMap>,
+    mut ev_reader: EventReader<TakeOwnership>,
+    changed: Query<(Entity, &Planet, &Transform, &Ownership), Changed<Planet>>,
+    planets: Query<(&Planet, &Transform)>,
+) {
+    for (entity, planet, transform, owner) in changed.iter() {
+        map.set_source(entity, influence(planet, transform, owner.0));
+    }
+    // read from the event, the ownership itself may only change later this frame
+    for ev in ev_reader.iter() {
+        if let Ok((planet, transform)) = planets.get(ev.entity) {
+            map.set_source(ev.entity, influence(planet, transform, Some(ev.owner)));
+        }
+    }
+}
+
+/// Covers the territory of every player with a translucent mesh in its colour.
+pub fn draw_territory(
+    mut commands: Commands,
+    map: Res<InfluenceMap>,
+    players: Res<RegisteredPlayers>,
+    mut drawn: Local<Option<u32>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut query: Query<(&TerritoryMesh, &mut Handle<Mesh>)>,
+) {
+    if *drawn == Some(map.generation) {
+        return;
+    }
+    *drawn = Some(map.generation);
+
+    let mut cells: HashMap<Uuid, Vec<Vec2>> = HashMap::default();
+    for (corner, owner) in map.cells() {
+        cells.entry(owner).or_default().push(corner);
+    }
+    for (territory, mut mesh) in query.iter_mut() {
+        let corners = cells.remove(&territory.player).unwrap_or_default();
+        *mesh = meshes.add(territory_mesh(&corners, map.cell_size()));
+    }
+    // players without a mesh yet
+    for (player, corners) in cells {
+        let color = player_color(&players, Some(player));
+        commands
+            .spawn_bundle(MaterialMeshBundle {
+                mesh: meshes.add(territory_mesh(&corners, map.cell_size())),
+                material: materials.add(StandardMaterial {
+                    base_color: Color::rgba(color.r(), color.g(), color.b(), TERRITORY_ALPHA),
+                    alpha_mode: AlphaMode::Blend,
+                    unlit: true,
+                    ..default()
+                }),
+                transform: Transform::from_xyz(0., 0., get_z(Layers::Territory)),
+                ..default()
+            })
+            .insert(TerritoryMesh { player });
+    }
+}
+
+/// One square per cell, given by its lower left corner.
+fn territory_mesh(corners: &[Vec2], size: f32) -> Mesh {
+    let mut positions: Vec<[f32; 3]> = Vec::with_capacity(corners.len() * 4);
+    let mut indices: Vec<u32> = Vec::with_capacity(corners.len() * 6);
+    for corner in corners.iter() {
+        let i = positions.len() as u32;
+        for (x, y) in [(0., 0.), (size, 0.), (size, size), (0., size)] {
+            positions.push([corner.x + x, corner.y + y, 0.]);
+        }
+        // counter-clockwise seen from the camera
+        indices.extend([i, i + 1, i + 2, i, i + 2, i + 3]);
+    }
+    let normals = vec![[0.0, 0.0, 1.0]; positions.len()];
+    let uvs = vec![[0.0, 0.0]; positions.len()];
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh
+}
@@ -0,0 +1,109 @@
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet, Uuid};
+
+use crate::game::components::{characteristics::*, players::Ownership};
+use crate::game::resources::{
+    game_obj_res::{GalaxyFeatures, SpatialIndex},
+    player_res::{LocalPlayer, PlanetIntel, PlayerVision, RegisteredPlayers},
+};
+use crate::game::utils::balance::Balance;
+use crate::game::utils::features::{self, FeatureKind, NEBULA_SIGHT};
+
+/// Works out what every player sees from the sensors of its ships and planets, and records the
+/// planets in sight as last seen. Runs on every peer: clients of a dedicated server only hold
+/// what the server let them see, and get the same vision out of it. Nebulae hide what is inside
+/// them and blind what looks out of them, beyond a short distance.
+pub fn update_vision(
+    balance: Res<Balance>,
+    features: Res<GalaxyFeatures>,
+    index: Res<SpatialIndex>,
+    players: Res<RegisteredPlayers>,
+    mut vision: ResMut<PlayerVision>,
+    query: Query<(
+        Entity,
+        &Transform,
+        &Ownership,
+        Option<&Planet>,
+        Option<&Buildings>,
+        Option<&SensorRange>,
+    )>,
+) {
+    let mut sensors: HashMap<Uuid, Vec<(Vec2, f32)>> = HashMap::default();
+    let mut owned: HashMap<Uuid, Vec<Entity>> = HashMap::default();
+    for (entity, transform, owner, planet, buildings, sensor) in query.iter() {
+        let player = match owner.0 {
+            Some(player) => player,
+            None => continue,
+        };
+        let range = match (planet, buildings, sensor) {
+            (Some(planet), Some(buildings), _) => balance.sensor(planet, buildings),
+            (_, _, Some(sensor)) => sensor.0,
+            _ => 0.,
+        };
+        let pos = transform.translation.truncate();
+        let range = match features::inside(&features.0, FeatureKind::Nebula, pos) {
+            true => range.min(NEBULA_SIGHT),
+            false => range,
+        };
+        sensors.entry(player).or_default().push((pos, range));
+        owned.entry(player).or_default().push(entity);
+    }
+
+    for player in players.0.keys() {
+        let mut visible: HashSet<Entity> = owned
+            .remove(player)
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        for (centre, range) in sensors.remove(player).unwrap_or_default() {
+            // planets are indexed by their centre, but seen as soon as their edge is
+            let reach = Vec2::splat(range + planet_type_to_radius(&PlanetType::Capital));
+            for e in index.selectables.in_rect(centre - reach, centre + reach) {
+                if let Ok((_, transform, _, planet, _, _)) = query.get(e) {
+                    let size = planet.map_or(0., |p| planet_type_to_radius(&p.planet_type));
+                    let pos = transform.translation.truncate();
+                    let range = match features::inside(&features.0, FeatureKind::Nebula, pos) {
+                        true => range.min(NEBULA_SIGHT),
+                        false => range,
+                    };
+                    if pos.distance(centre) <= range + size {
+                        visible.insert(e);
+                    }
+                }
+            }
+        }
+        let player_vision = vision.0.entry(*player).or_default();
+        for e in visible.iter() {
+            if let Ok((_, _, owner, Some(planet), _, _)) = query.get(*e) {
+                player_vision.planets.insert(
+                    *e,
+                    PlanetIntel {
+                        owner: owner.0,
+                        fighters: planet.fighters,
+                    },
+                );
+            }
+        }
+        player_vision.planets.retain(|e, _| query.contains(*e));
+        player_vision.visible = visible;
+    }
+}
+
+/// Hides enemy ships out of sight of the local player. Planets out of sight are dimmed by
+/// `draw_planets`.
+pub fn draw_fog(
+    local: Option<Res<LocalPlayer>>,
+    vision: Res<PlayerVision>,
+    mut ships: Query<(Entity, &mut Visibility), With<Ship>>,
+) {
+    let local = match local {
+        Some(local) => local.0,
+        None => return,
+    };
+    for (entity, mut visibility) in ships.iter_mut() {
+        let seen = vision.sees(local, entity);
+        if visibility.is_visible != seen {
+            visibility.is_visible = seen;
+        }
+    }
+}
@@ -0,0 +1,482 @@
+use crate::game::components::characteristics::{
+    planet_type_to_defense, planet_type_to_production, planet_type_to_sensor, Buildings, Planet,
+    PlanetType, ShipType,
+};
+use crate::game::resources::player_res::Research;
+use crate::game::utils::stats::{self, Modifier, Stat};
+
+/// Balance file built into the game, so that every peer plays by the same numbers.
+const BALANCE_FILE: &str = include_str!("../../../assets/balance.txt");
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlanetBalance {
+    /// Money to upgrade to the next planet type.
+    pub upgrade_cost: u32,
+    /// Buildings the planet can hold.
+    pub slots: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BuildingSpec {
+    pub name: String,
+    pub cost: u32,
+    /// Extra fighters per production tick.
+    pub production: f32,
+    /// Added to the planet defense.
+    pub defense: f32,
+    /// Money per production tick.
+    pub income: u32,
+    /// Extra distance the planet sees.
+    pub sight: f32,
+}
+
+impl BuildingSpec {
+    /// Short description of what the building does, such as "+1 fighters, +5 money".
+    pub fn effects(&self) -> String {
+        let mut effects = Vec::new();
+        if self.production != 0. {
+            effects.push(format!("+{} fighters", self.production));
+        }
+        if self.defense != 0. {
+            effects.push(format!("+{} defense", self.defense));
+        }
+        if self.income != 0 {
+            effects.push(format!("+{} money", self.income));
+        }
+        if self.sight != 0. {
+            effects.push(format!("+{} sight", self.sight));
+        }
+        effects.join(", ")
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TechSpec {
+    pub name: String,
+    pub cost: u32,
+    pub seconds: f32,
+    /// Technology to research first.
+    pub requires: Option<u8>,
+    pub modifier: Option<Modifier>,
+    /// Ship type players can only buy once they have this technology.
+    pub unlock: Option<ShipType>,
+}
+
+impl TechSpec {
+    /// Short description of what the technology does, such as "+15% fighter speed".
+    pub fn effects(&self) -> String {
+        let mut effects = Vec::new();
+        if let Some(modifier) = self.modifier {
+            effects.push(format!("{:+}% {}", modifier.percent, modifier.stat.name()));
+        }
+        if let Some(ship_type) = self.unlock {
+            effects.push(format!("unlocks {}s", ship_type_name(ship_type)));
+        }
+        effects.join(", ")
+    }
+}
+
+/// Costs and effects of planet upgrades, buildings, ships and research, read from
+/// `assets/balance.txt`.
+pub struct Balance {
+    pub planets: Vec<(PlanetType, PlanetBalance)>,
+    /// Indexed by the building ids stored in `Buildings`.
+    pub buildings: Vec<BuildingSpec>,
+    /// Ships players can buy, with their base cost.
+    pub ships: Vec<(ShipType, u32)>,
+    /// Indexed by the technology ids stored in `Research`.
+    pub techs: Vec<TechSpec>,
+}
+
+impl Default for Balance {
+    fn default() -> Self {
+        Balance::parse(BALANCE_FILE).unwrap_or_else(|e| panic!("assets/balance.txt: {}", e))
+    }
+}
+
+enum Section {
+    Planet(PlanetType),
+    Building(usize),
+    Ship(usize),
+    Tech(usize),
+}
+
+impl Balance {
+    /// Reads `[planet <type>]`, `[building <key>]`, `[ship <type>]` and `[tech <key>]` sections of
+    /// `key = value` lines. `#` starts a comment.
+    pub fn parse(text: &str) -> Result<Balance, String> {
+        let mut balance = Balance {
+            planets: Vec::new(),
+            buildings: Vec::new(),
+            ships: Vec::new(),
+            techs: Vec::new(),
+        };
+        let mut section = None;
+        let mut tech_keys: Vec<&str> = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let err = |msg: &str| format!("line {}: {}", n + 1, msg);
+
+            if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = Some(match header.split_once(' ') {
+                    Some(("planet", name)) => {
+                        let planet_type =
+                            parse_planet_type(name.trim()).ok_or_else(|| err("unknown planet"))?;
+                        balance
+                            .planets
+                            .push((planet_type, PlanetBalance::default()));
+                        Section::Planet(planet_type)
+                    }
+                    Some(("building", key)) => {
+                        balance.buildings.push(BuildingSpec {
+                            name: key.trim().to_string(),
+                            ..Default::default()
+                        });
+                        Section::Building(balance.buildings.len() - 1)
+                    }
+                    Some(("ship", name)) => {
+                        let ship_type = match name.trim() {
+                            "trader" => ShipType::Trade,
+                            "dreadnought" => ShipType::Dreadnought,
+                            _ => return Err(err("unknown ship")),
+                        };
+                        balance.ships.push((ship_type, 0));
+                        Section::Ship(balance.ships.len() - 1)
+                    }
+                    Some(("tech", key)) => {
+                        tech_keys.push(key.trim());
+                        balance.techs.push(TechSpec {
+                            name: key.trim().to_string(),
+                            ..Default::default()
+                        });
+                        Section::Tech(balance.techs.len() - 1)
+                    }
+                    _ => return Err(err("unknown section")),
+                });
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .map(|(k, v)| (k.trim(), v.trim()))
+                .ok_or_else(|| err("expected `key = value`"))?;
+            let number = || value.parse::<f32>().map_err(|_| err("expected a number"));
+            match section {
+                Some(Section::Planet(planet_type)) => {
+                    let planet = &mut balance.planets.last_mut().unwrap().1;
+                    match key {
+                        "upgrade_cost" => planet.upgrade_cost = number()? as u32,
+                        "slots" => planet.slots = number()? as usize,
+                        _ => return Err(err(&format!("unknown {:?} value", planet_type))),
+                    }
+                }
+                Some(Section::Building(i)) => {
+                    let building = &mut balance.buildings[i];
+                    match key {
+                        "name" => building.name = value.to_string(),
+                        "cost" => building.cost = number()? as u32,
+                        "production" => building.production = number()?,
+                        "defense" => building.defense = number()?,
+                        "income" => building.income = number()? as u32,
+                        "sight" => building.sight = number()?,
+                        _ => return Err(err("unknown building value")),
+                    }
+                }
+                Some(Section::Ship(i)) => match key {
+                    "cost" => balance.ships[i].1 = number()? as u32,
+                    _ => return Err(err("unknown ship value")),
+                },
+                Some(Section::Tech(i)) => {
+                    let tech = &mut balance.techs[i];
+                    match key {
+                        "name" => tech.name = value.to_string(),
+                        "cost" => tech.cost = number()? as u32,
+                        "seconds" => tech.seconds = number()?,
+                        // technologies must be listed after the ones they require
+                        "requires" => {
+                            let required = tech_keys[..i].iter().position(|k| *k == value);
+                            tech.requires =
+                                Some(required.ok_or_else(|| err("unknown technology"))? as u8);
+                        }
+                        "stat" => {
+                            let stat = match value {
+                                "fighter_speed" => Stat::FighterSpeed,
+                                "weapon_range" => Stat::WeaponRange,
+                                "trader_cost" => Stat::TraderCost,
+                                "production_interval" => Stat::ProductionInterval,
+                                _ => return Err(err("unknown stat")),
+                            };
+                            let percent = tech.modifier.map_or(0., |m| m.percent);
+                            tech.modifier = Some(Modifier { stat, percent });
+                        }
+                        "percent" => match tech.modifier.as_mut() {
+                            Some(modifier) => modifier.percent = number()?,
+                            None => return Err(err("`percent` must follow `stat`")),
+                        },
+                        "unlock" => match value {
+                            "dreadnought" => tech.unlock = Some(ShipType::Dreadnought),
+                            _ => return Err(err("only dreadnoughts can be unlocked")),
+                        },
+                        _ => return Err(err("unknown technology value")),
+                    }
+                }
+                None => return Err(err("value outside of a section")),
+            }
+        }
+        if balance.buildings.len() > u8::MAX as usize || balance.techs.len() > u8::MAX as usize {
+            return Err("too many buildings or technologies".to_string());
+        }
+        Ok(balance)
+    }
+
+    pub fn planet(&self, planet_type: PlanetType) -> PlanetBalance {
+        self.planets
+            .iter()
+            .find(|(t, _)| *t == planet_type)
+            .map(|(_, b)| b.clone())
+            .unwrap_or_default()
+    }
+
+    fn built<'a>(&'a self, buildings: &'a Buildings) -> impl Iterator<Item = &'a BuildingSpec> {
+        buildings
+            .0
+            .iter()
+            .filter_map(|b| self.buildings.get(*b as usize))
+    }
+
+    /// Fighters the planet adds to its garrison every production tick.
+    pub fn production(&self, planet: &Planet, buildings: &Buildings) -> f32 {
+        planet_type_to_production(&planet.planet_type)
+            + self.built(buildings).map(|b| b.production).sum::<f32>()
+    }
+
+    pub fn defense(&self, planet: &Planet, buildings: &Buildings) -> f32 {
+        planet_type_to_defense(&planet.planet_type)
+            + self.built(buildings).map(|b| b.defense).sum::<f32>()
+    }
+
+    /// Distance from its centre up to which the planet sees.
+    pub fn sensor(&self, planet: &Planet, buildings: &Buildings) -> f32 {
+        planet_type_to_sensor(&planet.planet_type)
+            + self.built(buildings).map(|b| b.sight).sum::<f32>()
+    }
+
+    /// Money the planet earns its owner every production tick.
+    pub fn income(&self, buildings: &Buildings) -> u32 {
+        self.built(buildings).map(|b| b.income).sum()
+    }
+
+    fn researched<'a>(
+        &'a self,
+        research: Option<&'a Research>,
+    ) -> impl Iterator<Item = &'a TechSpec> {
+        research
+            .into_iter()
+            .flat_map(|r| r.done.iter())
+            .filter_map(|t| self.techs.get(*t as usize))
+    }
+
+    /// Effective value of `stat` for a player with `research`.
+    pub fn stat(&self, research: Option<&Research>, stat: Stat, base: f32) -> f32 {
+        stats::resolve(
+            stat,
+            base,
+            self.researched(research)
+                .filter_map(|t| t.modifier.as_ref()),
+        )
+    }
+
+    /// Whether a player with `research` may buy `ship_type`. Ships no technology unlocks always
+    /// are.
+    pub fn unlocked(&self, research: Option<&Research>, ship_type: ShipType) -> bool {
+        !self.techs.iter().any(|t| t.unlock == Some(ship_type))
+            || self
+                .researched(research)
+                .any(|t| t.unlock == Some(ship_type))
+    }
+
+    /// What a player with `research` pays for `ship_type`, if it can be bought at all.
+    pub fn ship_cost(&self, research: Option<&Research>, ship_type: ShipType) -> Option<u32> {
+        let base = self.ships.iter().find(|(t, _)| *t == ship_type)?.1;
+        Some(match ship_type {
+            ShipType::Trade => self.stat(research, Stat::TraderCost, base as f32).round() as u32,
+            _ => base,
+        })
+    }
+
+    /// Whether a player with `research` can start researching `tech` now.
+    pub fn can_research(&self, research: Option<&Research>, tech: u8) -> bool {
+        let spec = match self.techs.get(tech as usize) {
+            Some(spec) => spec,
+            None => return false,
+        };
+        let done = |t: u8| research.map_or(false, |r| r.done.contains(&t));
+        research.map_or(true, |r| r.current.is_none())
+            && !done(tech)
+            && spec.requires.map_or(true, done)
+    }
+}
+
+pub fn ship_type_name(ship_type: ShipType) -> &'static str {
+    match ship_type {
+        ShipType::Fighter => "fighter",
+        ShipType::Trade => "trader",
+        ShipType::Dreadnought => "dreadnought",
+    }
+}
+
+fn parse_planet_type(name: &str) -> Option<PlanetType> {
+    match name {
+        "Outpost" => Some(PlanetType::Outpost),
+        "Watch" => Some(PlanetType::Watch),
+        "Base" => Some(PlanetType::Base),
+        "Colony" => Some(PlanetType::Colony),
+        "Capital" => Some(PlanetType::Capital),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "
+# comment line
+[planet Outpost]
+upgrade_cost = 100   # trailing comment
+slots = 1
+
+[building shipyard]
+name = Shipyard
+cost = 250
+production = 1.5
+
+[ship dreadnought]
+cost = 600
+
+[tech engines]
+name = Ion engines
+cost = 150
+seconds = 30
+stat = fighter_speed
+percent = 15
+
+[tech capital_ships]
+requires = engines
+unlock = dreadnought
+";
+
+    fn error(text: &str) -> Option<String> {
+        Balance::parse(text).err()
+    }
+
+    #[test]
+    fn the_built_in_file_parses() {
+        let balance = Balance::default();
+        assert_eq!(balance.planets.len(), 5);
+        assert!(!balance.buildings.is_empty());
+        assert!(balance.ship_cost(None, ShipType::Trade).is_some());
+        // technologies only require ones listed before them
+        for (i, tech) in balance.techs.iter().enumerate() {
+            assert!(
+                tech.requires.map_or(true, |r| (r as usize) < i),
+                "{}",
+                tech.name
+            );
+        }
+    }
+
+    #[test]
+    fn sections_fill_their_entries() {
+        let balance = Balance::parse(SAMPLE).unwrap();
+        assert_eq!(
+            balance.planet(PlanetType::Outpost),
+            PlanetBalance {
+                upgrade_cost: 100,
+                slots: 1,
+            }
+        );
+        // missing planets fall back to nothing
+        assert_eq!(
+            balance.planet(PlanetType::Capital),
+            PlanetBalance::default()
+        );
+        assert_eq!(
+            balance.buildings,
+            [BuildingSpec {
+                name: "Shipyard".to_string(),
+                cost: 250,
+                production: 1.5,
+                ..Default::default()
+            }]
+        );
+        assert_eq!(balance.ships, [(ShipType::Dreadnought, 600)]);
+        assert_eq!(
+            balance.techs[0],
+            TechSpec {
+                name: "Ion engines".to_string(),
+                cost: 150,
+                seconds: 30.,
+                requires: None,
+                modifier: Some(Modifier {
+                    stat: Stat::FighterSpeed,
+                    percent: 15.,
+                }),
+                unlock: None,
+            }
+        );
+        // the key names technologies without a `name`
+        assert_eq!(balance.techs[1].name, "capital_ships");
+        assert_eq!(balance.techs[1].requires, Some(0));
+        assert_eq!(balance.techs[1].unlock, Some(ShipType::Dreadnought));
+    }
+
+    #[test]
+    fn unlocked_ships_need_their_research() {
+        let balance = Balance::parse(SAMPLE).unwrap();
+        let mut research = Research::default();
+        assert!(!balance.unlocked(Some(&research), ShipType::Dreadnought));
+        assert!(balance.unlocked(Some(&research), ShipType::Trade));
+        assert!(!balance.can_research(Some(&research), 1));
+        research.done.push(0);
+        assert!(balance.can_research(Some(&research), 1));
+        research.done.push(1);
+        assert!(balance.unlocked(Some(&research), ShipType::Dreadnought));
+    }
+
+    #[test]
+    fn mistakes_name_their_line() {
+        assert_eq!(
+            error("cost = 5").as_deref(),
+            Some("line 1: value outside of a section")
+        );
+        assert_eq!(
+            error("[planet Moon]").as_deref(),
+            Some("line 1: unknown planet")
+        );
+        assert_eq!(
+            error("[ship fighter]").as_deref(),
+            Some("line 1: unknown ship")
+        );
+        assert_eq!(
+            error("[building a]\ncost = lots").as_deref(),
+            Some("line 2: expected a number")
+        );
+        assert_eq!(
+            error("[tech a]\npercent = 5").as_deref(),
+            Some("line 2: `percent` must follow `stat`")
+        );
+        // required technologies must come first
+        assert_eq!(
+            error("[tech a]\nrequires = b\n[tech b]").as_deref(),
+            Some("line 2: unknown technology")
+        );
+        assert_eq!(
+            error("[tech a]\nunlock = trader").as_deref(),
+            Some("line 2: only dreadnoughts can be unlocked")
+        );
+    }
+}
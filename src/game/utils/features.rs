@@ -0,0 +1,181 @@
+use bevy::prelude::{Component, Vec2};
+use rand::{rngs::StdRng, Rng};
+
+/// Nebulae slow ships down and hide what is inside them.
+pub const NEBULA_SPEED: f32 = 0.5;
+/// Distance up to which ships see into, out of or across a nebula.
+pub const NEBULA_SIGHT: f32 = 10.;
+/// Hull lost per unit flown through an asteroid field.
+pub const ASTEROID_DAMAGE: f32 = 0.02;
+/// Pull at the edge of the black hole core, fading to nothing at its radius.
+pub const BLACK_HOLE_PULL: f32 = 20.;
+/// Share of the black hole radius taken by the core, where ships are lost.
+pub const BLACK_HOLE_CORE: f32 = 0.2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeatureKind {
+    Nebula,
+    AsteroidField,
+    BlackHole,
+}
+
+/// Circular area of the galaxy affecting the ships inside it.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct Feature {
+    pub kind: FeatureKind,
+    pub centre: Vec2,
+    pub radius: f32,
+}
+
+impl Feature {
+    pub fn contains(&self, pos: Vec2) -> bool {
+        pos.distance(self.centre) < self.radius
+    }
+
+    /// Radius of the part ships must steer around: asteroid fields and black hole cores.
+    pub fn obstacle_radius(&self) -> Option<f32> {
+        match self.kind {
+            FeatureKind::Nebula => None,
+            FeatureKind::AsteroidField => Some(self.radius),
+            FeatureKind::BlackHole => Some(self.radius * BLACK_HOLE_CORE),
+        }
+    }
+}
+
+pub fn inside(features: &[Feature], kind: FeatureKind, pos: Vec2) -> bool {
+    features.iter().any(|f| f.kind == kind && f.contains(pos))
+}
+
+/// Acceleration of black holes at `pos`, stronger closer to their core.
+pub fn pull(features: &[Feature], pos: Vec2) -> Vec2 {
+    features
+        .iter()
+        .filter(|f| f.kind == FeatureKind::BlackHole && f.contains(pos))
+        .fold(Vec2::ZERO, |total, f| {
+            let towards = f.centre - pos;
+            let fall = 1. - towards.length() / f.radius;
+            total + towards.normalize_or_zero() * BLACK_HOLE_PULL * fall
+        })
+}
+
+/// Features of a galaxy of `radius`, drawn from the match seed. Their number grows with the
+/// galaxy area.
+pub fn generate(rng: &mut StdRng, radius: f32) -> Vec<Feature> {
+    let area = (radius / 300.).powi(2).min(100.);
+    let mut features = Vec::new();
+    for (kind, per_area, min_radius, max_radius) in [
+        (FeatureKind::Nebula, 3., 30., 70.),
+        (FeatureKind::AsteroidField, 3., 20., 45.),
+        (FeatureKind::BlackHole, 1., 40., 60.),
+    ] {
+        for _ in 0..(per_area * area).round() as u32 {
+            let dist = rng.gen::<f32>().sqrt() * radius;
+            let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+            features.push(Feature {
+                kind,
+                centre: Vec2::new(angle.cos(), angle.sin()) * dist,
+                radius: rng.gen_range(min_radius..max_radius),
+            });
+        }
+    }
+    features
+}
+
+/// Reads a map file, one feature per line: `nebula`, `asteroids` or `black_hole` followed by
+/// the x and y of its centre and its radius. `#` starts a comment.
+pub fn parse(text: &str) -> Result<Vec<Feature>, String> {
+    let mut features = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let err = |msg: &str| format!("line {}: {}", n + 1, msg);
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let kind = match words[0] {
+            "nebula" => FeatureKind::Nebula,
+            "asteroids" => FeatureKind::AsteroidField,
+            "black_hole" => FeatureKind::BlackHole,
+            _ => return Err(err("unknown feature")),
+        };
+        let numbers = words[1..]
+            .iter()
+            .map(|w| w.parse::<f32>())
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(|_| err("expected a number"))?;
+        match numbers.as_slice() {
+            [x, y, radius] if *radius > 0. => features.push(Feature {
+                kind,
+                centre: Vec2::new(*x, *y),
+                radius: *radius,
+            }),
+            _ => return Err(err("expected `<feature> <x> <y> <radius>`")),
+        }
+    }
+    Ok(features)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_files_list_one_feature_per_line() {
+        let text = "
+# the centre of the map
+nebula 0 0 50
+asteroids -120.5 40 25   # a belt
+black_hole 300 -300 45
+";
+        assert_eq!(
+            parse(text),
+            Ok(vec![
+                Feature {
+                    kind: FeatureKind::Nebula,
+                    centre: Vec2::new(0., 0.),
+                    radius: 50.,
+                },
+                Feature {
+                    kind: FeatureKind::AsteroidField,
+                    centre: Vec2::new(-120.5, 40.),
+                    radius: 25.,
+                },
+                Feature {
+                    kind: FeatureKind::BlackHole,
+                    centre: Vec2::new(300., -300.),
+                    radius: 45.,
+                },
+            ])
+        );
+        assert_eq!(parse("# nothing here\n\n"), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn mistakes_name_their_line() {
+        let shape = "expected `<feature> <x> <y> <radius>`";
+        assert_eq!(
+            parse("nebula 0 0 10\nwormhole 0 0 10"),
+            Err("line 2: unknown feature".to_string())
+        );
+        assert_eq!(
+            parse("nebula 0 zero 10"),
+            Err("line 1: expected a number".to_string())
+        );
+        assert_eq!(parse("nebula 0 0"), Err(format!("line 1: {}", shape)));
+        assert_eq!(parse("nebula 0 0 10 5"), Err(format!("line 1: {}", shape)));
+        assert_eq!(parse("nebula 0 0 -10"), Err(format!("line 1: {}", shape)));
+    }
+
+    #[test]
+    fn black_holes_pull_towards_their_core() {
+        let features = parse("black_hole 0 0 50\nnebula 100 0 20").unwrap();
+        let pull_at = |x: f32| pull(&features, Vec2::new(x, 0.));
+        assert!(pull_at(20.).x < 0.);
+        assert!(pull_at(-20.).x > 0.);
+        // stronger closer in, nothing outside
+        assert!(pull_at(10.).length() > pull_at(40.).length());
+        assert_eq!(pull_at(100.), Vec2::ZERO);
+        assert!(inside(&features, FeatureKind::Nebula, Vec2::new(110., 0.)));
+        assert!(!inside(&features, FeatureKind::Nebula, Vec2::new(20., 0.)));
+    }
+}
@@ -0,0 +1,69 @@
+use bevy::prelude::Vec2;
+
+/// What a ship knows about itself or a neighbour when steering.
+#[derive(Clone, Copy, Debug)]
+pub struct Boid {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    /// Ships only align and gather with their own group, usually their owner.
+    pub group: u128,
+}
+
+/// Steering weights, set per ship type.
+#[derive(Clone, Copy, Debug)]
+pub struct FlockWeights {
+    /// Neighbours further than this are ignored.
+    pub radius: f32,
+    pub separation: f32,
+    pub alignment: f32,
+    pub cohesion: f32,
+}
+
+/// Longest steering vector a ship can get, relative to its heading.
+pub const MAX_STEERING: f32 = 2.;
+
+/// Separation, alignment and cohesion steering for `me`.
+/// `neighbours` may include `me` and ships out of range, they are filtered here. Float sums
+/// depend on their order, so peers must pass them in the same one: the spatial index gives
+/// them by `NetId`.
+pub fn steer<'a>(
+    me: &Boid,
+    neighbours: impl Iterator<Item = &'a Boid>,
+    weights: &FlockWeights,
+) -> Vec2 {
+    let mut separation = Vec2::ZERO;
+    let mut heading = Vec2::ZERO;
+    let mut centre = Vec2::ZERO;
+    let mut flockmates = 0;
+    for other in neighbours {
+        let offset = me.pos - other.pos;
+        let dist = offset.length();
+        if dist >= weights.radius || dist <= f32::EPSILON {
+            continue;
+        }
+        // closer ships push harder
+        separation += offset / dist * (1. - dist / weights.radius);
+        if other.group == me.group {
+            heading += other.vel;
+            centre += other.pos;
+            flockmates += 1;
+        }
+    }
+    let mut steering = separation * weights.separation;
+    if flockmates > 0 {
+        let alignment = (heading / flockmates as f32 - me.vel).normalize_or_zero();
+        let cohesion = (centre / flockmates as f32 - me.pos).normalize_or_zero();
+        steering += alignment * weights.alignment + cohesion * weights.cohesion;
+    }
+    steering.clamp_length_max(MAX_STEERING)
+}
+
+/// Push away from a planet the ship is not heading to, once within `margin` of its surface.
+pub fn avoid_circle(pos: Vec2, centre: Vec2, radius: f32, margin: f32) -> Vec2 {
+    let offset = pos - centre;
+    let gap = offset.length() - radius;
+    if gap >= margin {
+        return Vec2::ZERO;
+    }
+    offset.normalize_or_zero() * (1. - gap.max(0.) / margin)
+}
@@ -0,0 +1,182 @@
+use std::cmp::Ordering;
+use std::f32::consts::PI;
+
+use bevy::prelude::Vec2;
+
+/// Shape a fleet takes around its target. Offsets are laid out with `y` pointing along the
+/// direction of travel and `x` to its right.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Formation {
+    /// Every ship aims at the target itself.
+    Cluster,
+    Line,
+    Wedge,
+    Circle,
+    Box,
+}
+
+impl Formation {
+    pub fn next(self) -> Formation {
+        match self {
+            Formation::Cluster => Formation::Line,
+            Formation::Line => Formation::Wedge,
+            Formation::Wedge => Formation::Circle,
+            Formation::Circle => Formation::Box,
+            Formation::Box => Formation::Cluster,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Formation::Cluster => "cluster",
+            Formation::Line => "line",
+            Formation::Wedge => "wedge",
+            Formation::Circle => "circle",
+            Formation::Box => "box",
+        }
+    }
+}
+
+/// Slot offsets for `count` ships, `spacing` apart, centred on the target.
+pub fn slots(formation: Formation, count: usize, spacing: f32) -> Vec<Vec2> {
+    let centred = |i: usize, n: usize| (i as f32 - (n as f32 - 1.) / 2.) * spacing;
+    match formation {
+        Formation::Cluster => vec![Vec2::ZERO; count],
+        Formation::Line => (0..count)
+            .map(|i| Vec2::new(centred(i, count), 0.))
+            .collect(),
+        Formation::Wedge => (0..count)
+            .map(|i| {
+                // the leader at the tip, then one ship on each side per row
+                let row = ((i + 1) / 2) as f32;
+                let side = if i % 2 == 1 { -1. } else { 1. };
+                Vec2::new(side * row * spacing, -row * spacing)
+            })
+            .collect(),
+        Formation::Circle => {
+            if count <= 1 {
+                return vec![Vec2::ZERO; count];
+            }
+            let radius = spacing.max(spacing * count as f32 / (2. * PI));
+            (0..count)
+                .map(|i| {
+                    let angle = i as f32 * 2. * PI / count as f32;
+                    Vec2::new(angle.sin(), angle.cos()) * radius
+                })
+                .collect()
+        }
+        Formation::Box => {
+            let cols = (count as f32).sqrt().ceil().max(1.) as usize;
+            let rows = (count + cols - 1) / cols;
+            (0..count)
+                .map(|i| Vec2::new(centred(i % cols, cols), -centred(i / cols, rows)))
+                .collect()
+        }
+    }
+}
+
+/// Front to back, then left to right.
+fn reading_order(a: &Vec2, b: &Vec2) -> Ordering {
+    b.y.partial_cmp(&a.y)
+        .unwrap_or(Ordering::Equal)
+        .then(a.x.partial_cmp(&b.x).unwrap_or(Ordering::Equal))
+}
+
+/// Gives each ship, by its position relative to the fleet, the slot in the same rank,
+/// so ships do not cross each other to reach their slot. Returns a slot per position.
+pub fn assign_slots(positions: &[Vec2], slots: &[Vec2]) -> Vec<Vec2> {
+    let mut by_position: Vec<usize> = (0..positions.len()).collect();
+    by_position.sort_by(|a, b| reading_order(&positions[*a], &positions[*b]));
+    let mut sorted_slots = slots.to_vec();
+    sorted_slots.sort_by(reading_order);
+    let mut assigned = vec![Vec2::ZERO; positions.len()];
+    for (rank, idx) in by_position.into_iter().enumerate() {
+        assigned[idx] = sorted_slots.get(rank).copied().unwrap_or_default();
+    }
+    assigned
+}
+
+/// World offset of a formation offset, for a fleet travelling along `forward`.
+pub fn to_world(offset: Vec2, forward: Vec2) -> Vec2 {
+    let right = Vec2::new(forward.y, -forward.x);
+    right * offset.x + forward * offset.y
+}
+
+/// Formation offset of a world offset, for a fleet travelling along `forward`.
+pub fn to_local(offset: Vec2, forward: Vec2) -> Vec2 {
+    let right = Vec2::new(forward.y, -forward.x);
+    Vec2::new(offset.dot(right), offset.dot(forward))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_formation_has_a_slot_per_ship() {
+        let mut formation = Formation::Cluster;
+        loop {
+            for count in [0, 1, 2, 7, 16] {
+                let slots = slots(formation, count, 4.);
+                assert_eq!(slots.len(), count, "{}", formation.name());
+                // centred on the target, wedges aside, and boxes only when their rows are full:
+                // seven ships leave one alone on the left of the last row
+                let centre = slots.iter().sum::<Vec2>() / count.max(1) as f32;
+                let full_box = formation == Formation::Box && count != 7;
+                if matches!(formation, Formation::Line | Formation::Circle) || full_box {
+                    assert!(centre.length() < 1e-3, "{}", formation.name());
+                }
+            }
+            formation = formation.next();
+            if formation == Formation::Cluster {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn slots_are_spaced_apart() {
+        for formation in [
+            Formation::Line,
+            Formation::Wedge,
+            Formation::Circle,
+            Formation::Box,
+        ] {
+            let slots = slots(formation, 9, 4.);
+            for (i, a) in slots.iter().enumerate() {
+                for b in slots.iter().skip(i + 1) {
+                    // circle slots are an arc length apart, their chord is a little shorter
+                    assert!(a.distance(*b) >= 3.9, "{}", formation.name());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wedges_lead_with_one_ship() {
+        let slots = slots(Formation::Wedge, 5, 4.);
+        assert_eq!(slots[0], Vec2::ZERO);
+        assert!(slots.iter().skip(1).all(|s| s.y < 0.));
+    }
+
+    #[test]
+    fn ships_keep_their_rank() {
+        // front ship on the left, back ship on the right
+        let positions = [Vec2::new(5., -5.), Vec2::new(-5., 5.)];
+        let slots = [Vec2::new(-2., 0.), Vec2::new(2., 0.)];
+        assert_eq!(assign_slots(&positions, &slots), [slots[1], slots[0]]);
+        let line = [Vec2::new(3., 0.), Vec2::new(-3., 0.), Vec2::new(0., 0.)];
+        let slots = [Vec2::new(-4., 0.), Vec2::new(0., 0.), Vec2::new(4., 0.)];
+        assert_eq!(assign_slots(&line, &slots), [slots[2], slots[0], slots[1]]);
+    }
+
+    #[test]
+    fn local_offsets_round_trip() {
+        let forward = Vec2::new(1., 1.).normalize();
+        let offset = Vec2::new(3., -2.);
+        let back = to_local(to_world(offset, forward), forward);
+        assert!(back.distance(offset) < 1e-4);
+        // forward in formation space is the direction of travel
+        assert!(to_world(Vec2::Y, forward).distance(forward) < 1e-4);
+    }
+}
@@ -0,0 +1,180 @@
+use bevy::prelude::{Entity, IVec2, Vec2};
+use bevy::utils::{HashMap, Uuid};
+
+/// Influence projected by one planet. It is worth `reach` at the centre and fades to nothing
+/// at `reach` from it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InfluenceSource {
+    pub centre: Vec2,
+    pub owner: Uuid,
+    pub reach: f32,
+}
+
+/// Grid of the player holding the most influence over each cell. Changing a source only
+/// recomputes the cells it reaches, before and after the change.
+pub struct InfluenceMap {
+    cell_size: f32,
+    sources: HashMap<Entity, InfluenceSource>,
+    owners: HashMap<IVec2, Uuid>,
+    /// Bumped whenever a cell changes owner, so that territory is only redrawn when needed.
+    pub generation: u32,
+}
+
+impl InfluenceMap {
+    pub fn new(cell_size: f32) -> Self {
+        InfluenceMap {
+            cell_size,
+            sources: HashMap::default(),
+            owners: HashMap::default(),
+            generation: 0,
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    fn cell(&self, pos: Vec2) -> IVec2 {
+        (pos / self.cell_size).floor().as_ivec2()
+    }
+
+    fn cell_centre(&self, cell: IVec2) -> Vec2 {
+        (cell.as_vec2() + Vec2::splat(0.5)) * self.cell_size
+    }
+
+    /// Player whose territory `pos` is in, if any.
+    pub fn owner_at(&self, pos: Vec2) -> Option<Uuid> {
+        self.owners.get(&self.cell(pos)).copied()
+    }
+
+    /// Owned cells, by the position of their lower left corner.
+    pub fn cells(&self) -> impl Iterator<Item = (Vec2, Uuid)> + '_ {
+        self.owners
+            .iter()
+            .map(|(cell, owner)| (cell.as_vec2() * self.cell_size, *owner))
+    }
+
+    /// Adds, moves or removes the influence of `entity`.
+    pub fn set_source(&mut self, entity: Entity, source: Option<InfluenceSource>) {
+        let old = self.sources.get(&entity).copied();
+        if old == source {
+            return;
+        }
+        match source {
+            Some(source) => self.sources.insert(entity, source),
+            None => self.sources.remove(&entity),
+        };
+        for s in old.iter().chain(source.iter()) {
+            self.recompute(s.centre, s.reach);
+        }
+    }
+
+    fn recompute(&mut self, centre: Vec2, reach: f32) {
+        let (min, max) = (
+            self.cell(centre - Vec2::splat(reach)),
+            self.cell(centre + Vec2::splat(reach)),
+        );
+        let mut changed = false;
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                let cell = IVec2::new(x, y);
+                let pos = self.cell_centre(cell);
+                let mut reaching: Vec<(Uuid, f32)> = self
+                    .sources
+                    .values()
+                    .map(|s| (s.owner, s.reach - s.centre.distance(pos)))
+                    .filter(|(_, influence)| *influence > 0.)
+                    .collect();
+                // summed in the same order on every peer, whatever order the sources are stored in
+                reaching.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.total_cmp(&b.1)));
+                let mut totals: Vec<(Uuid, f32)> = Vec::new();
+                for (owner, influence) in reaching {
+                    match totals.last_mut() {
+                        Some((p, total)) if *p == owner => *total += influence,
+                        _ => totals.push((owner, influence)),
+                    }
+                }
+                // ties go to the lowest id
+                let owner = totals
+                    .into_iter()
+                    .max_by(|a, b| a.1.total_cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+                    .map(|(p, _)| p);
+                let previous = match owner {
+                    Some(owner) => self.owners.insert(cell, owner),
+                    None => self.owners.remove(&cell),
+                };
+                changed |= previous != owner;
+            }
+        }
+        if changed {
+            self.generation = self.generation.wrapping_add(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(x: f32, y: f32, owner: u128, reach: f32) -> Option<InfluenceSource> {
+        Some(InfluenceSource {
+            centre: Vec2::new(x, y),
+            owner: Uuid::from_u128(owner),
+            reach,
+        })
+    }
+
+    fn owner(map: &InfluenceMap, x: f32, y: f32) -> Option<u128> {
+        map.owner_at(Vec2::new(x, y)).map(|o| o.as_u128())
+    }
+
+    #[test]
+    fn sources_claim_the_cells_they_reach() {
+        let mut map = InfluenceMap::new(10.);
+        map.set_source(Entity::from_raw(1), source(0., 0., 1, 30.));
+        assert_eq!(owner(&map, 0., 0.), Some(1));
+        assert_eq!(owner(&map, 20., 0.), Some(1));
+        assert_eq!(owner(&map, 50., 0.), None);
+    }
+
+    #[test]
+    fn the_strongest_influence_wins() {
+        let mut map = InfluenceMap::new(10.);
+        map.set_source(Entity::from_raw(1), source(0., 0., 1, 30.));
+        map.set_source(Entity::from_raw(2), source(40., 0., 2, 60.));
+        assert_eq!(owner(&map, 0., 0.), Some(2));
+        assert_eq!(owner(&map, -20., 0.), Some(1));
+        // influence of the same player adds up
+        map.set_source(Entity::from_raw(3), source(-5., 0., 1, 30.));
+        assert_eq!(owner(&map, 0., 0.), Some(1));
+    }
+
+    #[test]
+    fn ties_go_to_the_lowest_id() {
+        let mut map = InfluenceMap::new(10.);
+        // both 10 away from the centre of the cell at (5, 5)
+        map.set_source(Entity::from_raw(1), source(5., 15., 9, 20.));
+        map.set_source(Entity::from_raw(2), source(5., -5., 4, 20.));
+        assert_eq!(owner(&map, 5., 5.), Some(4));
+    }
+
+    #[test]
+    fn moved_and_removed_sources_give_up_their_cells() {
+        let mut map = InfluenceMap::new(10.);
+        let planet = Entity::from_raw(1);
+        map.set_source(planet, source(0., 0., 1, 20.));
+        let generation = map.generation;
+        // setting the same source again changes nothing
+        map.set_source(planet, source(0., 0., 1, 20.));
+        assert_eq!(map.generation, generation);
+
+        map.set_source(planet, source(100., 0., 1, 20.));
+        assert_eq!(owner(&map, 0., 0.), None);
+        assert_eq!(owner(&map, 100., 0.), Some(1));
+        assert_ne!(map.generation, generation);
+
+        map.set_source(planet, None);
+        assert_eq!(owner(&map, 100., 0.), None);
+        assert_eq!(map.cells().count(), 0);
+    }
+}
@@ -1,16 +1,24 @@
 use bevy::math::{Vec2, Vec3};
 
 pub enum Layers {
+    Features,
+    Territory,
+    Hyperlanes,
     Ships,
     Planets,
+    OrderLines,
     BoxSelect,
     Text,
 }
 
 pub fn get_z(obj_type: Layers) -> f32 {
     match obj_type {
+        Layers::Features => -2.,
+        Layers::Territory => -1.,
+        Layers::Hyperlanes => -0.5,
         Layers::Ships => 0.,
         Layers::Planets => 0.,
+        Layers::OrderLines => 2.,
         Layers::BoxSelect => 3.,
         Layers::Text => 27.5,
     }
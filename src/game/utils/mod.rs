@@ -1 +1,10 @@
+pub mod balance;
+pub mod features;
+pub mod flocking;
+pub mod formation;
+pub mod influence;
 pub mod layers_util;
+pub mod names;
+pub mod pathfinding;
+pub mod spatial_grid;
+pub mod stats;
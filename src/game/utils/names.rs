@@ -0,0 +1,29 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const FIRST: [&str; 24] = [
+    "Al", "Be", "Cor", "Dra", "Eri", "Fen", "Gal", "Hy", "Ix", "Jor", "Ka", "Lu", "Mor", "Ne",
+    "Or", "Pra", "Qua", "Ry", "Sol", "Tau", "Ul", "Vel", "Xan", "Zy",
+];
+const MIDDLE: [&str; 12] = [
+    "a", "e", "i", "o", "u", "ae", "ar", "en", "ir", "on", "or", "us",
+];
+const LAST: [&str; 14] = [
+    "ba", "des", "gon", "lia", "mir", "nox", "phus", "ris", "tar", "thys", "ven", "x", "drea",
+    "lon",
+];
+const SUFFIX: [&str; 5] = [" Prime", " II", " III", " IV", " Major"];
+
+/// Name of the planet with net id `id` in the match started from `seed`. Ids are the same on
+/// every peer, and so are the names.
+pub fn planet_name(seed: u64, id: u32) -> String {
+    let mut rng = StdRng::seed_from_u64(seed ^ (id as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    let mut name = FIRST[rng.gen_range(0..FIRST.len())].to_string();
+    if rng.gen_bool(0.5) {
+        name.push_str(MIDDLE[rng.gen_range(0..MIDDLE.len())]);
+    }
+    name.push_str(LAST[rng.gen_range(0..LAST.len())]);
+    if rng.gen_bool(0.2) {
+        name.push_str(SUFFIX[rng.gen_range(0..SUFFIX.len())]);
+    }
+    name
+}
@@ -0,0 +1,334 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::f32::consts::PI;
+
+use bevy::prelude::Vec2;
+
+/// Circle ships must fly around, usually a planet.
+#[derive(Clone, Copy, Debug)]
+pub struct Obstacle {
+    pub centre: Vec2,
+    pub radius: f32,
+}
+
+/// Clearance kept between a path and an obstacle.
+pub const PATH_MARGIN: f32 = 2.;
+/// Obstacles further than this from the straight line are not considered.
+pub const CORRIDOR_WIDTH: f32 = 40.;
+/// Above this many obstacles in the corridor the planner gives up and flies straight.
+pub const MAX_OBSTACLES: usize = 48;
+const NODES_PER_OBSTACLE: usize = 8;
+
+impl Obstacle {
+    fn inflated(&self) -> f32 {
+        self.radius + PATH_MARGIN
+    }
+
+    fn contains(&self, pos: Vec2) -> bool {
+        pos.distance(self.centre) < self.inflated()
+    }
+
+    fn bits(&self) -> [u32; 3] {
+        [
+            self.centre.x.to_bits(),
+            self.centre.y.to_bits(),
+            self.radius.to_bits(),
+        ]
+    }
+
+    fn blocks(&self, from: Vec2, to: Vec2) -> bool {
+        distance_to_segment(self.centre, from, to) < self.radius + PATH_MARGIN * 0.5
+    }
+}
+
+pub fn distance_to_segment(point: Vec2, from: Vec2, to: Vec2) -> f32 {
+    let seg = to - from;
+    let len_sq = seg.length_squared();
+    if len_sq <= f32::EPSILON {
+        return point.distance(from);
+    }
+    let t = ((point - from).dot(seg) / len_sq).clamp(0., 1.);
+    point.distance(from + seg * t)
+}
+
+/// Obstacles close enough to the straight line from `from` to `to` to matter.
+/// Obstacles around either end are dropped, so ships can leave or land on a planet.
+pub fn corridor(from: Vec2, to: Vec2, candidates: impl Iterator<Item = Obstacle>) -> Vec<Obstacle> {
+    let mut found: Vec<(f32, Obstacle)> = candidates
+        .filter(|o| !o.contains(from) && !o.contains(to))
+        .map(|o| (distance_to_segment(o.centre, from, to) - o.radius, o))
+        .filter(|(dist, _)| *dist < CORRIDOR_WIDTH)
+        .collect();
+    found.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+    found.truncate(MAX_OBSTACLES);
+    found.into_iter().map(|(_, o)| o).collect()
+}
+
+#[derive(PartialEq)]
+struct Open {
+    estimate: f32,
+    node: usize,
+}
+impl Eq for Open {}
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max heap, the cheapest estimate must come first
+        other
+            .estimate
+            .partial_cmp(&self.estimate)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.node.cmp(&self.node))
+    }
+}
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn visible(obstacles: &[Obstacle], a: Vec2, b: Vec2) -> bool {
+    !obstacles.iter().any(|o| o.blocks(a, b))
+}
+
+/// Visibility graph made of octagons around each obstacle, with the corners each corner sees.
+/// Building it is the expensive part of planning, so a graph is kept for as long as its
+/// obstacles do not change and reused for every trip through them.
+pub struct VisibilityGraph {
+    obstacles: Vec<Obstacle>,
+    corners: Vec<Vec2>,
+    sight: Vec<Vec<usize>>,
+}
+
+impl VisibilityGraph {
+    /// Identifies an obstacle set whatever the order of its obstacles.
+    pub fn key(obstacles: &[Obstacle]) -> Vec<[u32; 3]> {
+        let mut key: Vec<[u32; 3]> = obstacles.iter().map(Obstacle::bits).collect();
+        key.sort_unstable();
+        key
+    }
+
+    pub fn new(mut obstacles: Vec<Obstacle>) -> Self {
+        // the same set always gives the same graph, and so the same paths
+        obstacles.sort_by_key(Obstacle::bits);
+        // octagon corners sit far enough out for its edges to clear the inflated circle
+        let corner_scale = 1. / (PI / NODES_PER_OBSTACLE as f32).cos();
+        let mut corners = Vec::new();
+        for o in obstacles.iter() {
+            for i in 0..NODES_PER_OBSTACLE {
+                let angle = i as f32 * 2. * PI / NODES_PER_OBSTACLE as f32;
+                let corner =
+                    o.centre + Vec2::new(angle.cos(), angle.sin()) * o.inflated() * corner_scale;
+                if !obstacles.iter().any(|other| other.contains(corner)) {
+                    corners.push(corner);
+                }
+            }
+        }
+        let sight = (0..corners.len())
+            .map(|a| {
+                (0..corners.len())
+                    .filter(|b| *b != a && visible(&obstacles, corners[a], corners[*b]))
+                    .collect()
+            })
+            .collect();
+        VisibilityGraph {
+            obstacles,
+            corners,
+            sight,
+        }
+    }
+
+    /// A* from `from` to `to` through the graph. Only the legs leaving `from` and reaching `to`
+    /// are checked against the obstacles.
+    /// `cost` scales the length of each leg, so routes can favour some regions; it must be at
+    /// least 1 for the search to stay optimal. Returns the waypoints after `from`, ending with
+    /// `to`.
+    pub fn plan(&self, from: Vec2, to: Vec2, cost: impl Fn(Vec2, Vec2) -> f32) -> Vec<Vec2> {
+        if visible(&self.obstacles, from, to) {
+            return vec![to];
+        }
+
+        // node 0 is the start, node 1 the goal, corner i is node i + 2
+        let node = |i: usize| match i {
+            0 => from,
+            1 => to,
+            corner => self.corners[corner - 2],
+        };
+        let count = self.corners.len() + 2;
+        let sees_goal: Vec<bool> = self
+            .corners
+            .iter()
+            .map(|corner| visible(&self.obstacles, *corner, to))
+            .collect();
+        let next_nodes = |i: usize| -> Vec<usize> {
+            match i {
+                0 => (2..count)
+                    .filter(|next| visible(&self.obstacles, from, node(*next)))
+                    .collect(),
+                corner => {
+                    let goal = sees_goal[corner - 2].then(|| 1);
+                    goal.into_iter()
+                        .chain(self.sight[corner - 2].iter().map(|next| next + 2))
+                        .collect()
+                }
+            }
+        };
+
+        let mut best = vec![f32::INFINITY; count];
+        let mut came_from = vec![usize::MAX; count];
+        let mut open = BinaryHeap::new();
+        best[0] = 0.;
+        open.push(Open {
+            estimate: from.distance(to),
+            node: 0,
+        });
+        while let Some(Open {
+            estimate,
+            node: current,
+        }) = open.pop()
+        {
+            if current == 1 {
+                let mut path = Vec::new();
+                let mut step = 1;
+                while step != 0 {
+                    path.push(node(step));
+                    step = came_from[step];
+                }
+                path.reverse();
+                return path;
+            }
+            if estimate > best[current] + node(current).distance(to) {
+                continue;
+            }
+            let pos = node(current);
+            for next in next_nodes(current) {
+                let leg = pos.distance(node(next)) * cost(pos, node(next)).max(1.);
+                let reached = best[current] + leg;
+                if reached < best[next] {
+                    best[next] = reached;
+                    came_from[next] = current;
+                    open.push(Open {
+                        estimate: reached + node(next).distance(to),
+                        node: next,
+                    });
+                }
+            }
+        }
+        // boxed in: fly straight and let steering do what it can
+        vec![to]
+    }
+}
+
+/// Plans once through `obstacles`, building a graph that is thrown away afterwards.
+pub fn plan_path(
+    from: Vec2,
+    to: Vec2,
+    obstacles: &[Obstacle],
+    cost: impl Fn(Vec2, Vec2) -> f32,
+) -> Vec<Vec2> {
+    VisibilityGraph::new(obstacles.to_vec()).plan(from, to, cost)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flat(_: Vec2, _: Vec2) -> f32 {
+        1.
+    }
+
+    fn clears(path: &[Vec2], from: Vec2, obstacles: &[Obstacle]) -> bool {
+        let mut last = from;
+        path.iter().all(|next| {
+            let clear = visible(obstacles, last, *next);
+            last = *next;
+            clear
+        })
+    }
+
+    #[test]
+    fn open_space_is_flown_straight() {
+        let to = Vec2::new(100., 0.);
+        let planet = Obstacle {
+            centre: Vec2::new(50., 40.),
+            radius: 10.,
+        };
+        assert_eq!(plan_path(Vec2::ZERO, to, &[planet], flat), [to]);
+    }
+
+    #[test]
+    fn paths_go_around_obstacles() {
+        let (from, to) = (Vec2::ZERO, Vec2::new(100., 0.));
+        let obstacles = [
+            Obstacle {
+                centre: Vec2::new(30., 0.),
+                radius: 8.,
+            },
+            Obstacle {
+                centre: Vec2::new(70., 2.),
+                radius: 8.,
+            },
+        ];
+        let path = plan_path(from, to, &obstacles, flat);
+        assert_eq!(path.last(), Some(&to));
+        assert!(path.len() > 1);
+        assert!(clears(&path, from, &obstacles));
+    }
+
+    #[test]
+    fn costly_legs_are_avoided() {
+        let (from, to) = (Vec2::ZERO, Vec2::new(100., 0.));
+        let obstacles = [Obstacle {
+            centre: Vec2::new(50., 0.),
+            radius: 10.,
+        }];
+        // the side below the straight line costs ten times more
+        let path = plan_path(from, to, &obstacles, |a, b| match a.y + b.y < 0. {
+            true => 10.,
+            false => 1.,
+        });
+        assert!(path.iter().all(|p| p.y >= 0.));
+        let path = plan_path(from, to, &obstacles, |a, b| match a.y + b.y > 0. {
+            true => 10.,
+            false => 1.,
+        });
+        assert!(path.iter().all(|p| p.y <= 0.));
+    }
+
+    #[test]
+    fn graphs_do_not_depend_on_obstacle_order() {
+        let a = Obstacle {
+            centre: Vec2::new(30., 1.),
+            radius: 8.,
+        };
+        let b = Obstacle {
+            centre: Vec2::new(70., -1.),
+            radius: 8.,
+        };
+        assert_eq!(VisibilityGraph::key(&[a, b]), VisibilityGraph::key(&[b, a]));
+        let (from, to) = (Vec2::ZERO, Vec2::new(100., 0.));
+        assert_eq!(
+            VisibilityGraph::new(vec![a, b]).plan(from, to, flat),
+            VisibilityGraph::new(vec![b, a]).plan(from, to, flat)
+        );
+    }
+
+    #[test]
+    fn corridors_drop_far_and_end_obstacles() {
+        let (from, to) = (Vec2::ZERO, Vec2::new(100., 0.));
+        let near = Obstacle {
+            centre: Vec2::new(50., 20.),
+            radius: 5.,
+        };
+        let far = Obstacle {
+            centre: Vec2::new(50., 200.),
+            radius: 5.,
+        };
+        let home = Obstacle {
+            centre: Vec2::ZERO,
+            radius: 5.,
+        };
+        let found = corridor(from, to, [near, far, home].into_iter());
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].centre, near.centre);
+    }
+}
@@ -0,0 +1,257 @@
+use bevy::prelude::{Entity, IVec2, Vec2};
+use bevy::utils::HashMap;
+
+/// Uniform grid over the galaxy plane. An entity is stored in every cell its circle overlaps,
+/// so lookups only visit a handful of cells instead of every entity.
+/// Lookups report entities by their `order`, never by hash or insertion order, so peers that
+/// give every entity the same order find the same entities in the same order.
+/// Kept free of game types so the benchmark can build it on its own.
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<Entity>>,
+    entries: HashMap<Entity, GridEntry>,
+}
+
+struct GridEntry {
+    order: u32,
+    pos: Vec2,
+    radius: f32,
+    min: IVec2,
+    max: IVec2,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        SpatialGrid {
+            cell_size,
+            cells: HashMap::default(),
+            entries: HashMap::default(),
+        }
+    }
+
+    fn cell(&self, pos: Vec2) -> IVec2 {
+        (pos / self.cell_size).floor().as_ivec2()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds or moves an entity. Cells are only touched when the entity crosses a cell border.
+    pub fn insert(&mut self, entity: Entity, order: u32, pos: Vec2, radius: f32) {
+        let min = self.cell(pos - Vec2::splat(radius));
+        let max = self.cell(pos + Vec2::splat(radius));
+        if let Some(entry) = self.entries.get_mut(&entity) {
+            if entry.min == min && entry.max == max {
+                entry.order = order;
+                entry.pos = pos;
+                entry.radius = radius;
+                return;
+            }
+        }
+        self.remove(entity);
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                self.cells.entry(IVec2::new(x, y)).or_default().push(entity);
+            }
+        }
+        self.entries.insert(
+            entity,
+            GridEntry {
+                order,
+                pos,
+                radius,
+                min,
+                max,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        if let Some(entry) = self.entries.remove(&entity) {
+            for x in entry.min.x..=entry.max.x {
+                for y in entry.min.y..=entry.max.y {
+                    let key = IVec2::new(x, y);
+                    if let Some(cell) = self.cells.get_mut(&key) {
+                        cell.retain(|e| *e != entity);
+                        if cell.is_empty() {
+                            self.cells.remove(&key);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Removes every entity `keep` turns down, such as the ones that were despawned.
+    pub fn retain(&mut self, mut keep: impl FnMut(Entity) -> bool) {
+        let gone: Vec<Entity> = self.entries.keys().copied().filter(|e| !keep(*e)).collect();
+        for entity in gone {
+            self.remove(entity);
+        }
+    }
+
+    pub fn position(&self, entity: Entity) -> Option<Vec2> {
+        self.entries.get(&entity).map(|entry| entry.pos)
+    }
+
+    pub fn radius(&self, entity: Entity) -> Option<f32> {
+        self.entries.get(&entity).map(|entry| entry.radius)
+    }
+
+    /// First entity in order whose circle contains `pos`.
+    pub fn at_point(&self, pos: Vec2) -> Option<Entity> {
+        self.cells
+            .get(&self.cell(pos))?
+            .iter()
+            .copied()
+            .filter(|e| {
+                let entry = &self.entries[e];
+                entry.pos.distance(pos) < entry.radius
+            })
+            .min_by_key(|e| self.entries[e].order)
+    }
+
+    /// Entity whose centre is closest to `pos`, no further than `max_dist`.
+    pub fn nearest(&self, pos: Vec2, max_dist: f32) -> Option<Entity> {
+        let mut nearest = None;
+        let mut min_dist = max_dist;
+        for e in self.in_rect(pos - Vec2::splat(max_dist), pos + Vec2::splat(max_dist)) {
+            let dist = self.entries[&e].pos.distance(pos);
+            if dist <= min_dist {
+                min_dist = dist;
+                nearest = Some(e);
+            }
+        }
+        nearest
+    }
+
+    /// Entities whose centre lies inside the rectangle, in order.
+    pub fn in_rect(&self, min: Vec2, max: Vec2) -> Vec<Entity> {
+        let mut found = self.in_rect_unordered(min, max);
+        found.sort_by_key(|e| self.entries[e].order);
+        found
+    }
+
+    fn in_rect_unordered(&self, min: Vec2, max: Vec2) -> Vec<Entity> {
+        let inside = |pos: Vec2| pos.cmpge(min).all() && pos.cmple(max).all();
+        let (cell_min, cell_max) = (self.cell(min), self.cell(max));
+        let cell_count =
+            (cell_max.x - cell_min.x + 1) as usize * (cell_max.y - cell_min.y + 1) as usize;
+        // a rectangle covering more cells than are occupied is cheaper to answer entity by entity
+        if cell_count > self.cells.len() {
+            return self
+                .entries
+                .iter()
+                .filter(|(_, entry)| inside(entry.pos))
+                .map(|(e, _)| *e)
+                .collect();
+        }
+        let mut found = Vec::new();
+        for x in cell_min.x..=cell_max.x {
+            for y in cell_min.y..=cell_max.y {
+                let key = IVec2::new(x, y);
+                if let Some(cell) = self.cells.get(&key) {
+                    for e in cell.iter() {
+                        let entry = &self.entries[e];
+                        // entities spanning several cells are reported from the cell of their centre
+                        if inside(entry.pos) && self.cell(entry.pos) == key {
+                            found.push(*e);
+                        }
+                    }
+                }
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(circles: &[(u32, Vec2, f32)]) -> SpatialGrid {
+        let mut grid = SpatialGrid::new(10.);
+        for (id, pos, radius) in circles {
+            grid.insert(Entity::from_raw(*id), *id, *pos, *radius);
+        }
+        grid
+    }
+
+    #[test]
+    fn rect_lookups_follow_the_order() {
+        let grid = grid(&[
+            (3, Vec2::new(5., 5.), 1.),
+            (1, Vec2::new(25., 5.), 1.),
+            (2, Vec2::new(15., 15.), 1.),
+            (4, Vec2::new(95., 95.), 1.),
+        ]);
+        let ids = |found: Vec<Entity>| found.into_iter().map(|e| e.id()).collect::<Vec<_>>();
+        assert_eq!(ids(grid.in_rect(Vec2::ZERO, Vec2::splat(30.))), [1, 2, 3]);
+        // wider than the occupied cells, answered entity by entity
+        assert_eq!(
+            ids(grid.in_rect(Vec2::splat(-1000.), Vec2::splat(1000.))),
+            [1, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn large_circles_are_found_once() {
+        let grid = grid(&[(1, Vec2::new(10., 10.), 15.)]);
+        assert_eq!(
+            grid.in_rect(Vec2::ZERO, Vec2::splat(20.)),
+            [Entity::from_raw(1)]
+        );
+        // found from a cell it overlaps but is not centred in
+        assert_eq!(
+            grid.at_point(Vec2::new(-2., 10.)),
+            Some(Entity::from_raw(1))
+        );
+    }
+
+    #[test]
+    fn overlapping_circles_resolve_by_order() {
+        let grid = grid(&[(7, Vec2::new(5., 5.), 3.), (2, Vec2::new(6., 5.), 3.)]);
+        assert_eq!(grid.at_point(Vec2::new(5.5, 5.)), Some(Entity::from_raw(2)));
+        assert_eq!(grid.at_point(Vec2::new(2.5, 5.)), Some(Entity::from_raw(7)));
+        assert_eq!(grid.at_point(Vec2::new(50., 50.)), None);
+    }
+
+    #[test]
+    fn moved_and_removed_entities_leave_their_cells() {
+        let mut grid = grid(&[(1, Vec2::new(5., 5.), 1.)]);
+        let e = Entity::from_raw(1);
+        grid.insert(e, 1, Vec2::new(55., 5.), 1.);
+        assert_eq!(grid.at_point(Vec2::new(5., 5.)), None);
+        assert_eq!(grid.at_point(Vec2::new(55., 5.)), Some(e));
+        assert_eq!(grid.position(e), Some(Vec2::new(55., 5.)));
+        grid.remove(e);
+        assert!(grid.is_empty());
+        assert_eq!(grid.at_point(Vec2::new(55., 5.)), None);
+    }
+
+    #[test]
+    fn retain_drops_the_rest() {
+        let mut grid = grid(&[(1, Vec2::new(5., 5.), 1.), (2, Vec2::new(6., 5.), 8.)]);
+        grid.retain(|e| e.id() == 1);
+        assert_eq!(grid.len(), 1);
+        assert_eq!(
+            grid.in_rect(Vec2::splat(-20.), Vec2::splat(20.)),
+            [Entity::from_raw(1)]
+        );
+    }
+
+    #[test]
+    fn nearest_stays_within_reach() {
+        let grid = grid(&[(1, Vec2::new(0., 0.), 1.), (2, Vec2::new(8., 0.), 1.)]);
+        assert_eq!(
+            grid.nearest(Vec2::new(5., 0.), 4.),
+            Some(Entity::from_raw(2))
+        );
+        assert_eq!(grid.nearest(Vec2::new(30., 0.), 4.), None);
+    }
+}
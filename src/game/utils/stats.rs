@@ -0,0 +1,44 @@
+/// Values research can change. Base values stay where they are, in components and the balance
+/// file, and the effective value is worked out from the base and the modifiers that apply
+/// whenever it is needed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stat {
+    FighterSpeed,
+    WeaponRange,
+    TraderCost,
+    /// Seconds between two production ticks.
+    ProductionInterval,
+}
+
+impl Stat {
+    pub fn name(self) -> &'static str {
+        match self {
+            Stat::FighterSpeed => "fighter speed",
+            Stat::WeaponRange => "weapon range",
+            Stat::TraderCost => "trader cost",
+            Stat::ProductionInterval => "production time",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Modifier {
+    pub stat: Stat,
+    /// Change of the stat, negative to lower it.
+    pub percent: f32,
+}
+
+/// Effective value of `stat`. Percentages add up before being applied, so two +15% make +30% in
+/// any order. A stat never drops below a tenth of its base.
+pub fn resolve<'a>(
+    stat: Stat,
+    base: f32,
+    modifiers: impl IntoIterator<Item = &'a Modifier>,
+) -> f32 {
+    let percent: f32 = modifiers
+        .into_iter()
+        .filter(|m| m.stat == stat)
+        .map(|m| m.percent)
+        .sum();
+    base * (1. + percent / 100.).max(0.1)
+}
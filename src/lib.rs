@@ -0,0 +1,12 @@
+//! The game itself. `main.rs` only picks the plugins; benchmarks drive the systems from here.
+
+#[allow(unused_imports)]
+pub mod assets;
+pub mod camera;
+pub mod game;
+pub mod math_util;
+pub mod minimap;
+pub mod net;
+pub mod selection;
+pub mod state;
+pub mod ui;
@@ -1,26 +1,37 @@
-#[allow(unused_imports)]
-pub mod assets;
-pub mod camera;
-pub mod game;
-pub mod math_util;
-pub mod selection;
-pub mod state;
-pub mod ui;
+use std::time::Duration;
 
-use bevy::{diagnostic::FrameTimeDiagnosticsPlugin, prelude::*};
+use bevy::{
+    app::{ScheduleRunnerPlugin, ScheduleRunnerSettings},
+    diagnostic::FrameTimeDiagnosticsPlugin,
+    prelude::*,
+    render::settings::WgpuSettings,
+    winit::WinitPlugin,
+};
 
 #[cfg(feature = "debug")]
 use bevy_inspector_egui::{InspectorPlugin, RegisterInspectable, WorldInspectorPlugin};
+#[cfg(feature = "debug")]
+use galactic_wars::game::components::characteristics;
 
-use assets::{materials::PlanetMaterial, AssetsPlugin};
-use camera::CameraPlugin;
-use game::GamePlugin;
-use selection::SelectionPlugin;
-use state::StatePlugin;
-use ui::UiPlugin;
+use galactic_wars::assets::{materials::PlanetMaterial, AssetsPlugin};
+use galactic_wars::camera::CameraPlugin;
+use galactic_wars::game::GamePlugin;
+use galactic_wars::minimap::MiniMapPlugin;
+use galactic_wars::net::{components::NetMode, NetPlugin};
+use galactic_wars::selection::SelectionPlugin;
+use galactic_wars::state::StatePlugin;
+use galactic_wars::ui::UiPlugin;
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let net_mode = NetMode::from_args(&args);
     let mut app = App::new();
+    if net_mode.is_server() {
+        app.insert_resource(net_mode);
+        run_headless_server(app);
+        return;
+    }
+    app.insert_resource(net_mode);
     app.insert_resource(WindowDescriptor {
         title: "Galactic Wars".to_string(),
         width: 800.,
@@ -34,7 +45,9 @@ fn main() {
     .add_plugin(CameraPlugin)
     .add_plugin(StatePlugin)
     .add_plugin(SelectionPlugin)
+    .add_plugin(MiniMapPlugin)
     .add_plugin(GamePlugin)
+    .add_plugin(NetPlugin)
     .add_plugin(AssetsPlugin)
     .add_plugin(UiPlugin);
 
@@ -43,9 +56,28 @@ fn main() {
     #[cfg(feature = "debug")]
     app.add_plugin(WorldInspectorPlugin::new())
         // .add_plugin(InspectorPlugin::<game::components::interact::Destination>::new());
-        .register_inspectable::<game::components::characteristics::Movement>()
-        .register_inspectable::<game::components::characteristics::Avoidance>()
-        .register_inspectable::<game::components::characteristics::Planet>();
+        .register_inspectable::<characteristics::Movement>()
+        .register_inspectable::<characteristics::Avoidance>()
+        .register_inspectable::<characteristics::Planet>();
+
+    app.run();
+}
+
+/// Dedicated server: the full simulation without window, renderer or UI.
+fn run_headless_server(mut app: App) {
+    app.insert_resource(WgpuSettings {
+        backends: None,
+        ..default()
+    })
+    .insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(
+        1.0 / 60.0,
+    )))
+    .add_plugins_with(DefaultPlugins, |group| group.disable::<WinitPlugin>())
+    .add_plugin(ScheduleRunnerPlugin)
+    .add_plugin(MaterialPlugin::<PlanetMaterial>::default())
+    .add_plugin(StatePlugin)
+    .add_plugin(GamePlugin)
+    .add_plugin(NetPlugin);
 
     app.run();
 }
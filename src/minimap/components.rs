@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+
+/// Screen placement of the minimap and the part of the galaxy it covers.
+pub struct MiniMap {
+    /// Side of the minimap, in pixels.
+    pub size: f32,
+    /// Distance from the bottom right corner of the window, in pixels.
+    pub margin: f32,
+    /// Half the side of the world square shown on the minimap.
+    pub extent: f32,
+    pub hovered: bool,
+    pub dragging: bool,
+    /// World position under the cursor, while hovering the minimap.
+    pub cursor: Option<Vec2>,
+}
+
+impl Default for MiniMap {
+    fn default() -> Self {
+        MiniMap {
+            size: 200.,
+            margin: 10.,
+            extent: 330.,
+            hovered: false,
+            dragging: false,
+            cursor: None,
+        }
+    }
+}
+
+impl MiniMap {
+    /// Converts a window cursor position (origin bottom left) to a world position,
+    /// if the cursor is over the minimap.
+    pub fn screen_to_world(&self, cursor: Vec2, window: &Window) -> Option<Vec2> {
+        let origin = Vec2::new(window.width() - self.margin - self.size, self.margin);
+        let local = (cursor - origin) / self.size;
+        if local.cmplt(Vec2::ZERO).any() || local.cmpgt(Vec2::ONE).any() {
+            return None;
+        }
+        Some((local * 2. - Vec2::ONE) * self.extent)
+    }
+
+    /// Converts a world position to pixels from the top left corner of the minimap.
+    pub fn world_to_map(&self, pos: Vec2) -> Vec2 {
+        let local = ((pos / self.extent + Vec2::ONE) / 2.).clamp(Vec2::ZERO, Vec2::ONE);
+        Vec2::new(local.x, 1. - local.y) * self.size
+    }
+}
+
+pub struct MiniMapRefresh(pub Timer);
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MiniMapDot {
+    pub left: f32,
+    pub top: f32,
+    pub size: f32,
+    pub color: (f32, f32, f32, f32),
+}
+
+/// What the minimap widget draws, in pixels from its top left corner.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MiniMapView {
+    pub size: f32,
+    pub galaxy: (f32, f32, f32),
+    pub planets: Vec<MiniMapDot>,
+    pub fleets: Vec<MiniMapDot>,
+    pub viewport: (f32, f32, f32, f32),
+}
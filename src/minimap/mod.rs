@@ -0,0 +1,25 @@
+use crate::state::GameState;
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+mod systems;
+use systems::*;
+pub mod components;
+use components::*;
+
+pub struct MiniMapPlugin;
+impl Plugin for MiniMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MiniMap::default())
+            .insert_resource(MiniMapView::default())
+            .insert_resource(MiniMapRefresh(Timer::from_seconds(0.1, true)))
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::InGame)
+                    .with_system(minimap_hover)
+                    .with_system(minimap_navigation)
+                    .with_system(minimap_orders)
+                    .with_system(update_minimap_view)
+                    .into(),
+            );
+    }
+}
@@ -0,0 +1,187 @@
+use bevy::prelude::*;
+use bevy::utils::{HashMap, Uuid};
+
+use crate::camera::{ndc_to_world, MainCamera};
+use crate::game::components::characteristics::*;
+use crate::game::components::commands::*;
+use crate::game::components::config::{galaxy_size_to_radius, InitGameSetup};
+use crate::game::components::players::Ownership;
+use crate::game::resources::{
+    game_obj_res::SpatialIndex,
+    game_status_res::{DeployChoice, FormationChoice, IsTradeRouting, PendingOrder},
+    player_res::{LocalPlayer, PlayerVision, RegisteredPlayers},
+};
+use crate::game::systems::event_log::player_color;
+use crate::game::systems::movement::{destination_at, move_order};
+use crate::game::systems::orders::give_armed_order;
+use crate::game::systems::production::deploy_amount;
+use crate::selection::components::Selected;
+
+use super::components::*;
+
+/// Minimap pixels grouped into a single fleet dot.
+const FLEET_CELL: f32 = 4.;
+
+pub fn minimap_hover(windows: Res<Windows>, mut minimap: ResMut<MiniMap>) {
+    let window = windows.get_primary().unwrap();
+    let cursor = window
+        .cursor_position()
+        .and_then(|cursor| minimap.screen_to_world(cursor, window));
+    if minimap.cursor != cursor {
+        minimap.hovered = cursor.is_some();
+        minimap.cursor = cursor;
+    }
+}
+
+pub fn minimap_navigation(
+    ms_input: Res<Input<MouseButton>>,
+    mut minimap: ResMut<MiniMap>,
+    mut camera_query: Query<&mut Transform, With<MainCamera>>,
+) {
+    if ms_input.just_pressed(MouseButton::Left) && minimap.hovered {
+        minimap.dragging = true;
+    }
+    if ms_input.just_released(MouseButton::Left) {
+        minimap.dragging = false;
+    }
+    if minimap.dragging {
+        if let Some(pos) = minimap.cursor {
+            let mut transf = camera_query.single_mut();
+            transf.translation.x = pos.x;
+            transf.translation.y = pos.y;
+        }
+    }
+}
+
+/// Right-clicks on the minimap give the same orders as in the galaxy: ship moves and armed
+/// orders, and fighter deployments from the selected planets. Trade route stops are added by
+/// `define_trade_route`.
+pub fn minimap_orders(
+    kb_input: Res<Input<KeyCode>>,
+    ms_input: Res<Input<MouseButton>>,
+    minimap: Res<MiniMap>,
+    choice: Res<FormationChoice>,
+    deploy_choice: Res<DeployChoice>,
+    is_trade_routing: Res<IsTradeRouting>,
+    mut pending: ResMut<PendingOrder>,
+    query: Query<Entity, (With<Selected>, With<Destination>)>,
+    selected_planets: Query<Entity, (With<Planet>, With<Selected>)>,
+    index: Res<SpatialIndex>,
+    mut cmd_writer: EventWriter<IssueCommand>,
+) {
+    if is_trade_routing.key_down {
+        return;
+    }
+    if let (true, Some(pos)) = (ms_input.just_pressed(MouseButton::Right), minimap.cursor) {
+        let planets: Vec<Entity> = selected_planets.iter().collect();
+        if !planets.is_empty() {
+            cmd_writer.send(IssueCommand(PlayerCommand::DeployFighters {
+                planets,
+                destination: destination_at(&index, pos),
+                amount: deploy_amount(&kb_input, &deploy_choice),
+            }));
+        }
+        let ships: Vec<Entity> = query.iter().collect();
+        if !ships.is_empty() {
+            let command = match pending.order {
+                Some(_) => give_armed_order(&mut pending, &kb_input, &choice, &index, pos, ships),
+                None => {
+                    let destination = destination_at(&index, pos);
+                    Some(move_order(&kb_input, &choice, ships, destination))
+                }
+            };
+            if let Some(command) = command {
+                cmd_writer.send(IssueCommand(command));
+            }
+        }
+    }
+}
+
+pub fn update_minimap_view(
+    time: Res<Time>,
+    game_config: Res<InitGameSetup>,
+    players: Res<RegisteredPlayers>,
+    local: Option<Res<LocalPlayer>>,
+    vision: Res<PlayerVision>,
+    mut refresh: ResMut<MiniMapRefresh>,
+    mut minimap: ResMut<MiniMap>,
+    mut view: ResMut<MiniMapView>,
+    camera_query: Query<(&Transform, &Camera), With<MainCamera>>,
+    planet_query: Query<(Entity, &Transform, &Ownership), With<Planet>>,
+    ship_query: Query<(Entity, &Transform, &Ownership), With<Ship>>,
+) {
+    let radius = galaxy_size_to_radius(&game_config.galaxy_size);
+    if minimap.extent != radius * 1.1 {
+        minimap.extent = radius * 1.1;
+    }
+    if !refresh.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    let local = local.map(|l| l.0);
+    let seen = |e: Entity| local.map_or(true, |l| vision.sees(l, e));
+    let to_rgba = |owner: Option<Uuid>| {
+        let color = player_color(&players, owner);
+        (color.r(), color.g(), color.b(), 1.)
+    };
+
+    let planets = planet_query
+        .iter()
+        .map(|(e, transf, owner)| {
+            let pos = minimap.world_to_map(transf.translation.truncate());
+            // planets out of sight keep the colour of their owner when last seen
+            let owner = match (seen(e), local) {
+                (false, Some(l)) => vision.last_seen(l, e).and_then(|intel| intel.owner),
+                _ => owner.0,
+            };
+            MiniMapDot {
+                left: pos.x - 3.,
+                top: pos.y - 3.,
+                size: 6.,
+                color: to_rgba(owner),
+            }
+        })
+        .collect();
+
+    // ships close to each other are drawn as a single dot per owner
+    let mut cells: HashMap<(i32, i32, Option<Uuid>), u32> = HashMap::default();
+    for (_, transf, owner) in ship_query.iter().filter(|(e, _, _)| seen(*e)) {
+        let cell = (minimap.world_to_map(transf.translation.truncate()) / FLEET_CELL).floor();
+        *cells
+            .entry((cell.x as i32, cell.y as i32, owner.0))
+            .or_insert(0) += 1;
+    }
+    let fleets = cells
+        .into_iter()
+        .map(|((x, y, owner), count)| {
+            let size = if count > 5 { 4. } else { 2. };
+            MiniMapDot {
+                left: x as f32 * FLEET_CELL,
+                top: y as f32 * FLEET_CELL,
+                size,
+                color: to_rgba(owner),
+            }
+        })
+        .collect();
+
+    let (transf, camera) = camera_query.single();
+    let bottom_left = minimap.world_to_map(ndc_to_world(Vec2::new(-1., -1.), transf, camera));
+    let top_right = minimap.world_to_map(ndc_to_world(Vec2::new(1., 1.), transf, camera));
+    let galaxy = radius / minimap.extent * minimap.size;
+
+    *view = MiniMapView {
+        size: minimap.size,
+        galaxy: (
+            (minimap.size - galaxy) / 2.,
+            (minimap.size - galaxy) / 2.,
+            galaxy,
+        ),
+        planets,
+        fleets,
+        viewport: (
+            bottom_left.x,
+            top_right.y,
+            top_right.x - bottom_left.x,
+            bottom_left.y - top_right.y,
+        ),
+    };
+}
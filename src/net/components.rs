@@ -0,0 +1,262 @@
+use std::net::{TcpListener, TcpStream};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, Uuid},
+};
+
+use super::protocol::{EntityState, NetMessage};
+use crate::game::components::{characteristics::NetId, commands::PlayerCommand};
+use crate::game::resources::game_status_res::{PlayerSlot, SIM_DT};
+
+pub const MAX_PLAYERS: usize = 4;
+/// Simulation ticks in a lockstep turn, all run once the turn is confirmed.
+pub const TICKS_PER_TURN: u32 = 6;
+/// Length of a lockstep turn. Commands issued during a turn are executed `INPUT_DELAY` turns later.
+pub const TURN_SECONDS: f32 = TICKS_PER_TURN as f32 * SIM_DT;
+pub const INPUT_DELAY: u32 = 3;
+/// Every peer hashes its game state each `CHECKSUM_INTERVAL` turns to detect desyncs.
+pub const CHECKSUM_INTERVAL: u32 = 50;
+pub const HEARTBEAT_SECONDS: f64 = 1.0;
+pub const DROP_TIMEOUT_SECONDS: f64 = 10.0;
+/// Rate at which a dedicated server streams state to its clients.
+pub const SNAPSHOT_SECONDS: f32 = 0.05;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NetMode {
+    Offline,
+    Host {
+        port: String,
+        name: String,
+    },
+    Join {
+        addr: String,
+        name: String,
+    },
+    /// Headless authoritative server, starting the match once `players` joined.
+    Server {
+        port: String,
+        players: usize,
+    },
+    Connect {
+        addr: String,
+        name: String,
+    },
+}
+impl NetMode {
+    /// `galactic-wars host <port> <name>` or `galactic-wars join <addr:port> <name>` for lockstep,
+    /// `galactic-wars server <port> [players]` or `galactic-wars connect <addr:port> <name>` for
+    /// a dedicated server. Arguments are only checked when the connection opens, so a bad one
+    /// shows up in the lobby.
+    pub fn from_args(args: &[String]) -> Self {
+        let name = args.get(3).cloned().unwrap_or_else(|| "Player".to_string());
+        match (args.get(1).map(|s| s.as_str()), args.get(2)) {
+            (Some("host"), Some(port)) => NetMode::Host {
+                port: port.clone(),
+                name,
+            },
+            (Some("join"), Some(addr)) => NetMode::Join {
+                addr: addr.clone(),
+                name,
+            },
+            (Some("server"), Some(port)) => NetMode::Server {
+                port: port.clone(),
+                // an unreadable count is refused with the other out of range ones
+                players: args.get(3).map_or(2, |p| p.parse().unwrap_or(0)),
+            },
+            (Some("connect"), Some(addr)) => NetMode::Connect {
+                addr: addr.clone(),
+                name,
+            },
+            _ => NetMode::Offline,
+        }
+    }
+
+    pub fn is_networked(&self) -> bool {
+        *self != NetMode::Offline
+    }
+
+    /// Owns the listening socket and the lobby.
+    pub fn is_host(&self) -> bool {
+        matches!(self, NetMode::Host { .. } | NetMode::Server { .. })
+    }
+
+    pub fn is_lockstep(&self) -> bool {
+        matches!(self, NetMode::Host { .. } | NetMode::Join { .. })
+    }
+
+    pub fn is_server(&self) -> bool {
+        matches!(self, NetMode::Server { .. })
+    }
+
+    pub fn is_client(&self) -> bool {
+        matches!(self, NetMode::Connect { .. })
+    }
+
+    /// Clients of a dedicated server only mirror the state they receive.
+    pub fn simulates(&self) -> bool {
+        !self.is_client()
+    }
+}
+
+// EVENTS
+pub struct NetReceived {
+    pub peer: usize,
+    pub msg: NetMessage,
+}
+
+pub struct Peer {
+    pub stream: TcpStream,
+    pub inbox: Vec<u8>,
+    pub outbox: Vec<u8>,
+    pub player: Option<Uuid>,
+    pub last_seen: f64,
+    pub closed: bool,
+}
+impl Peer {
+    pub fn new(stream: TcpStream, now: f64) -> Self {
+        Self {
+            stream,
+            inbox: Vec::new(),
+            outbox: Vec::new(),
+            player: None,
+            last_seen: now,
+            closed: false,
+        }
+    }
+
+    pub fn send(&mut self, msg: &NetMessage) {
+        self.outbox.extend(msg.frame());
+    }
+}
+
+/// Open sockets. The host keeps one peer per client, a client only keeps the host.
+pub struct Connection {
+    pub listener: Option<TcpListener>,
+    pub peers: Vec<Peer>,
+    pub last_heartbeat: f64,
+}
+impl Connection {
+    pub fn new(listener: Option<TcpListener>, peers: Vec<Peer>, now: f64) -> Self {
+        Self {
+            listener,
+            peers,
+            last_heartbeat: now,
+        }
+    }
+
+    pub fn broadcast(&mut self, msg: &NetMessage) {
+        for peer in self.peers.iter_mut().filter(|p| !p.closed) {
+            peer.send(msg);
+        }
+    }
+}
+
+pub struct Lobby {
+    pub slots: Vec<PlayerSlot>,
+    pub local: Option<Uuid>,
+    /// Why the lobby could not be opened or joined.
+    pub error: Option<String>,
+}
+impl Lobby {
+    pub fn new(local: Option<PlayerSlot>) -> Self {
+        Self {
+            local: local.as_ref().map(|slot| slot.uuid),
+            slots: local.into_iter().collect(),
+            error: None,
+        }
+    }
+
+    pub fn failed(error: String) -> Self {
+        Self {
+            error: Some(error),
+            ..Self::new(None)
+        }
+    }
+}
+
+/// What the lobby screen shows.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LobbyView {
+    pub lines: Vec<String>,
+    pub is_host: bool,
+    pub error: Option<String>,
+}
+
+pub struct Lockstep {
+    /// Next turn to be executed.
+    pub turn: u32,
+    pub elapsed: f32,
+    /// Local commands waiting to be sent with the next batch.
+    pub pending: Vec<PlayerCommand>,
+    /// Encoded command batches by turn and player.
+    pub batches: HashMap<u32, HashMap<Uuid, Vec<u8>>>,
+    /// Players in the match, sorted so every peer executes batches in the same order.
+    pub players: Vec<Uuid>,
+    /// Players that left, and the first turn for which no batch is expected from them.
+    pub dropped: HashMap<Uuid, u32>,
+    /// Highest turn received from each player.
+    pub last_turn_from: HashMap<Uuid, u32>,
+    pub checksums: HashMap<u32, HashMap<Uuid, u64>>,
+    pub desynced: Option<u32>,
+}
+impl Lockstep {
+    pub fn new(mut players: Vec<Uuid>, empty_batch: Vec<u8>) -> Self {
+        players.sort();
+        let mut batches = HashMap::new();
+        // nobody can issue commands for the first turns, so they are known to be empty
+        for turn in 0..INPUT_DELAY {
+            let turn_batches: &mut HashMap<Uuid, Vec<u8>> = batches.entry(turn).or_default();
+            for p in players.iter() {
+                turn_batches.insert(*p, empty_batch.clone());
+            }
+        }
+        Self {
+            turn: 0,
+            elapsed: 0.,
+            pending: Vec::new(),
+            batches,
+            players,
+            dropped: HashMap::new(),
+            last_turn_from: HashMap::new(),
+            checksums: HashMap::new(),
+            desynced: None,
+        }
+    }
+
+    pub fn expects(&self, player: &Uuid, turn: u32) -> bool {
+        match self.dropped.get(player) {
+            Some(from_turn) => turn < *from_turn,
+            None => true,
+        }
+    }
+
+    /// True when every player still in the match sent its batch for the current turn.
+    pub fn is_ready(&self) -> bool {
+        let batches = self.batches.get(&self.turn);
+        self.players
+            .iter()
+            .filter(|p| self.expects(p, self.turn))
+            .all(|p| batches.map_or(false, |b| b.contains_key(p)))
+    }
+}
+
+/// Server side: last state sent to each player for every object, so that only changes go out.
+#[derive(Default)]
+pub struct SnapshotHistory {
+    pub tick: u32,
+    pub elapsed: f32,
+    pub last_sent: HashMap<Option<Uuid>, HashMap<NetId, EntityState>>,
+    /// Ships lost since the last snapshot: owner, destroyer and whether it was a trader.
+    pub destroyed: Vec<(Option<Uuid>, Option<Uuid>, bool)>,
+    /// Planets attacked since the last snapshot: planet, owner and attacker.
+    pub attacked: Vec<(Entity, Uuid, Uuid)>,
+}
+
+/// Client side: replicated ships glide between the two latest snapshots.
+#[derive(Component)]
+pub struct Interpolated {
+    pub from: Transform,
+    pub to: Transform,
+    pub elapsed: f32,
+}
@@ -0,0 +1,111 @@
+pub mod components;
+pub mod protocol;
+mod sync;
+mod systems;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::game::resources::player_res::LocalPlayer;
+use crate::state::GameState;
+use components::*;
+use sync::*;
+use systems::*;
+
+/// Runs the command pipeline. Offline, commands are executed with the next simulation tick. In a
+/// lockstep game the peers batch commands per turn, exchange them through the host, and simulate
+/// a turn once every player's batch for it has arrived. With a dedicated server, clients send
+/// commands to the server, which alone simulates and streams back snapshots.
+pub struct NetPlugin;
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        let net_mode = app
+            .world
+            .get_resource::<NetMode>()
+            .cloned()
+            .unwrap_or(NetMode::Offline);
+        if !net_mode.is_lockstep() {
+            app.add_system(advance_sim_clock.run_in_state(GameState::InGame));
+        }
+        if !net_mode.is_networked() {
+            app.add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::InGame)
+                    .run_if_resource_exists::<LocalPlayer>()
+                    .with_system(forward_local_commands)
+                    .into(),
+            );
+            return;
+        }
+
+        app.add_event::<NetReceived>()
+            .add_startup_system(open_connection)
+            .add_system(poll_network.run_if_resource_exists::<Connection>())
+            .add_system(
+                handle_lobby_messages
+                    .run_in_state(GameState::Lobby)
+                    .run_if_resource_exists::<Lobby>(),
+            );
+
+        if net_mode.is_lockstep() {
+            app.add_system(
+                lobby_start_key
+                    .run_in_state(GameState::Lobby)
+                    .run_if(is_host)
+                    .run_if_resource_exists::<Lobby>(),
+            )
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::InGame)
+                    .run_if_resource_exists::<Lockstep>()
+                    .run_if_resource_exists::<LocalPlayer>()
+                    .with_system(collect_local_commands)
+                    .with_system(handle_lockstep_messages)
+                    .with_system(advance_turn)
+                    .with_system(detect_dropped_players)
+                    .into(),
+            );
+        }
+
+        if net_mode.is_server() {
+            app.insert_resource(SnapshotHistory::default())
+                .add_system(
+                    server_autostart
+                        .run_in_state(GameState::Lobby)
+                        .run_if_resource_exists::<Lobby>(),
+                )
+                .add_system_set(
+                    ConditionSet::new()
+                        .run_in_state(GameState::InGame)
+                        .with_system(receive_client_commands)
+                        .with_system(relay_social_commands)
+                        .with_system(broadcast_snapshots)
+                        .into(),
+                );
+        }
+
+        if net_mode.is_client() {
+            app.add_startup_system(disable_physics).add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::InGame)
+                    .with_system(send_client_commands)
+                    .with_system(receive_relayed_commands)
+                    .with_system(apply_snapshots)
+                    .with_system(apply_snapshot_events)
+                    .with_system(interpolate_transforms)
+                    .with_system(detect_server_lost)
+                    .into(),
+            );
+        }
+    }
+}
+
+/// Replicas are moved by snapshots, the local physics must not fight them.
+fn disable_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = false;
+}
+
+fn is_host(net_mode: Res<NetMode>) -> bool {
+    net_mode.is_host()
+}
@@ -0,0 +1,1044 @@
+use bevy::{prelude::*, utils::Uuid};
+
+use crate::game::components::{
+    characteristics::{DestinationEnum, NetId, PlanetType, ShipType},
+    commands::{DeployAmount, PlayerCommand},
+    players::DiplomacyAction,
+};
+use crate::game::resources::{game_status_res::PlayerSlot, player_res::Research};
+use crate::game::utils::formation::Formation;
+
+/// Messages exchanged between peers. The host relays `Turn` and `Checksum` messages to every
+/// other client, so clients only ever talk to the host.
+#[derive(Clone, Debug)]
+pub enum NetMessage {
+    Hello {
+        name: String,
+    },
+    LobbyState {
+        slots: Vec<PlayerSlot>,
+        you: Uuid,
+    },
+    StartMatch {
+        seed: u64,
+    },
+    Turn {
+        turn: u32,
+        player: Uuid,
+        commands: Vec<u8>,
+    },
+    Checksum {
+        turn: u32,
+        player: Uuid,
+        value: u64,
+    },
+    PlayerDropped {
+        player: Uuid,
+        from_turn: u32,
+    },
+    Heartbeat,
+    /// Client to dedicated server. The issuing player is known from the connection.
+    Commands {
+        commands: Vec<u8>,
+    },
+    /// Dedicated server to clients: objects that changed or disappeared since the last snapshot,
+    /// and the ships lost and planets attacked meanwhile.
+    Snapshot {
+        tick: u32,
+        states: Vec<EntityState>,
+        removed: Vec<NetId>,
+        money: Vec<(Uuid, u32)>,
+        research: Vec<(Uuid, Research)>,
+        /// Owner, destroyer and whether it was a trader, per ship lost.
+        destroyed: Vec<(Option<Uuid>, Option<Uuid>, bool)>,
+        /// Planet, its owner and the attacker.
+        attacked: Vec<(NetId, Uuid, Uuid)>,
+    },
+    /// Dedicated server to clients: chat and diplomacy commands executed on the server.
+    Relayed {
+        player: Uuid,
+        commands: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ObjectKind {
+    Planet(PlanetType),
+    Fighter,
+    Trader,
+    Dreadnought,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntityState {
+    pub id: NetId,
+    pub kind: ObjectKind,
+    pub owner: Option<Uuid>,
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub fighters: f32,
+    /// Player capturing a planet and how far along it is.
+    pub capture: Option<(Uuid, f32)>,
+    pub buildings: Vec<u8>,
+}
+
+#[derive(Default)]
+pub struct Writer(pub Vec<u8>);
+impl Writer {
+    pub fn put_u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+    pub fn put_u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    pub fn put_u64(&mut self, v: u64) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    pub fn put_f32(&mut self, v: f32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    pub fn put_uuid(&mut self, v: Uuid) {
+        self.0.extend_from_slice(&v.as_u128().to_le_bytes());
+    }
+    pub fn put_bytes(&mut self, v: &[u8]) {
+        self.put_u32(v.len() as u32);
+        self.0.extend_from_slice(v);
+    }
+    pub fn put_str(&mut self, v: &str) {
+        self.put_bytes(v.as_bytes());
+    }
+    pub fn put_vec3(&mut self, v: Vec3) {
+        self.put_f32(v.x);
+        self.put_f32(v.y);
+        self.put_f32(v.z);
+    }
+    pub fn put_quat(&mut self, v: Quat) {
+        for f in v.to_array() {
+            self.put_f32(f);
+        }
+    }
+    pub fn put_owner(&mut self, v: Option<Uuid>) {
+        match v {
+            Some(uuid) => {
+                self.put_u8(1);
+                self.put_uuid(uuid);
+            }
+            None => self.put_u8(0),
+        }
+    }
+}
+
+pub struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+impl<'a> Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let slice = self.buf.get(self.pos..self.pos + n)?;
+        self.pos += n;
+        Some(slice)
+    }
+    pub fn get_u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+    pub fn get_u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+    pub fn get_u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+    pub fn get_f32(&mut self) -> Option<f32> {
+        Some(f32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+    pub fn get_uuid(&mut self) -> Option<Uuid> {
+        Some(Uuid::from_u128(u128::from_le_bytes(
+            self.take(16)?.try_into().ok()?,
+        )))
+    }
+    pub fn get_bytes(&mut self) -> Option<Vec<u8>> {
+        let len = self.get_u32()? as usize;
+        Some(self.take(len)?.to_vec())
+    }
+    pub fn get_str(&mut self) -> Option<String> {
+        String::from_utf8(self.get_bytes()?).ok()
+    }
+    pub fn get_vec3(&mut self) -> Option<Vec3> {
+        Some(Vec3::new(self.get_f32()?, self.get_f32()?, self.get_f32()?))
+    }
+    pub fn get_quat(&mut self) -> Option<Quat> {
+        Some(Quat::from_xyzw(
+            self.get_f32()?,
+            self.get_f32()?,
+            self.get_f32()?,
+            self.get_f32()?,
+        ))
+    }
+    pub fn get_owner(&mut self) -> Option<Option<Uuid>> {
+        match self.get_u8()? {
+            0 => Some(None),
+            _ => Some(Some(self.get_uuid()?)),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+}
+
+impl NetMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        match self {
+            NetMessage::Hello { name } => {
+                w.put_u8(0);
+                w.put_str(name);
+            }
+            NetMessage::LobbyState { slots, you } => {
+                w.put_u8(1);
+                w.put_uuid(*you);
+                w.put_u32(slots.len() as u32);
+                for slot in slots.iter() {
+                    w.put_uuid(slot.uuid);
+                    w.put_str(&slot.name);
+                    w.put_u8(slot.slot);
+                }
+            }
+            NetMessage::StartMatch { seed } => {
+                w.put_u8(2);
+                w.put_u64(*seed);
+            }
+            NetMessage::Turn {
+                turn,
+                player,
+                commands,
+            } => {
+                w.put_u8(3);
+                w.put_u32(*turn);
+                w.put_uuid(*player);
+                w.put_bytes(commands);
+            }
+            NetMessage::Checksum {
+                turn,
+                player,
+                value,
+            } => {
+                w.put_u8(4);
+                w.put_u32(*turn);
+                w.put_uuid(*player);
+                w.put_u64(*value);
+            }
+            NetMessage::PlayerDropped { player, from_turn } => {
+                w.put_u8(5);
+                w.put_uuid(*player);
+                w.put_u32(*from_turn);
+            }
+            NetMessage::Heartbeat => w.put_u8(6),
+            NetMessage::Commands { commands } => {
+                w.put_u8(7);
+                w.put_bytes(commands);
+            }
+            NetMessage::Snapshot {
+                tick,
+                states,
+                removed,
+                money,
+                research,
+                destroyed,
+                attacked,
+            } => {
+                w.put_u8(8);
+                w.put_u32(*tick);
+                w.put_u32(states.len() as u32);
+                for state in states.iter() {
+                    put_entity_state(&mut w, state);
+                }
+                w.put_u32(removed.len() as u32);
+                for id in removed.iter() {
+                    w.put_u32(id.0);
+                }
+                w.put_u32(money.len() as u32);
+                for (player, amount) in money.iter() {
+                    w.put_uuid(*player);
+                    w.put_u32(*amount);
+                }
+                w.put_u32(research.len() as u32);
+                for (player, progress) in research.iter() {
+                    w.put_uuid(*player);
+                    w.put_bytes(&progress.done);
+                    match progress.current {
+                        Some(tech) => {
+                            w.put_u8(1);
+                            w.put_u8(tech);
+                        }
+                        None => w.put_u8(0),
+                    }
+                    w.put_f32(progress.elapsed);
+                }
+                w.put_u32(destroyed.len() as u32);
+                for (owner, by, is_trader) in destroyed.iter() {
+                    w.put_owner(*owner);
+                    w.put_owner(*by);
+                    w.put_u8(*is_trader as u8);
+                }
+                w.put_u32(attacked.len() as u32);
+                for (planet, owner, attacker) in attacked.iter() {
+                    w.put_u32(planet.0);
+                    w.put_uuid(*owner);
+                    w.put_uuid(*attacker);
+                }
+            }
+            NetMessage::Relayed { player, commands } => {
+                w.put_u8(9);
+                w.put_uuid(*player);
+                w.put_bytes(commands);
+            }
+        }
+        w.0
+    }
+
+    pub fn decode(buf: &[u8]) -> Option<NetMessage> {
+        let mut r = Reader::new(buf);
+        let msg = match r.get_u8()? {
+            0 => NetMessage::Hello { name: r.get_str()? },
+            1 => {
+                let you = r.get_uuid()?;
+                let len = r.get_u32()?;
+                let mut slots = Vec::new();
+                for _ in 0..len {
+                    slots.push(PlayerSlot {
+                        uuid: r.get_uuid()?,
+                        name: r.get_str()?,
+                        slot: r.get_u8()?,
+                    });
+                }
+                NetMessage::LobbyState { slots, you }
+            }
+            2 => NetMessage::StartMatch { seed: r.get_u64()? },
+            3 => NetMessage::Turn {
+                turn: r.get_u32()?,
+                player: r.get_uuid()?,
+                commands: r.get_bytes()?,
+            },
+            4 => NetMessage::Checksum {
+                turn: r.get_u32()?,
+                player: r.get_uuid()?,
+                value: r.get_u64()?,
+            },
+            5 => NetMessage::PlayerDropped {
+                player: r.get_uuid()?,
+                from_turn: r.get_u32()?,
+            },
+            6 => NetMessage::Heartbeat,
+            7 => NetMessage::Commands {
+                commands: r.get_bytes()?,
+            },
+            8 => {
+                let tick = r.get_u32()?;
+                let len = r.get_u32()?;
+                let mut states = Vec::new();
+                for _ in 0..len {
+                    states.push(get_entity_state(&mut r)?);
+                }
+                let len = r.get_u32()?;
+                let mut removed = Vec::new();
+                for _ in 0..len {
+                    removed.push(NetId(r.get_u32()?));
+                }
+                let len = r.get_u32()?;
+                let mut money = Vec::new();
+                for _ in 0..len {
+                    money.push((r.get_uuid()?, r.get_u32()?));
+                }
+                let len = r.get_u32()?;
+                let mut research = Vec::new();
+                for _ in 0..len {
+                    let player = r.get_uuid()?;
+                    let done = r.get_bytes()?;
+                    let current = match r.get_u8()? {
+                        0 => None,
+                        _ => Some(r.get_u8()?),
+                    };
+                    let elapsed = r.get_f32()?;
+                    research.push((
+                        player,
+                        Research {
+                            done,
+                            current,
+                            elapsed,
+                        },
+                    ));
+                }
+                let len = r.get_u32()?;
+                let mut destroyed = Vec::new();
+                for _ in 0..len {
+                    destroyed.push((r.get_owner()?, r.get_owner()?, r.get_u8()? != 0));
+                }
+                let len = r.get_u32()?;
+                let mut attacked = Vec::new();
+                for _ in 0..len {
+                    attacked.push((NetId(r.get_u32()?), r.get_uuid()?, r.get_uuid()?));
+                }
+                NetMessage::Snapshot {
+                    tick,
+                    states,
+                    removed,
+                    money,
+                    research,
+                    destroyed,
+                    attacked,
+                }
+            }
+            9 => NetMessage::Relayed {
+                player: r.get_uuid()?,
+                commands: r.get_bytes()?,
+            },
+            _ => return None,
+        };
+        Some(msg)
+    }
+
+    /// Length prefixed frame, ready to be written to a stream.
+    pub fn frame(&self) -> Vec<u8> {
+        let body = self.encode();
+        let mut framed = (body.len() as u32).to_le_bytes().to_vec();
+        framed.extend(body);
+        framed
+    }
+}
+
+/// Removes every complete frame from `inbox`, leaving partial data for the next read.
+pub fn split_frames(inbox: &mut Vec<u8>) -> Vec<Vec<u8>> {
+    let mut frames = Vec::new();
+    loop {
+        if inbox.len() < 4 {
+            break;
+        }
+        let len = u32::from_le_bytes([inbox[0], inbox[1], inbox[2], inbox[3]]) as usize;
+        if inbox.len() < 4 + len {
+            break;
+        }
+        frames.push(inbox[4..4 + len].to_vec());
+        inbox.drain(..4 + len);
+    }
+    frames
+}
+
+fn put_entity_state(w: &mut Writer, state: &EntityState) {
+    w.put_u32(state.id.0);
+    match state.kind {
+        ObjectKind::Planet(planet_type) => {
+            w.put_u8(0);
+            w.put_u8(match planet_type {
+                PlanetType::Outpost => 0,
+                PlanetType::Watch => 1,
+                PlanetType::Base => 2,
+                PlanetType::Colony => 3,
+                PlanetType::Capital => 4,
+            });
+        }
+        ObjectKind::Fighter => w.put_u8(1),
+        ObjectKind::Trader => w.put_u8(2),
+        ObjectKind::Dreadnought => w.put_u8(3),
+    }
+    w.put_owner(state.owner);
+    w.put_vec3(state.translation);
+    w.put_quat(state.rotation);
+    w.put_f32(state.fighters);
+    w.put_owner(state.capture.map(|(player, _)| player));
+    if let Some((_, progress)) = state.capture {
+        w.put_f32(progress);
+    }
+    w.put_bytes(&state.buildings);
+}
+
+fn get_entity_state(r: &mut Reader) -> Option<EntityState> {
+    let id = NetId(r.get_u32()?);
+    let kind = match r.get_u8()? {
+        0 => ObjectKind::Planet(match r.get_u8()? {
+            0 => PlanetType::Outpost,
+            1 => PlanetType::Watch,
+            2 => PlanetType::Base,
+            3 => PlanetType::Colony,
+            4 => PlanetType::Capital,
+            _ => return None,
+        }),
+        1 => ObjectKind::Fighter,
+        2 => ObjectKind::Trader,
+        3 => ObjectKind::Dreadnought,
+        _ => return None,
+    };
+    Some(EntityState {
+        id,
+        kind,
+        owner: r.get_owner()?,
+        translation: r.get_vec3()?,
+        rotation: r.get_quat()?,
+        fighters: r.get_f32()?,
+        capture: match r.get_owner()? {
+            Some(player) => Some((player, r.get_f32()?)),
+            None => None,
+        },
+        buildings: r.get_bytes()?,
+    })
+}
+
+fn put_destination(
+    w: &mut Writer,
+    dest: &DestinationEnum,
+    to_net: &impl Fn(Entity) -> Option<NetId>,
+) {
+    match dest {
+        DestinationEnum::None => w.put_u8(0),
+        DestinationEnum::Space(loc) => {
+            w.put_u8(1);
+            w.put_vec3(*loc);
+        }
+        DestinationEnum::Planet { planet, loc } => match to_net(*planet) {
+            Some(id) => {
+                w.put_u8(2);
+                w.put_u32(id.0);
+                w.put_vec3(*loc);
+            }
+            None => {
+                w.put_u8(1);
+                w.put_vec3(*loc);
+            }
+        },
+    }
+}
+
+fn get_destination(
+    r: &mut Reader,
+    to_entity: &impl Fn(NetId) -> Option<Entity>,
+) -> Option<DestinationEnum> {
+    let dest = match r.get_u8()? {
+        0 => DestinationEnum::None,
+        1 => DestinationEnum::Space(r.get_vec3()?),
+        2 => {
+            let id = NetId(r.get_u32()?);
+            let loc = r.get_vec3()?;
+            match to_entity(id) {
+                Some(planet) => DestinationEnum::Planet { planet, loc },
+                None => DestinationEnum::Space(loc),
+            }
+        }
+        _ => return None,
+    };
+    Some(dest)
+}
+
+fn put_formation(w: &mut Writer, formation: Formation) {
+    w.put_u8(match formation {
+        Formation::Cluster => 0,
+        Formation::Line => 1,
+        Formation::Wedge => 2,
+        Formation::Circle => 3,
+        Formation::Box => 4,
+    });
+}
+
+fn get_formation(r: &mut Reader) -> Option<Formation> {
+    let formation = match r.get_u8()? {
+        0 => Formation::Cluster,
+        1 => Formation::Line,
+        2 => Formation::Wedge,
+        3 => Formation::Circle,
+        4 => Formation::Box,
+        _ => return None,
+    };
+    Some(formation)
+}
+
+fn put_entities(w: &mut Writer, entities: &[Entity], to_net: &impl Fn(Entity) -> Option<NetId>) {
+    let ids: Vec<NetId> = entities.iter().filter_map(|e| to_net(*e)).collect();
+    w.put_u32(ids.len() as u32);
+    for id in ids {
+        w.put_u32(id.0);
+    }
+}
+
+fn get_entities(
+    r: &mut Reader,
+    to_entity: &impl Fn(NetId) -> Option<Entity>,
+) -> Option<Vec<Entity>> {
+    let len = r.get_u32()?;
+    let mut entities = Vec::new();
+    for _ in 0..len {
+        // entities destroyed before the turn executes are silently dropped from the order
+        if let Some(e) = to_entity(NetId(r.get_u32()?)) {
+            entities.push(e);
+        }
+    }
+    Some(entities)
+}
+
+/// Serializes a batch of commands, translating local entities to `NetId`s.
+pub fn encode_commands(
+    commands: &[PlayerCommand],
+    to_net: impl Fn(Entity) -> Option<NetId>,
+) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.put_u32(commands.len() as u32);
+    for command in commands.iter() {
+        match command {
+            PlayerCommand::MoveShips {
+                ships,
+                destination,
+                formation,
+                hold,
+            } => {
+                w.put_u8(0);
+                put_entities(&mut w, ships, &to_net);
+                put_destination(&mut w, destination, &to_net);
+                put_formation(&mut w, *formation);
+                w.put_u8(*hold as u8);
+            }
+            PlayerCommand::AttackMove {
+                ships,
+                destination,
+                formation,
+                hold,
+            } => {
+                w.put_u8(8);
+                put_entities(&mut w, ships, &to_net);
+                put_destination(&mut w, destination, &to_net);
+                put_formation(&mut w, *formation);
+                w.put_u8(*hold as u8);
+            }
+            PlayerCommand::Patrol { ships, points } => {
+                w.put_u8(9);
+                put_entities(&mut w, ships, &to_net);
+                w.put_u32(points.len() as u32);
+                for point in points.iter() {
+                    w.put_vec3(*point);
+                }
+            }
+            PlayerCommand::Guard { ships, target } => {
+                w.put_u8(10);
+                put_entities(&mut w, ships, &to_net);
+                put_entities(&mut w, &[*target], &to_net);
+            }
+            PlayerCommand::HoldPosition { ships } => {
+                w.put_u8(11);
+                put_entities(&mut w, ships, &to_net);
+            }
+            PlayerCommand::DeployFighters {
+                planets,
+                destination,
+                amount,
+            } => {
+                w.put_u8(1);
+                put_entities(&mut w, planets, &to_net);
+                put_destination(&mut w, destination, &to_net);
+                match amount {
+                    DeployAmount::Share(percent) => {
+                        w.put_u8(0);
+                        w.put_u8(*percent);
+                    }
+                    DeployAmount::Exact(count) => {
+                        w.put_u8(1);
+                        w.put_u32(*count);
+                    }
+                }
+            }
+            PlayerCommand::SetTradeRoute { ships, route } => {
+                w.put_u8(2);
+                put_entities(&mut w, ships, &to_net);
+                w.put_u32(route.len() as u32);
+                for dest in route.iter() {
+                    put_destination(&mut w, dest, &to_net);
+                }
+            }
+            PlayerCommand::Chat { text } => {
+                w.put_u8(3);
+                w.put_str(text);
+            }
+            PlayerCommand::Diplomacy { to, action } => {
+                w.put_u8(4);
+                w.put_uuid(*to);
+                w.put_u8(match action {
+                    DiplomacyAction::ProposeAlliance => 0,
+                    DiplomacyAction::AcceptAlliance => 1,
+                    DiplomacyAction::DeclareWar => 2,
+                });
+            }
+            PlayerCommand::MergeFleets {
+                ships,
+                formation,
+                hold,
+            } => {
+                w.put_u8(5);
+                put_entities(&mut w, ships, &to_net);
+                put_formation(&mut w, *formation);
+                w.put_u8(*hold as u8);
+            }
+            PlayerCommand::SplitFleet { ships } => {
+                w.put_u8(6);
+                put_entities(&mut w, ships, &to_net);
+            }
+            PlayerCommand::QueueDestination { ships, destination } => {
+                w.put_u8(7);
+                put_entities(&mut w, ships, &to_net);
+                put_destination(&mut w, destination, &to_net);
+            }
+            PlayerCommand::UpgradePlanets { planets } => {
+                w.put_u8(12);
+                put_entities(&mut w, planets, &to_net);
+            }
+            PlayerCommand::Build { planets, building } => {
+                w.put_u8(13);
+                put_entities(&mut w, planets, &to_net);
+                w.put_u8(*building);
+            }
+            PlayerCommand::Research { tech } => {
+                w.put_u8(14);
+                w.put_u8(*tech);
+            }
+            PlayerCommand::BuyShip { planets, ship_type } => {
+                w.put_u8(15);
+                put_entities(&mut w, planets, &to_net);
+                w.put_u8(match ship_type {
+                    ShipType::Trade => 0,
+                    ShipType::Dreadnought => 1,
+                    ShipType::Fighter => 2,
+                });
+            }
+        }
+    }
+    w.0
+}
+
+/// Inverse of `encode_commands`, translating `NetId`s back to local entities.
+pub fn decode_commands(
+    buf: &[u8],
+    to_entity: impl Fn(NetId) -> Option<Entity>,
+) -> Option<Vec<PlayerCommand>> {
+    let mut r = Reader::new(buf);
+    let len = r.get_u32()?;
+    let mut commands = Vec::new();
+    for _ in 0..len {
+        let command = match r.get_u8()? {
+            0 => PlayerCommand::MoveShips {
+                ships: get_entities(&mut r, &to_entity)?,
+                destination: get_destination(&mut r, &to_entity)?,
+                formation: get_formation(&mut r)?,
+                hold: r.get_u8()? != 0,
+            },
+            1 => PlayerCommand::DeployFighters {
+                planets: get_entities(&mut r, &to_entity)?,
+                destination: get_destination(&mut r, &to_entity)?,
+                amount: match r.get_u8()? {
+                    0 => DeployAmount::Share(r.get_u8()?),
+                    1 => DeployAmount::Exact(r.get_u32()?),
+                    _ => return None,
+                },
+            },
+            2 => {
+                let ships = get_entities(&mut r, &to_entity)?;
+                let route_len = r.get_u32()?;
+                let mut route = Vec::new();
+                for _ in 0..route_len {
+                    route.push(get_destination(&mut r, &to_entity)?);
+                }
+                PlayerCommand::SetTradeRoute { ships, route }
+            }
+            3 => PlayerCommand::Chat { text: r.get_str()? },
+            4 => PlayerCommand::Diplomacy {
+                to: r.get_uuid()?,
+                action: match r.get_u8()? {
+                    0 => DiplomacyAction::ProposeAlliance,
+                    1 => DiplomacyAction::AcceptAlliance,
+                    2 => DiplomacyAction::DeclareWar,
+                    _ => return None,
+                },
+            },
+            5 => PlayerCommand::MergeFleets {
+                ships: get_entities(&mut r, &to_entity)?,
+                formation: get_formation(&mut r)?,
+                hold: r.get_u8()? != 0,
+            },
+            6 => PlayerCommand::SplitFleet {
+                ships: get_entities(&mut r, &to_entity)?,
+            },
+            7 => PlayerCommand::QueueDestination {
+                ships: get_entities(&mut r, &to_entity)?,
+                destination: get_destination(&mut r, &to_entity)?,
+            },
+            8 => PlayerCommand::AttackMove {
+                ships: get_entities(&mut r, &to_entity)?,
+                destination: get_destination(&mut r, &to_entity)?,
+                formation: get_formation(&mut r)?,
+                hold: r.get_u8()? != 0,
+            },
+            9 => {
+                let ships = get_entities(&mut r, &to_entity)?;
+                let points_len = r.get_u32()?;
+                let mut points = Vec::new();
+                for _ in 0..points_len {
+                    points.push(r.get_vec3()?);
+                }
+                PlayerCommand::Patrol { ships, points }
+            }
+            10 => {
+                let ships = get_entities(&mut r, &to_entity)?;
+                // a guarded entity destroyed before the turn executes leaves the ships holding
+                match get_entities(&mut r, &to_entity)?.first() {
+                    Some(target) => PlayerCommand::Guard {
+                        ships,
+                        target: *target,
+                    },
+                    None => PlayerCommand::HoldPosition { ships },
+                }
+            }
+            11 => PlayerCommand::HoldPosition {
+                ships: get_entities(&mut r, &to_entity)?,
+            },
+            12 => PlayerCommand::UpgradePlanets {
+                planets: get_entities(&mut r, &to_entity)?,
+            },
+            13 => PlayerCommand::Build {
+                planets: get_entities(&mut r, &to_entity)?,
+                building: r.get_u8()?,
+            },
+            14 => PlayerCommand::Research { tech: r.get_u8()? },
+            15 => PlayerCommand::BuyShip {
+                planets: get_entities(&mut r, &to_entity)?,
+                ship_type: match r.get_u8()? {
+                    0 => ShipType::Trade,
+                    1 => ShipType::Dreadnought,
+                    2 => ShipType::Fighter,
+                    _ => return None,
+                },
+            },
+            _ => return None,
+        };
+        commands.push(command);
+    }
+    Some(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ids on the wire differ from local entities, as they do between peers
+    fn to_net(e: Entity) -> Option<NetId> {
+        Some(NetId(e.id() + 100))
+    }
+
+    fn to_entity(id: NetId) -> Option<Entity> {
+        id.0.checked_sub(100).map(Entity::from_raw)
+    }
+
+    fn ships(ids: &[u32]) -> Vec<Entity> {
+        ids.iter().map(|id| Entity::from_raw(*id)).collect()
+    }
+
+    // commands and messages only derive `Debug`, which shows every field
+    fn assert_same(a: &impl std::fmt::Debug, b: &impl std::fmt::Debug) {
+        assert_eq!(format!("{:?}", a), format!("{:?}", b));
+    }
+
+    #[test]
+    fn commands_round_trip() {
+        let planet = DestinationEnum::Planet {
+            planet: Entity::from_raw(7),
+            loc: Vec3::new(1., 2., 3.),
+        };
+        let space = DestinationEnum::Space(Vec3::new(-4., 5.5, 0.));
+        let commands = vec![
+            PlayerCommand::MoveShips {
+                ships: ships(&[1, 2]),
+                destination: planet.clone(),
+                formation: Formation::Wedge,
+                hold: true,
+            },
+            PlayerCommand::AttackMove {
+                ships: ships(&[3]),
+                destination: space.clone(),
+                formation: Formation::Cluster,
+                hold: false,
+            },
+            PlayerCommand::Patrol {
+                ships: ships(&[1]),
+                points: vec![Vec3::ZERO, Vec3::new(10., -10., 0.)],
+            },
+            PlayerCommand::Guard {
+                ships: ships(&[1]),
+                target: Entity::from_raw(9),
+            },
+            PlayerCommand::HoldPosition { ships: ships(&[4]) },
+            PlayerCommand::QueueDestination {
+                ships: ships(&[1]),
+                destination: DestinationEnum::None,
+            },
+            PlayerCommand::DeployFighters {
+                planets: ships(&[7, 8]),
+                destination: space.clone(),
+                amount: DeployAmount::Share(50),
+            },
+            PlayerCommand::DeployFighters {
+                planets: ships(&[7]),
+                destination: planet.clone(),
+                amount: DeployAmount::Exact(12),
+            },
+            PlayerCommand::SetTradeRoute {
+                ships: ships(&[5]),
+                route: vec![planet.clone(), space],
+            },
+            PlayerCommand::UpgradePlanets {
+                planets: ships(&[7]),
+            },
+            PlayerCommand::Build {
+                planets: ships(&[7]),
+                building: 2,
+            },
+            PlayerCommand::BuyShip {
+                planets: ships(&[8]),
+                ship_type: ShipType::Dreadnought,
+            },
+            PlayerCommand::Research { tech: 4 },
+            PlayerCommand::MergeFleets {
+                ships: ships(&[1, 2, 3]),
+                formation: Formation::Box,
+                hold: true,
+            },
+            PlayerCommand::SplitFleet { ships: ships(&[2]) },
+            PlayerCommand::Chat {
+                text: "gg, wp ✓".to_string(),
+            },
+            PlayerCommand::Diplomacy {
+                to: Uuid::from_u128(42),
+                action: DiplomacyAction::DeclareWar,
+            },
+        ];
+        let decoded = decode_commands(&encode_commands(&commands, to_net), to_entity);
+        assert_same(&decoded, &Some(commands));
+    }
+
+    #[test]
+    fn unknown_entities_are_dropped() {
+        let command = PlayerCommand::Guard {
+            ships: ships(&[1, 2]),
+            target: Entity::from_raw(9),
+        };
+        let buf = encode_commands(&[command], to_net);
+        // the guarded ship and one of the guards are gone on this peer
+        let decoded = decode_commands(&buf, |id| match id.0 {
+            101 => Some(Entity::from_raw(1)),
+            _ => None,
+        });
+        assert_same(
+            &decoded,
+            &Some(vec![PlayerCommand::HoldPosition { ships: ships(&[1]) }]),
+        );
+    }
+
+    #[test]
+    fn truncated_commands_are_refused() {
+        let buf = encode_commands(
+            &[PlayerCommand::Chat {
+                text: "hello".to_string(),
+            }],
+            to_net,
+        );
+        for len in 0..buf.len() {
+            assert!(decode_commands(&buf[..len], to_entity).is_none(), "{}", len);
+        }
+        assert!(decode_commands(&[1, 0, 0, 0, 200], to_entity).is_none());
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let player = Uuid::from_u128(1);
+        let planet = EntityState {
+            id: NetId(3),
+            kind: ObjectKind::Planet(PlanetType::Colony),
+            owner: Some(player),
+            translation: Vec3::new(10., 20., 0.),
+            rotation: Quat::from_rotation_z(0.5),
+            fighters: 12.5,
+            capture: Some((Uuid::from_u128(2), 0.25)),
+            buildings: vec![0, 2],
+        };
+        let ship = EntityState {
+            id: NetId(4),
+            kind: ObjectKind::Dreadnought,
+            owner: None,
+            capture: None,
+            buildings: Vec::new(),
+            ..planet.clone()
+        };
+        let messages = vec![
+            NetMessage::Hello {
+                name: "Ada".to_string(),
+            },
+            NetMessage::LobbyState {
+                slots: vec![PlayerSlot {
+                    uuid: player,
+                    name: "Ada".to_string(),
+                    slot: 1,
+                }],
+                you: player,
+            },
+            NetMessage::StartMatch { seed: u64::MAX },
+            NetMessage::Turn {
+                turn: 7,
+                player,
+                commands: vec![1, 2, 3],
+            },
+            NetMessage::Checksum {
+                turn: 8,
+                player,
+                value: 0xdead_beef,
+            },
+            NetMessage::PlayerDropped {
+                player,
+                from_turn: 9,
+            },
+            NetMessage::Heartbeat,
+            NetMessage::Commands {
+                commands: vec![4, 5],
+            },
+            NetMessage::Snapshot {
+                tick: 10,
+                states: vec![planet, ship],
+                removed: vec![NetId(5)],
+                money: vec![(player, 300)],
+                research: vec![(
+                    player,
+                    Research {
+                        done: vec![0, 1],
+                        current: Some(3),
+                        elapsed: 4.5,
+                    },
+                )],
+                destroyed: vec![(Some(player), None, true)],
+                attacked: vec![(NetId(3), player, Uuid::from_u128(2))],
+            },
+            NetMessage::Relayed {
+                player,
+                commands: vec![6],
+            },
+        ];
+        for msg in messages {
+            assert_same(&NetMessage::decode(&msg.encode()), &Some(msg));
+        }
+        assert!(NetMessage::decode(&[200]).is_none());
+    }
+
+    #[test]
+    fn frames_wait_for_their_last_byte() {
+        let (a, b) = (NetMessage::Heartbeat, NetMessage::StartMatch { seed: 3 });
+        let mut stream = a.frame();
+        stream.extend(b.frame());
+        let mut inbox = stream[..stream.len() - 1].to_vec();
+        let frames = split_frames(&mut inbox);
+        assert_eq!(frames, [a.encode()]);
+        inbox.push(*stream.last().unwrap());
+        assert_eq!(split_frames(&mut inbox), [b.encode()]);
+        assert!(inbox.is_empty());
+    }
+}
@@ -0,0 +1,461 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet, Uuid},
+};
+use iyes_loopless::prelude::*;
+
+use super::components::*;
+use super::protocol::*;
+use crate::assets::materials::PlanetMaterial;
+use crate::game::components::{characteristics::*, commands::*, players::Ownership};
+use crate::game::obj::{spawn_planet, spawn_ship};
+use crate::game::resources::{
+    game_obj_res::{NetIdAllocator, NetIdMap},
+    game_status_res::SimClock,
+    player_res::{PlayerMoney, PlayerResearch, PlayerVision, RegisteredPlayers, Research},
+};
+use crate::state::GameState;
+
+/// Client: orders are only requests, the server decides what happens.
+pub fn send_client_commands(
+    mut connection: ResMut<Connection>,
+    net_ids: Query<&NetId>,
+    mut issued: EventReader<IssueCommand>,
+) {
+    let pending: Vec<PlayerCommand> = issued.iter().map(|cmd| cmd.0.clone()).collect();
+    if !pending.is_empty() {
+        let commands = encode_commands(&pending, |e| net_ids.get(e).ok().cloned());
+        connection.broadcast(&NetMessage::Commands { commands });
+    }
+}
+
+/// Server: the issuing player comes from the socket, so a client cannot order for someone else.
+pub fn receive_client_commands(
+    connection: Res<Connection>,
+    net_id_map: Res<NetIdMap>,
+    mut ev_reader: EventReader<NetReceived>,
+    mut clock: ResMut<SimClock>,
+) {
+    for ev in ev_reader.iter() {
+        if let NetMessage::Commands { commands } = &ev.msg {
+            let player = match connection.peers[ev.peer].player {
+                Some(player) => player,
+                None => continue,
+            };
+            match decode_commands(commands, |id| net_id_map.0.get(&id).cloned()) {
+                Some(cmds) => {
+                    for command in cmds {
+                        clock.pending.push(ReceivedCommand { player, command });
+                    }
+                }
+                None => warn!("malformed commands from {}", player),
+            }
+        }
+    }
+}
+
+/// Server: chat and diplomacy have no simulation state to sync, so they are forwarded as is.
+pub fn relay_social_commands(
+    mut connection: ResMut<Connection>,
+    mut executed: EventReader<ExecuteCommand>,
+) {
+    for cmd in executed.iter().filter(|cmd| cmd.command.is_social()) {
+        let commands = encode_commands(&[cmd.command.clone()], |_| None);
+        connection.broadcast(&NetMessage::Relayed {
+            player: cmd.player,
+            commands,
+        });
+    }
+}
+
+/// Client: executes what the server relayed, which only updates the log and allegiances.
+pub fn receive_relayed_commands(
+    mut ev_reader: EventReader<NetReceived>,
+    mut clock: ResMut<SimClock>,
+) {
+    for ev in ev_reader.iter() {
+        if let NetMessage::Relayed { player, commands } = &ev.msg {
+            for command in decode_commands(commands, |_| None).unwrap_or_default() {
+                clock.pending.push(ReceivedCommand {
+                    player: *player,
+                    command,
+                });
+            }
+        }
+    }
+}
+
+/// Server: sends each client what changed within its sight, the ships it lost or destroyed and
+/// the attacks on planets it owns or sees.
+pub fn broadcast_snapshots(
+    time: Res<Time>,
+    money: Res<PlayerMoney>,
+    research: Res<PlayerResearch>,
+    vision: Res<PlayerVision>,
+    mut history: ResMut<SnapshotHistory>,
+    mut connection: ResMut<Connection>,
+    mut destroyed_reader: EventReader<ShipDestroyed>,
+    mut attacked_reader: EventReader<PlanetAttacked>,
+    query: Query<(
+        Entity,
+        &NetId,
+        &Ownership,
+        &Transform,
+        Option<&Planet>,
+        Option<&Fighter>,
+        Option<&Dreadnought>,
+        Option<&Siege>,
+        Option<&Buildings>,
+    )>,
+) {
+    for ev in destroyed_reader.iter() {
+        history.destroyed.push((ev.owner, ev.by, ev.is_trader));
+    }
+    for ev in attacked_reader.iter() {
+        history.attacked.push((ev.planet, ev.owner, ev.attacker));
+    }
+    history.elapsed += time.delta_seconds();
+    if history.elapsed < SNAPSHOT_SECONDS {
+        return;
+    }
+    history.elapsed = 0.;
+    history.tick += 1;
+
+    let mut all = Vec::new();
+    for (entity, id, owner, transform, planet, fighter, dreadnought, siege, buildings) in
+        query.iter()
+    {
+        let kind = match (planet, fighter, dreadnought) {
+            (Some(p), _, _) => ObjectKind::Planet(p.planet_type),
+            (None, Some(_), _) => ObjectKind::Fighter,
+            (None, None, Some(_)) => ObjectKind::Dreadnought,
+            (None, None, None) => ObjectKind::Trader,
+        };
+        let state = EntityState {
+            id: *id,
+            kind,
+            owner: owner.0,
+            translation: transform.translation,
+            rotation: transform.rotation,
+            fighters: planet.map_or(0., |p| p.fighters),
+            capture: siege.and_then(|s| s.capturer.map(|c| (c, s.progress))),
+            buildings: buildings.map_or(Vec::new(), |b| b.0.clone()),
+        };
+        all.push((entity, state));
+    }
+    let money: Vec<(Uuid, u32)> = money.0.iter().map(|(k, v)| (*k, *v)).collect();
+    let research: Vec<(Uuid, Research)> = research.0.iter().map(|(k, v)| (*k, v.clone())).collect();
+    let destroyed = std::mem::take(&mut history.destroyed);
+    let attacked: Vec<(Entity, NetId, Uuid, Uuid)> = std::mem::take(&mut history.attacked)
+        .into_iter()
+        .filter_map(|(planet, owner, attacker)| {
+            let (_, id, ..) = query.get(planet).ok()?;
+            Some((planet, *id, owner, attacker))
+        })
+        .collect();
+
+    // every client only gets what its player sees
+    for peer in connection.peers.iter_mut().filter(|p| !p.closed) {
+        let viewer = peer.player;
+        let last_sent = history.last_sent.entry(viewer).or_default();
+        let mut current = HashMap::new();
+        let mut states = Vec::new();
+        for (entity, state) in all.iter() {
+            let state = match viewer {
+                Some(player) if !vision.sees(player, *entity) => match state.kind {
+                    ObjectKind::Planet(_) => {
+                        let intel = vision.last_seen(player, *entity);
+                        EntityState {
+                            owner: intel.and_then(|i| i.owner),
+                            fighters: intel.map_or(0., |i| i.fighters),
+                            capture: None,
+                            buildings: Vec::new(),
+                            ..state.clone()
+                        }
+                    }
+                    // ships out of sight are removed from the client
+                    _ => continue,
+                },
+                _ => state.clone(),
+            };
+            if last_sent.get(&state.id) != Some(&state) {
+                states.push(state.clone());
+            }
+            current.insert(state.id, state);
+        }
+        let removed: Vec<NetId> = last_sent
+            .keys()
+            .filter(|id| !current.contains_key(id))
+            .cloned()
+            .collect();
+        *last_sent = current;
+        let involves = |player: Option<Uuid>| viewer.is_none() || player == viewer;
+        let destroyed: Vec<(Option<Uuid>, Option<Uuid>, bool)> = destroyed
+            .iter()
+            .filter(|(owner, by, _)| involves(*owner) || involves(*by))
+            .cloned()
+            .collect();
+        let attacked: Vec<(NetId, Uuid, Uuid)> = attacked
+            .iter()
+            .filter(|(planet, owner, _, _)| match viewer {
+                Some(player) => player == *owner || vision.sees(player, *planet),
+                None => true,
+            })
+            .map(|(_, id, owner, attacker)| (*id, *owner, *attacker))
+            .collect();
+
+        peer.send(&NetMessage::Snapshot {
+            tick: history.tick,
+            states,
+            removed,
+            money: money.clone(),
+            research: research.clone(),
+            destroyed,
+            attacked,
+        });
+    }
+}
+
+/// Client: mirrors the server state, spawning and despawning replicas as needed.
+pub fn apply_snapshots(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut my_materials: ResMut<Assets<PlanetMaterial>>,
+    mut net_ids: ResMut<NetIdAllocator>,
+    mut neutral_material: Local<Option<Handle<PlanetMaterial>>>,
+    asset_server: Res<AssetServer>,
+    players: Res<RegisteredPlayers>,
+    net_id_map: Res<NetIdMap>,
+    // replicas spawned by earlier snapshots that are not in `NetIdMap` yet
+    mut spawned: Local<HashMap<NetId, Entity>>,
+    mut money: ResMut<PlayerMoney>,
+    mut research: ResMut<PlayerResearch>,
+    mut research_writer: EventWriter<ResearchCompleted>,
+    mut ev_reader: EventReader<NetReceived>,
+    mut ownership_writer: EventWriter<TakeOwnership>,
+    mut query: Query<(
+        &mut Transform,
+        &Ownership,
+        Option<&mut Planet>,
+        Option<&mut Siege>,
+        Option<&mut Buildings>,
+        Option<&mut Interpolated>,
+    )>,
+) {
+    spawned.retain(|id, _| !net_id_map.0.contains_key(id));
+    // despawned by an earlier snapshot of this frame, but still in the world until it ends
+    let mut despawned = HashSet::new();
+    for ev in ev_reader.iter() {
+        if let NetMessage::Snapshot {
+            states,
+            removed,
+            money: balances,
+            research: progress,
+            ..
+        } = &ev.msg
+        {
+            for state in states.iter() {
+                // a replica that left the client's sight is gone, even if it is still mapped
+                let existing = net_id_map
+                    .0
+                    .get(&state.id)
+                    .filter(|e| query.contains(**e) && !despawned.contains(*e))
+                    .or_else(|| spawned.get(&state.id))
+                    .cloned();
+                match existing.and_then(|e| query.get_mut(e).ok().map(|q| (e, q))) {
+                    Some((
+                        entity,
+                        (mut transform, owner, planet, siege, buildings, interpolated),
+                    )) => {
+                        if let Some(mut planet) = planet {
+                            planet.fighters = state.fighters;
+                            if let ObjectKind::Planet(planet_type) = state.kind {
+                                if planet.planet_type != planet_type {
+                                    planet.planet_type = planet_type;
+                                }
+                            }
+                        }
+                        if let Some(mut buildings) = buildings {
+                            if buildings.0 != state.buildings {
+                                buildings.0 = state.buildings.clone();
+                            }
+                        }
+                        if let Some(mut siege) = siege {
+                            siege.capturer = state.capture.map(|(player, _)| player);
+                            siege.progress = state.capture.map_or(0., |(_, progress)| progress);
+                        }
+                        if let (Some(new_owner), true) = (state.owner, owner.0 != state.owner) {
+                            ownership_writer.send(TakeOwnership {
+                                entity,
+                                owner: new_owner,
+                            });
+                        }
+                        match interpolated {
+                            Some(mut interpolated) => {
+                                interpolated.from = *transform;
+                                interpolated.to = transform
+                                    .with_translation(state.translation)
+                                    .with_rotation(state.rotation);
+                                interpolated.elapsed = 0.;
+                            }
+                            None => {
+                                transform.translation = state.translation;
+                                transform.rotation = state.rotation;
+                            }
+                        }
+                    }
+                    // replicas only get their components once the frame applies its commands
+                    None if existing.is_some() => {}
+                    None => {
+                        let transform = Transform::from_translation(state.translation)
+                            .with_rotation(state.rotation);
+                        let entity = match state.kind {
+                            ObjectKind::Planet(planet_type) => {
+                                let material = match state.owner.and_then(|o| players.0.get(&o)) {
+                                    Some(details) => details.new_color.clone(),
+                                    None => neutral_material
+                                        .get_or_insert_with(|| {
+                                            my_materials.add(PlanetMaterial::new(
+                                                Color::GRAY,
+                                                asset_server.load("img/Planet2_40.png"),
+                                            ))
+                                        })
+                                        .clone(),
+                                };
+                                let entity = spawn_planet(
+                                    &mut commands,
+                                    &mut meshes,
+                                    &mut net_ids,
+                                    asset_server.load("fonts/ShareTechMono.ttf"),
+                                    planet_type,
+                                    transform,
+                                    state.owner,
+                                    material,
+                                    state.fighters,
+                                );
+                                commands
+                                    .entity(entity)
+                                    .insert(Buildings(state.buildings.clone()));
+                                entity
+                            }
+                            ObjectKind::Fighter | ObjectKind::Trader | ObjectKind::Dreadnought => {
+                                let owner = match state.owner {
+                                    Some(owner) => owner,
+                                    None => continue,
+                                };
+                                let details = match players.0.get(&owner) {
+                                    Some(details) => details,
+                                    None => continue,
+                                };
+                                let ship_type = match state.kind {
+                                    ObjectKind::Fighter => ShipType::Fighter,
+                                    ObjectKind::Dreadnought => ShipType::Dreadnought,
+                                    _ => ShipType::Trade,
+                                };
+                                let entity = spawn_ship(
+                                    &mut commands,
+                                    &mut meshes,
+                                    &mut net_ids,
+                                    ship_type,
+                                    transform,
+                                    DestinationEnum::None,
+                                    &owner,
+                                    details,
+                                );
+                                commands.entity(entity).insert(Interpolated {
+                                    from: transform,
+                                    to: transform,
+                                    elapsed: 0.,
+                                });
+                                entity
+                            }
+                        };
+                        // replicas carry the server id rather than a locally allocated one
+                        commands.entity(entity).insert(state.id);
+                        spawned.insert(state.id, entity);
+                    }
+                }
+            }
+            for id in removed.iter() {
+                let spawned_entity = spawned.remove(id);
+                if let Some(entity) = net_id_map.0.get(id).cloned().or(spawned_entity) {
+                    commands.entity(entity).despawn_recursive();
+                    despawned.insert(entity);
+                }
+            }
+            for (player, amount) in balances.iter() {
+                money.0.insert(*player, *amount);
+            }
+            for (player, player_research) in progress.iter() {
+                let known = research.0.get(player).map_or(0, |r| r.done.len());
+                for tech in player_research.done.iter().skip(known) {
+                    research_writer.send(ResearchCompleted {
+                        player: *player,
+                        tech: *tech,
+                    });
+                }
+                research.0.insert(*player, player_research.clone());
+            }
+        }
+    }
+}
+
+/// Client: replays the losses and attacks of each snapshot, for the log and the planet pulse.
+pub fn apply_snapshot_events(
+    net_id_map: Res<NetIdMap>,
+    mut ev_reader: EventReader<NetReceived>,
+    mut destroyed_writer: EventWriter<ShipDestroyed>,
+    mut attacked_writer: EventWriter<PlanetAttacked>,
+) {
+    for ev in ev_reader.iter() {
+        if let NetMessage::Snapshot {
+            destroyed,
+            attacked,
+            ..
+        } = &ev.msg
+        {
+            for (owner, by, is_trader) in destroyed.iter() {
+                destroyed_writer.send(ShipDestroyed {
+                    owner: *owner,
+                    by: *by,
+                    is_trader: *is_trader,
+                });
+            }
+            for (id, owner, attacker) in attacked.iter() {
+                if let Some(planet) = net_id_map.0.get(id) {
+                    attacked_writer.send(PlanetAttacked {
+                        planet: *planet,
+                        owner: *owner,
+                        attacker: *attacker,
+                    });
+                }
+            }
+        }
+    }
+}
+
+pub fn interpolate_transforms(
+    time: Res<Time>,
+    mut query: Query<(&mut Transform, &mut Interpolated)>,
+) {
+    for (mut transform, mut interpolated) in query.iter_mut() {
+        interpolated.elapsed += time.delta_seconds();
+        let t = (interpolated.elapsed / SNAPSHOT_SECONDS).min(1.);
+        transform.translation = interpolated
+            .from
+            .translation
+            .lerp(interpolated.to.translation, t);
+        transform.rotation = interpolated
+            .from
+            .rotation
+            .slerp(interpolated.to.rotation, t);
+    }
+}
+
+/// Client: without the server there is no game left to mirror.
+pub fn detect_server_lost(mut commands: Commands, connection: Res<Connection>) {
+    if connection.peers.iter().all(|p| p.closed) {
+        warn!("lost connection to server");
+        commands.insert_resource(NextState(GameState::MainMenu));
+    }
+}
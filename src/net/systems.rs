@@ -0,0 +1,551 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use bevy::{prelude::*, utils::Uuid};
+use iyes_loopless::prelude::*;
+
+use super::components::*;
+use super::protocol::*;
+use crate::game::components::{
+    characteristics::{NetId, Planet},
+    commands::*,
+    players::Ownership,
+};
+use crate::game::resources::{
+    game_obj_res::NetIdMap,
+    game_status_res::{MatchSetup, PlayerSlot, SimClock},
+    player_res::{LocalPlayer, PlayerMoney},
+};
+use crate::state::GameState;
+
+/// Opens the lobby, or shows in it why the port could not be opened or the host could not be
+/// reached.
+pub fn open_connection(mut commands: Commands, net_mode: Res<NetMode>, time: Res<Time>) {
+    let now = time.seconds_since_startup();
+    let opened = match &*net_mode {
+        NetMode::Host { port, name } => listen(port).map(|listener| {
+            let me = PlayerSlot {
+                uuid: Uuid::new_v4(),
+                name: name.clone(),
+                slot: 0,
+            };
+            (
+                Connection::new(Some(listener), Vec::new(), now),
+                Lobby::new(Some(me)),
+            )
+        }),
+        NetMode::Server { port, players } => match *players {
+            2..=MAX_PLAYERS => listen(port).map(|listener| {
+                (
+                    Connection::new(Some(listener), Vec::new(), now),
+                    Lobby::new(None),
+                )
+            }),
+            _ => Err(format!("A match needs 2 to {} players", MAX_PLAYERS)),
+        },
+        NetMode::Join { addr, name } | NetMode::Connect { addr, name } => {
+            connect(addr).map(|stream| {
+                let mut host = Peer::new(stream, now);
+                host.send(&NetMessage::Hello { name: name.clone() });
+                (Connection::new(None, vec![host], now), Lobby::new(None))
+            })
+        }
+        NetMode::Offline => return,
+    };
+    match opened {
+        Ok((connection, lobby)) => {
+            commands.insert_resource(connection);
+            commands.insert_resource(lobby);
+        }
+        Err(e) => {
+            error!("{}", e);
+            commands.insert_resource(Lobby::failed(e));
+        }
+    }
+}
+
+fn listen(port: &str) -> Result<TcpListener, String> {
+    TcpListener::bind(format!("0.0.0.0:{}", port))
+        .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+        .map_err(|e| format!("Could not open port {}: {}", port, e))
+}
+
+fn connect(addr: &str) -> Result<TcpStream, String> {
+    let stream = TcpStream::connect(addr)
+        .and_then(|stream| stream.set_nonblocking(true).map(|_| stream))
+        .map_err(|e| format!("Could not reach {}: {}", addr, e))?;
+    stream.set_nodelay(true).ok();
+    Ok(stream)
+}
+
+/// Single player: commands are executed with the next tick.
+pub fn forward_local_commands(
+    local: Res<LocalPlayer>,
+    mut issued: EventReader<IssueCommand>,
+    mut clock: ResMut<SimClock>,
+) {
+    for cmd in issued.iter() {
+        clock.pending.push(ReceivedCommand {
+            player: local.0,
+            command: cmd.0.clone(),
+        });
+    }
+}
+
+/// Outside lockstep there is nobody to wait for, the simulation follows real time.
+pub fn advance_sim_clock(time: Res<Time>, mut clock: ResMut<SimClock>) {
+    clock.advance(time.delta_seconds());
+}
+
+pub fn poll_network(
+    time: Res<Time>,
+    net_mode: Res<NetMode>,
+    cur_state: Res<CurrentState<GameState>>,
+    mut connection: ResMut<Connection>,
+    mut ev_writer: EventWriter<NetReceived>,
+) {
+    let now = time.seconds_since_startup();
+    let accepted = connection
+        .listener
+        .as_ref()
+        .and_then(|listener| listener.accept().ok());
+    if let Some((stream, _)) = accepted {
+        // a host keeps a slot for its own player, a dedicated server has none
+        let capacity = match net_mode.is_server() {
+            true => MAX_PLAYERS,
+            false => MAX_PLAYERS - 1,
+        };
+        let connected = connection.peers.iter().filter(|p| !p.closed).count();
+        // late joiners are refused once the match started
+        if cur_state.0 == GameState::Lobby
+            && connected < capacity
+            && stream.set_nonblocking(true).is_ok()
+        {
+            stream.set_nodelay(true).ok();
+            connection.peers.push(Peer::new(stream, now));
+        }
+    }
+
+    let mut relay = Vec::new();
+    for (i, peer) in connection.peers.iter_mut().enumerate() {
+        if peer.closed {
+            continue;
+        }
+        let mut buf = [0u8; 4096];
+        loop {
+            match peer.stream.read(&mut buf) {
+                Ok(0) => {
+                    peer.closed = true;
+                    break;
+                }
+                Ok(n) => peer.inbox.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    peer.closed = true;
+                    break;
+                }
+            }
+        }
+        for frame in split_frames(&mut peer.inbox) {
+            peer.last_seen = now;
+            match NetMessage::decode(&frame) {
+                Some(msg) => {
+                    if net_mode.is_host() {
+                        match &msg {
+                            // clients only speak for themselves
+                            NetMessage::Turn { player, .. }
+                            | NetMessage::Checksum { player, .. }
+                                if peer.player != Some(*player) =>
+                            {
+                                warn!("peer {} sent a message on behalf of {}", i, player);
+                                continue;
+                            }
+                            NetMessage::Turn { .. } | NetMessage::Checksum { .. } => {
+                                relay.push((i, msg.clone()));
+                            }
+                            // only the host announces who dropped
+                            NetMessage::PlayerDropped { .. } => continue,
+                            _ => {}
+                        }
+                    }
+                    ev_writer.send(NetReceived { peer: i, msg });
+                }
+                None => warn!("discarding malformed message from peer {}", i),
+            }
+        }
+    }
+    for (from, msg) in relay {
+        for (i, peer) in connection.peers.iter_mut().enumerate() {
+            if i != from && !peer.closed {
+                peer.send(&msg);
+            }
+        }
+    }
+
+    if now - connection.last_heartbeat > HEARTBEAT_SECONDS {
+        connection.last_heartbeat = now;
+        connection.broadcast(&NetMessage::Heartbeat);
+    }
+    for peer in connection.peers.iter_mut().filter(|p| !p.closed) {
+        while !peer.outbox.is_empty() {
+            match peer.stream.write(&peer.outbox) {
+                Ok(n) => {
+                    peer.outbox.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    peer.closed = true;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+pub fn handle_lobby_messages(
+    mut commands: Commands,
+    net_mode: Res<NetMode>,
+    mut connection: ResMut<Connection>,
+    mut lobby: ResMut<Lobby>,
+    mut ev_reader: EventReader<NetReceived>,
+) {
+    let mut changed = false;
+    for ev in ev_reader.iter() {
+        match &ev.msg {
+            // a peer that already has a slot keeps it
+            NetMessage::Hello { name }
+                if net_mode.is_host() && connection.peers[ev.peer].player.is_none() =>
+            {
+                let free_slot = (0..MAX_PLAYERS as u8)
+                    .find(|s| !lobby.slots.iter().any(|slot| slot.slot == *s));
+                match free_slot {
+                    Some(slot) => {
+                        let uuid = Uuid::new_v4();
+                        lobby.slots.push(PlayerSlot {
+                            uuid,
+                            name: name.clone(),
+                            slot,
+                        });
+                        connection.peers[ev.peer].player = Some(uuid);
+                        changed = true;
+                    }
+                    None => connection.peers[ev.peer].closed = true,
+                }
+            }
+            NetMessage::LobbyState { slots, you } => {
+                lobby.slots = slots.clone();
+                lobby.local = Some(*you);
+            }
+            NetMessage::StartMatch { seed } => {
+                start_match(&mut commands, &net_mode, &mut lobby, *seed);
+            }
+            _ => {}
+        }
+    }
+
+    // players leaving the lobby free their slot
+    if net_mode.is_host() {
+        for peer in connection.peers.iter_mut().filter(|p| p.closed) {
+            if let Some(player) = peer.player.take() {
+                lobby.slots.retain(|s| s.uuid != player);
+                changed = true;
+            }
+        }
+    }
+
+    if changed {
+        let slots = lobby.slots.clone();
+        for peer in connection.peers.iter_mut().filter(|p| !p.closed) {
+            if let Some(you) = peer.player {
+                peer.send(&NetMessage::LobbyState {
+                    slots: slots.clone(),
+                    you,
+                });
+            }
+        }
+    }
+}
+
+pub fn lobby_start_key(
+    mut commands: Commands,
+    net_mode: Res<NetMode>,
+    kb_input: Res<Input<KeyCode>>,
+    mut connection: ResMut<Connection>,
+    mut lobby: ResMut<Lobby>,
+) {
+    if kb_input.just_pressed(KeyCode::Return) && lobby.slots.len() > 1 {
+        let seed = rand::random::<u64>();
+        connection.broadcast(&NetMessage::StartMatch { seed });
+        start_match(&mut commands, &net_mode, &mut lobby, seed);
+    }
+}
+
+/// A dedicated server has nobody at the keyboard, it starts once the lobby is full.
+pub fn server_autostart(
+    mut commands: Commands,
+    net_mode: Res<NetMode>,
+    mut connection: ResMut<Connection>,
+    mut lobby: ResMut<Lobby>,
+) {
+    if let NetMode::Server { players, .. } = *net_mode {
+        if lobby.slots.len() >= players {
+            let seed = rand::random::<u64>();
+            connection.broadcast(&NetMessage::StartMatch { seed });
+            start_match(&mut commands, &net_mode, &mut lobby, seed);
+        }
+    }
+}
+
+fn start_match(commands: &mut Commands, net_mode: &NetMode, lobby: &mut Lobby, seed: u64) {
+    // the dedicated server plays on behalf of nobody
+    let local = match (net_mode.is_server(), lobby.local) {
+        (true, _) => Uuid::nil(),
+        (false, Some(local)) => local,
+        (false, None) => {
+            lobby.error = Some("The match started before the host let us in".to_string());
+            return;
+        }
+    };
+    let mut players = lobby.slots.clone();
+    players.sort_by_key(|s| s.slot);
+    if net_mode.is_lockstep() {
+        commands.insert_resource(Lockstep::new(
+            players.iter().map(|s| s.uuid).collect(),
+            encode_commands(&[], |_| None),
+        ));
+    }
+    commands.insert_resource(MatchSetup {
+        players,
+        local,
+        seed,
+    });
+    commands.insert_resource(NextState(GameState::InGame));
+}
+
+pub fn collect_local_commands(
+    mut lockstep: ResMut<Lockstep>,
+    mut issued: EventReader<IssueCommand>,
+) {
+    for cmd in issued.iter() {
+        lockstep.pending.push(cmd.0.clone());
+    }
+}
+
+pub fn handle_lockstep_messages(
+    mut lockstep: ResMut<Lockstep>,
+    mut ev_reader: EventReader<NetReceived>,
+) {
+    for ev in ev_reader.iter() {
+        match &ev.msg {
+            NetMessage::Turn {
+                turn,
+                player,
+                commands,
+            } => {
+                lockstep
+                    .batches
+                    .entry(*turn)
+                    .or_default()
+                    .insert(*player, commands.clone());
+                let last = lockstep.last_turn_from.entry(*player).or_insert(0);
+                *last = (*last).max(*turn);
+            }
+            NetMessage::Checksum {
+                turn,
+                player,
+                value,
+            } => {
+                lockstep
+                    .checksums
+                    .entry(*turn)
+                    .or_default()
+                    .insert(*player, *value);
+                verify_checksums(&mut lockstep, *turn);
+            }
+            NetMessage::PlayerDropped { player, from_turn } => {
+                warn!("player {} dropped from turn {}", player, from_turn);
+                lockstep.dropped.insert(*player, *from_turn);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn verify_checksums(lockstep: &mut Lockstep, turn: u32) {
+    let expected: Vec<Uuid> = lockstep
+        .players
+        .iter()
+        .filter(|p| lockstep.expects(p, turn))
+        .cloned()
+        .collect();
+    if let Some(values) = lockstep.checksums.get(&turn) {
+        if expected.iter().all(|p| values.contains_key(p)) {
+            let mut all = expected.iter().map(|p| values[p]);
+            let first = all.next();
+            if all.any(|v| Some(v) != first) && lockstep.desynced.is_none() {
+                error!("desync detected at turn {}", turn);
+                lockstep.desynced = Some(turn);
+            }
+            lockstep.checksums.remove(&turn);
+        }
+    }
+}
+
+/// Queues the ticks of turns whose batches arrived, with their commands in player order, and
+/// sends the local batch for `turn + INPUT_DELAY`. Nothing is queued while a batch is missing,
+/// so the simulation waits for slow peers instead of running ahead of them.
+pub fn advance_turn(
+    time: Res<Time>,
+    local: Res<LocalPlayer>,
+    net_id_map: Res<NetIdMap>,
+    net_ids: Query<&NetId>,
+    state_query: Query<(&NetId, &Ownership, Option<&Planet>)>,
+    money: Res<PlayerMoney>,
+    mut lockstep: ResMut<Lockstep>,
+    mut connection: ResMut<Connection>,
+    mut clock: ResMut<SimClock>,
+) {
+    if lockstep.desynced.is_some() {
+        return;
+    }
+    lockstep.elapsed += time.delta_seconds();
+    while lockstep.elapsed >= TURN_SECONDS {
+        if !lockstep.is_ready() {
+            // do not build up turns while waiting on a slow peer
+            lockstep.elapsed = TURN_SECONDS;
+            return;
+        }
+        // checksums must see every earlier turn simulated, whatever the frame rate
+        if lockstep.turn % CHECKSUM_INTERVAL == 0 && !clock.ticks.is_empty() {
+            return;
+        }
+        lockstep.elapsed -= TURN_SECONDS;
+        let turn = lockstep.turn;
+
+        if turn % CHECKSUM_INTERVAL == 0 {
+            let value = state_checksum(&state_query, &money);
+            lockstep
+                .checksums
+                .entry(turn)
+                .or_default()
+                .insert(local.0, value);
+            connection.broadcast(&NetMessage::Checksum {
+                turn,
+                player: local.0,
+                value,
+            });
+            verify_checksums(&mut lockstep, turn);
+        }
+
+        let batches = lockstep.batches.remove(&turn).unwrap_or_default();
+        let mut received = Vec::new();
+        for player in lockstep.players.iter() {
+            if !lockstep.expects(player, turn) {
+                continue;
+            }
+            if let Some(batch) = batches.get(player) {
+                let decoded = decode_commands(batch, |id| net_id_map.0.get(&id).cloned());
+                match decoded {
+                    Some(cmds) => {
+                        for command in cmds {
+                            received.push(ReceivedCommand {
+                                player: *player,
+                                command,
+                            });
+                        }
+                    }
+                    None => warn!("malformed batch from {} at turn {}", player, turn),
+                }
+            }
+        }
+        clock.queue(TICKS_PER_TURN, received);
+
+        let send_turn = turn + INPUT_DELAY;
+        let pending = std::mem::take(&mut lockstep.pending);
+        let encoded = encode_commands(&pending, |e| net_ids.get(e).ok().cloned());
+        lockstep
+            .batches
+            .entry(send_turn)
+            .or_default()
+            .insert(local.0, encoded.clone());
+        connection.broadcast(&NetMessage::Turn {
+            turn: send_turn,
+            player: local.0,
+            commands: encoded,
+        });
+        lockstep.turn += 1;
+    }
+}
+
+/// Hash of the state that matters for the outcome of the match: ownership, garrisons and money.
+fn state_checksum(
+    state_query: &Query<(&NetId, &Ownership, Option<&Planet>)>,
+    money: &Res<PlayerMoney>,
+) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let mut objects: Vec<(NetId, Option<Uuid>, Option<u32>)> = state_query
+        .iter()
+        .map(|(id, owner, planet)| (*id, owner.0, planet.map(|p| p.fighters as u32)))
+        .collect();
+    objects.sort_by_key(|o| o.0);
+    for (id, owner, fighters) in objects {
+        id.hash(&mut hasher);
+        owner.map(|o| o.as_u128()).hash(&mut hasher);
+        fighters.hash(&mut hasher);
+    }
+    let mut balances: Vec<(&Uuid, &u32)> = money.0.iter().collect();
+    balances.sort();
+    for (player, amount) in balances {
+        player.as_u128().hash(&mut hasher);
+        amount.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Host side: players whose socket closed or went silent stop being waited for. The drop turn
+/// is the first turn the host has no batch for, which every client will also have received.
+/// Client side: losing the host leaves the local player alone in the match.
+pub fn detect_dropped_players(
+    time: Res<Time>,
+    net_mode: Res<NetMode>,
+    local: Res<LocalPlayer>,
+    mut lockstep: ResMut<Lockstep>,
+    mut connection: ResMut<Connection>,
+) {
+    let now = time.seconds_since_startup();
+    let mut lost = Vec::new();
+    for peer in connection.peers.iter_mut() {
+        if !peer.closed && now - peer.last_seen > DROP_TIMEOUT_SECONDS {
+            peer.closed = true;
+        }
+        if peer.closed {
+            match net_mode.is_host() {
+                true => lost.extend(peer.player.take()),
+                false => {
+                    if peer.player.is_none() {
+                        // the host peer on a client has no player attached, mark it handled
+                        peer.player = Some(Uuid::nil());
+                        warn!("lost connection to host");
+                        lost.extend(lockstep.players.iter().filter(|p| **p != local.0).cloned());
+                    }
+                }
+            }
+        }
+    }
+    for player in lost {
+        if lockstep.dropped.contains_key(&player) {
+            continue;
+        }
+        let from_turn = lockstep
+            .last_turn_from
+            .get(&player)
+            .map_or(INPUT_DELAY, |t| t + 1);
+        lockstep.dropped.insert(player, from_turn);
+        warn!("player {} dropped from turn {}", player, from_turn);
+        if net_mode.is_host() {
+            connection.broadcast(&NetMessage::PlayerDropped { player, from_turn });
+        }
+    }
+}
@@ -11,12 +11,48 @@ pub struct Selectable;
 pub struct IsSelecting {
     pub is_selecting: bool,
     pub mouse_enter: Option<Vec2>,
+    /// Last entity picked with a single click and when, to detect double clicks.
+    pub last_click: Option<(Entity, f64)>,
 }
 
 #[derive(Component)]
 pub struct SelectionBox;
 
+/// Ctrl+number control groups, indexed from key 1 to 9.
+#[derive(Default)]
+pub struct ControlGroups {
+    pub groups: [Vec<Entity>; 9],
+    /// Last group recalled and when, to detect double taps.
+    pub last_recall: Option<(usize, f64)>,
+}
+
+/// Entity shown in the info panel. Foreign entities can be inspected but not selected.
+#[derive(Default)]
+pub struct Inspected(pub Option<Entity>);
+
+/// Read-only description of the inspected entity.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InfoView {
+    pub title: String,
+    pub lines: Vec<String>,
+}
+
+/// Summary of the selected ships, their orders and the order armed for the next click.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SelectedView {
+    pub lines: Vec<String>,
+}
+
+/// How a new pick combines with the current selection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SelectMode {
+    Replace,
+    Add,
+    Remove,
+}
+
 pub struct SelectMany {
     pub bottom_left: Vec2,
     pub top_right: Vec2,
+    pub mode: SelectMode,
 }
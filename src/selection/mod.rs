@@ -1,3 +1,4 @@
+use crate::game::resources::player_res::LocalPlayer;
 use crate::state::GameState;
 use bevy::prelude::*;
 use iyes_loopless::prelude::*;
@@ -12,14 +13,26 @@ impl Plugin for SelectionPlugin {
         app.insert_resource(IsSelecting {
             is_selecting: false,
             mouse_enter: None,
+            last_click: None,
         })
+        .insert_resource(ControlGroups::default())
+        .insert_resource(Inspected::default())
+        .insert_resource(InfoView::default())
+        .insert_resource(SelectedView::default())
         .add_event::<SelectMany>()
         .add_system_set(
             ConditionSet::new()
                 .run_in_state(GameState::InGame)
+                .run_if_resource_exists::<LocalPlayer>()
                 .with_system(box_select)
                 .with_system(update_box)
                 .with_system(draw_box_select)
+                .with_system(select_by_type)
+                .with_system(control_groups)
+                .with_system(prune_control_groups)
+                .with_system(deselect_lost_entities)
+                .with_system(update_info_view)
+                .with_system(update_selected_view)
                 .into(),
         );
     }
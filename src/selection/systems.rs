@@ -2,61 +2,222 @@ use bevy::ecs::query::QuerySingleError;
 use bevy::prelude::*;
 use bevy::render::mesh::{Indices, PrimitiveTopology};
 
-use crate::camera::MouseWorldPos;
+use crate::camera::{ndc_to_world, MainCamera, MouseWorldPos};
+use crate::game::components::characteristics::{
+    next_planet_type, Buildings, Destination, DestinationEnum, Dreadnought, Fighter, OrderQueue,
+    Planet, PlanetName, Ship, ShipOrder, ShipType, Trader,
+};
+use crate::game::components::players::Ownership;
+use crate::game::resources::game_obj_res::SpatialIndex;
+use crate::game::resources::game_status_res::{
+    ArmedOrder, DeployChoice, FormationChoice, PendingOrder,
+};
+use crate::game::resources::log_res::ChatInput;
+use crate::game::resources::player_res::{
+    LocalPlayer, PlayerMoney, PlayerResearch, PlayerVision, RegisteredPlayers,
+};
+use crate::game::systems::event_log::{planet_type_name, player_name};
+use crate::game::systems::production::{spare_fighters, PRODUCTION_SECONDS};
+use crate::game::utils::balance::{ship_type_name, Balance};
 use crate::game::utils::layers_util;
+use crate::game::utils::stats::Stat;
+use crate::minimap::components::MiniMap;
 
 use super::components::*;
-// TODO: selection hotkeys: to select all ships / all planets / all ships of specific type
+
+const GROUP_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+/// Seconds between two presses of a group key to center the camera on it.
+const DOUBLE_TAP_SECONDS: f64 = 0.3;
+
+/// Seconds between two clicks on a ship to select all visible ships of its type.
+const DOUBLE_CLICK_SECONDS: f64 = 0.3;
+
+/// How far from the cursor a click still picks an entity.
+const CLICK_DISTANCE: f32 = 3.2;
 
 pub fn update_box(
     mut commands: Commands,
+    time: Res<Time>,
+    local: Res<LocalPlayer>,
+    index: Res<SpatialIndex>,
     mut ev_select_box: EventReader<SelectMany>,
+    mut is_selecting_res: ResMut<IsSelecting>,
+    mut inspected: ResMut<Inspected>,
     query_selected: Query<Entity, With<Selected>>,
-    query: Query<(Entity, &Transform), With<Selectable>>,
+    query: Query<(&Ownership, Option<&Ship>), With<Selectable>>,
+    kind_query: Query<(Option<&Fighter>, Option<&Trader>, Option<&Dreadnought>)>,
+    camera_query: Query<(&Transform, &Camera), With<MainCamera>>,
 ) {
+    // only the local player's ships and planets can be selected, others are only inspected
+    let is_owned = |e: &Entity| match query.get(*e) {
+        Ok((owner, _)) => owner.0 == Some(local.0),
+        Err(_) => false,
+    };
     for ev in ev_select_box.iter() {
-        for s_entity in query_selected.iter() {
-            commands.entity(s_entity).remove::<Selected>();
-        }
+        let mut picked: Vec<Entity> = Vec::new();
         if ev.bottom_left.distance(ev.top_right) < 1. {
-            let mut min_entity = None;
-            let mut min_dist: f32 = 10.0;
-            for (e, transf) in query.iter() {
-                let mouse_pos_3d =
-                    layers_util::vec2_to_vec3(ev.bottom_left, layers_util::Layers::Planets);
-                let obj_dist = mouse_pos_3d.distance_squared(transf.translation);
-                if obj_dist <= min_dist {
-                    min_dist = obj_dist;
-                    min_entity = Some(e);
+            let min_entity = index.selectables.nearest(ev.bottom_left, CLICK_DISTANCE);
+            inspected.0 = min_entity;
+            if let Some(e) = min_entity.filter(is_owned) {
+                let now = time.seconds_since_startup();
+                let double_click = matches!(
+                    is_selecting_res.last_click,
+                    Some((last, at)) if last == e && now - at < DOUBLE_CLICK_SECONDS
+                );
+                is_selecting_res.last_click = Some((e, now));
+                match (double_click, ship_kind(&kind_query, e)) {
+                    (true, Some(kind)) => {
+                        let (bottom_left, top_right) = camera_view(&camera_query);
+                        picked = index
+                            .selectables
+                            .in_rect(bottom_left, top_right)
+                            .into_iter()
+                            .filter(is_owned)
+                            .filter(|e| ship_kind(&kind_query, *e) == Some(kind))
+                            .collect();
+                    }
+                    _ => picked.push(e),
                 }
             }
-            if let Some(e) = min_entity {
+        } else {
+            let in_area: Vec<(Entity, bool)> = index
+                .selectables
+                .in_rect(ev.bottom_left, ev.top_right)
+                .into_iter()
+                .filter(is_owned)
+                .map(|e| (e, matches!(query.get(e), Ok((_, Some(_))))))
+                .collect();
+            // ships take priority: planets are picked only when the box holds no ship
+            let has_ships = in_area.iter().any(|(_, is_ship)| *is_ship);
+            picked = in_area
+                .into_iter()
+                .filter(|(_, is_ship)| *is_ship == has_ships)
+                .map(|(e, _)| e)
+                .collect();
+            inspected.0 = match picked.len() {
+                1 => Some(picked[0]),
+                _ => None,
+            };
+        }
+        apply_selection(&mut commands, &query_selected, picked, ev.mode);
+    }
+}
+
+fn camera_view(camera_query: &Query<(&Transform, &Camera), With<MainCamera>>) -> (Vec2, Vec2) {
+    let (transf, camera) = camera_query.single();
+    (
+        ndc_to_world(Vec2::new(-1., -1.), transf, camera),
+        ndc_to_world(Vec2::new(1., 1.), transf, camera),
+    )
+}
+
+fn ship_kind(
+    kind_query: &Query<(Option<&Fighter>, Option<&Trader>, Option<&Dreadnought>)>,
+    entity: Entity,
+) -> Option<ShipType> {
+    match kind_query.get(entity) {
+        Ok((Some(_), _, _)) => Some(ShipType::Fighter),
+        Ok((_, Some(_), _)) => Some(ShipType::Trade),
+        Ok((_, _, Some(_))) => Some(ShipType::Dreadnought),
+        _ => None,
+    }
+}
+
+fn apply_selection(
+    commands: &mut Commands,
+    query_selected: &Query<Entity, With<Selected>>,
+    picked: Vec<Entity>,
+    mode: SelectMode,
+) {
+    match mode {
+        SelectMode::Replace => {
+            for e in query_selected.iter() {
+                commands.entity(e).remove::<Selected>();
+            }
+            for e in picked {
                 commands.entity(e).insert(Selected);
             }
-        } else {
-            // TODO: split selection between ships and planets.
-            for (e, transf) in query.iter() {
-                if transf.translation.x >= ev.bottom_left.x
-                    && transf.translation.x <= ev.top_right.x
-                    && transf.translation.y >= ev.bottom_left.y
-                    && transf.translation.y <= ev.top_right.y
-                {
-                    commands.entity(e).insert(Selected);
-                }
+        }
+        SelectMode::Add => {
+            for e in picked {
+                commands.entity(e).insert(Selected);
             }
         }
+        SelectMode::Remove => {
+            for e in picked {
+                commands.entity(e).remove::<Selected>();
+            }
+        }
+    }
+}
+
+pub fn select_by_type(
+    mut commands: Commands,
+    kb_input: Res<Input<KeyCode>>,
+    chat: Res<ChatInput>,
+    local: Res<LocalPlayer>,
+    query_selected: Query<Entity, With<Selected>>,
+    fighters: Query<(Entity, &Ownership), With<Fighter>>,
+    traders: Query<(Entity, &Ownership), With<Trader>>,
+    planets: Query<(Entity, &Ownership), With<Planet>>,
+) {
+    if chat.active {
+        return;
+    }
+    let owned = |(e, owner): (Entity, &Ownership)| match owner.0 == Some(local.0) {
+        true => Some(e),
+        false => None,
+    };
+    let picked: Vec<Entity> = if kb_input.just_pressed(KeyCode::F1) {
+        fighters.iter().filter_map(owned).collect()
+    } else if kb_input.just_pressed(KeyCode::F2) {
+        traders.iter().filter_map(owned).collect()
+    } else if kb_input.just_pressed(KeyCode::F3) {
+        planets.iter().filter_map(owned).collect()
+    } else {
+        return;
+    };
+    apply_selection(
+        &mut commands,
+        &query_selected,
+        picked,
+        select_mode(&kb_input),
+    );
+}
+
+pub fn select_mode(kb_input: &Input<KeyCode>) -> SelectMode {
+    if kb_input.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+        SelectMode::Add
+    } else if kb_input.any_pressed([KeyCode::LControl, KeyCode::RControl]) {
+        SelectMode::Remove
+    } else {
+        SelectMode::Replace
     }
 }
 
 pub fn box_select(
     mut commands: Commands,
     ms_input: Res<Input<MouseButton>>,
+    kb_input: Res<Input<KeyCode>>,
     ms_pos: Res<MouseWorldPos>,
+    minimap: Res<MiniMap>,
     selection_box: Query<Entity, With<SelectionBox>>,
     mut is_selecting_res: ResMut<IsSelecting>,
     mut ev_select_writer: EventWriter<SelectMany>,
 ) {
-    if ms_input.just_pressed(MouseButton::Left) {
+    // clicks on the minimap move the camera instead
+    if ms_input.just_pressed(MouseButton::Left) && !minimap.hovered {
         is_selecting_res.is_selecting = true;
         is_selecting_res.mouse_enter = Some(ms_pos.0);
     }
@@ -72,6 +233,7 @@ pub fn box_select(
                     ev_select_writer.send(SelectMany {
                         bottom_left,
                         top_right,
+                        mode: select_mode(&kb_input),
                     });
                 }
             }
@@ -178,3 +340,356 @@ pub fn box_select_mesh(screen_pos_origin: Vec2, screen_pos_target: Vec2) -> Mesh
     mesh.set_indices(Some(Indices::U32(vec![0, 1, 2, 1, 3, 2])));
     mesh
 }
+
+pub fn control_groups(
+    mut commands: Commands,
+    time: Res<Time>,
+    kb_input: Res<Input<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut groups: ResMut<ControlGroups>,
+    query_selected: Query<Entity, With<Selected>>,
+    query: Query<&Transform, With<Selectable>>,
+    mut camera_query: Query<&mut Transform, (With<MainCamera>, Without<Selectable>)>,
+) {
+    if chat.active {
+        return;
+    }
+    // Alt with a number key builds on the selected planets
+    if kb_input.any_pressed([KeyCode::LAlt, KeyCode::RAlt]) {
+        return;
+    }
+    let ctrl = kb_input.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let shift = kb_input.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    for (idx, key) in GROUP_KEYS.iter().enumerate() {
+        if !kb_input.just_pressed(*key) {
+            continue;
+        }
+        if ctrl {
+            groups.groups[idx] = query_selected.iter().collect();
+        } else if shift {
+            let group = &mut groups.groups[idx];
+            for e in query_selected.iter() {
+                if !group.contains(&e) {
+                    group.push(e);
+                }
+            }
+        } else {
+            for e in query_selected.iter() {
+                commands.entity(e).remove::<Selected>();
+            }
+            let group = &groups.groups[idx];
+            // ships destroyed this frame are not pruned yet
+            for e in group.iter().filter(|e| query.contains(**e)) {
+                commands.entity(*e).insert(Selected);
+            }
+            let now = time.seconds_since_startup();
+            if let Some((last, at)) = groups.last_recall {
+                if last == idx && now - at < DOUBLE_TAP_SECONDS {
+                    let positions: Vec<Vec3> = group
+                        .iter()
+                        .filter_map(|e| query.get(*e).ok())
+                        .map(|t| t.translation)
+                        .collect();
+                    if !positions.is_empty() {
+                        let center = positions.iter().sum::<Vec3>() / positions.len() as f32;
+                        let mut transf = camera_query.single_mut();
+                        transf.translation.x = center.x;
+                        transf.translation.y = center.y;
+                    }
+                }
+            }
+            groups.last_recall = Some((idx, now));
+        }
+    }
+}
+
+/// Drops destroyed ships from the control groups. They are destroyed in the simulation, whose
+/// despawns `RemovedComponents` misses by the time this runs, so the groups are swept instead.
+pub fn prune_control_groups(mut groups: ResMut<ControlGroups>, query: Query<(), With<Selectable>>) {
+    for group in groups.groups.iter_mut() {
+        group.retain(|e| query.contains(*e));
+    }
+}
+
+/// Captured entities can no longer receive orders: drop them from the selection and groups.
+pub fn deselect_lost_entities(
+    mut commands: Commands,
+    local: Res<LocalPlayer>,
+    mut groups: ResMut<ControlGroups>,
+    query: Query<(Entity, &Ownership, Option<&Selected>), Changed<Ownership>>,
+) {
+    for (e, owner, selected) in query.iter() {
+        if owner.0 == Some(local.0) {
+            continue;
+        }
+        if selected.is_some() {
+            commands.entity(e).remove::<Selected>();
+        }
+        if groups.groups.iter().any(|group| group.contains(&e)) {
+            for group in groups.groups.iter_mut() {
+                group.retain(|g| *g != e);
+            }
+        }
+    }
+}
+
+pub fn update_info_view(
+    local: Res<LocalPlayer>,
+    vision: Res<PlayerVision>,
+    players: Res<RegisteredPlayers>,
+    balance: Res<Balance>,
+    research: Res<PlayerResearch>,
+    mut inspected: ResMut<Inspected>,
+    mut view: ResMut<InfoView>,
+    query: Query<&Ownership>,
+    planets: Query<(&Planet, &Buildings, Option<&PlanetName>)>,
+    traders: Query<(&Ownership, &Destination, &OrderQueue), With<Trader>>,
+    ships: Query<
+        (
+            Option<&Fighter>,
+            Option<&Dreadnought>,
+            Option<&Destination>,
+            Option<&ShipOrder>,
+        ),
+        With<Ship>,
+    >,
+) {
+    let entity = match inspected.0 {
+        Some(entity) => entity,
+        None => {
+            if *view != InfoView::default() {
+                *view = InfoView::default();
+            }
+            return;
+        }
+    };
+    let seen = vision.sees(local.0, entity);
+    let owner = match query.get(entity) {
+        Ok(owner) if seen || planets.contains(entity) => owner.0,
+        // enemy ships leaving sight can no longer be inspected
+        _ => {
+            inspected.0 = None;
+            return;
+        }
+    };
+    let intel = vision.last_seen(local.0, entity);
+    let owner = match seen {
+        true => owner,
+        false => intel.and_then(|i| i.owner),
+    };
+    let owner_line = match owner {
+        Some(player) => format!("Owner: {}", player_name(&players, &local, player)),
+        None => "Owner: neutral".to_string(),
+    };
+    let new_view = if let Ok((planet, buildings, name)) = planets.get(entity) {
+        InfoView {
+            title: format!(
+                "{} ({})",
+                name.map_or("Planet", |n| n.0.as_str()),
+                planet_type_name(planet.planet_type)
+            ),
+            lines: match (seen, intel) {
+                (true, _) => {
+                    let mut lines = vec![owner_line, format!("Fighters: {}", planet.fighters)];
+                    // only owned planets produce
+                    if let Some(player) = owner {
+                        let interval = balance.stat(
+                            research.0.get(&player),
+                            Stat::ProductionInterval,
+                            PRODUCTION_SECONDS,
+                        );
+                        lines.push(format!(
+                            "Produces: +{} every {:.1}s",
+                            balance.production(planet, buildings),
+                            interval
+                        ));
+                        let income = balance.income(buildings);
+                        if income > 0 {
+                            lines.push(format!("Income: +{} money", income));
+                        }
+                    }
+                    let built: Vec<&str> = buildings
+                        .0
+                        .iter()
+                        .filter_map(|b| balance.buildings.get(*b as usize))
+                        .map(|b| b.name.as_str())
+                        .collect();
+                    lines.push(format!(
+                        "Buildings ({}/{}): {}",
+                        built.len(),
+                        balance.planet(planet.planet_type).slots,
+                        match built.is_empty() {
+                            true => "none".to_string(),
+                            false => built.join(", "),
+                        }
+                    ));
+                    let stops_here = |stop: &DestinationEnum| match stop {
+                        DestinationEnum::Planet { planet, .. } => *planet == entity,
+                        _ => false,
+                    };
+                    let routes = traders
+                        .iter()
+                        .filter(|(trader_owner, dest, queue)| {
+                            trader_owner.0 == Some(local.0)
+                                && queue.looping
+                                && (stops_here(&dest.0) || queue.orders.iter().any(stops_here))
+                        })
+                        .count();
+                    if routes > 0 {
+                        lines.push(format!("Your trade routes: {}", routes));
+                    }
+                    lines
+                }
+                (false, Some(intel)) => vec![
+                    format!("{} (last seen)", owner_line),
+                    format!("Fighters: {} (last seen)", intel.fighters),
+                ],
+                (false, None) => vec!["Not scouted yet".to_string()],
+            },
+        }
+    } else if let Ok((fighter, dreadnought, destination, order)) = ships.get(entity) {
+        let status = match (destination.map(|d| &d.0), order) {
+            (_, Some(order)) if *order != ShipOrder::Move => format!("Order: {}", order.name()),
+            (Some(DestinationEnum::None) | None, _) => "Idle".to_string(),
+            (Some(_), _) => "Moving".to_string(),
+        };
+        InfoView {
+            title: match (fighter, dreadnought) {
+                (Some(_), _) => "Fighter".to_string(),
+                (None, Some(_)) => "Dreadnought".to_string(),
+                (None, None) => "Trader".to_string(),
+            },
+            lines: vec![owner_line, status],
+        }
+    } else {
+        InfoView::default()
+    };
+    if *view != new_view {
+        *view = new_view;
+    }
+}
+
+pub fn update_selected_view(
+    pending: Res<PendingOrder>,
+    formation: Res<FormationChoice>,
+    deploy: Res<DeployChoice>,
+    balance: Res<Balance>,
+    money: Res<PlayerMoney>,
+    research: Res<PlayerResearch>,
+    local: Option<Res<LocalPlayer>>,
+    mut view: ResMut<SelectedView>,
+    ships: Query<
+        (Option<&Fighter>, Option<&Dreadnought>, &ShipOrder),
+        (With<Selected>, With<Ship>),
+    >,
+    planets: Query<(&Planet, &Buildings), With<Selected>>,
+) {
+    let mut fighters = 0;
+    let mut traders = 0;
+    let mut dreadnoughts = 0;
+    let mut orders: Vec<(&'static str, usize)> = Vec::new();
+    for (fighter, dreadnought, order) in ships.iter() {
+        match (fighter, dreadnought) {
+            (Some(_), _) => fighters += 1,
+            (None, Some(_)) => dreadnoughts += 1,
+            (None, None) => traders += 1,
+        }
+        match orders.iter_mut().find(|(name, _)| *name == order.name()) {
+            Some((_, count)) => *count += 1,
+            None => orders.push((order.name(), 1)),
+        }
+    }
+    let mut lines = Vec::new();
+    if fighters + traders + dreadnoughts > 0 {
+        lines.push(match dreadnoughts {
+            0 => format!("{} fighters, {} traders", fighters, traders),
+            _ => format!(
+                "{} fighters, {} traders, {} dreadnoughts",
+                fighters, traders, dreadnoughts
+            ),
+        });
+        let summary: Vec<String> = orders
+            .iter()
+            .map(|(name, count)| format!("{} {}", name, count))
+            .collect();
+        lines.push(format!("Orders: {}", summary.join(", ")));
+        lines.push(format!(
+            "Formation: {}{} (V change, B hold)",
+            formation.formation.name(),
+            if formation.hold { ", held" } else { "" }
+        ));
+        lines.push(match pending.order {
+            Some(ArmedOrder::Patrol) => format!(
+                "Right-click patrol points, Shift for more ({} placed)",
+                pending.patrol.len()
+            ),
+            Some(ArmedOrder::AttackMove) => "Right-click to attack-move".to_string(),
+            Some(ArmedOrder::Guard) => "Right-click a planet or ship to guard".to_string(),
+            None => "P patrol, E attack-move, Q guard, T hold".to_string(),
+        });
+    }
+    if !planets.is_empty() {
+        let spare: u32 = planets
+            .iter()
+            .map(|(p, _)| spare_fighters(p.fighters))
+            .sum();
+        lines.push(format!(
+            "{} planets, {} fighters ready",
+            planets.iter().count(),
+            spare
+        ));
+        lines.push(match deploy.exact {
+            Some(count) => format!("Deploy: {} (+/- to change, 0 for shares)", count),
+            None => "Deploy: all, Shift half, Alt quarter (+/- exact)".to_string(),
+        });
+        let local = local.map(|l| l.0);
+        let funds = local.and_then(|l| money.0.get(&l).copied()).unwrap_or(0);
+        lines.push(format!("Money: {}", funds));
+        if let Ok((planet, buildings)) = planets.get_single() {
+            let planet_balance = balance.planet(planet.planet_type);
+            let built: Vec<&str> = buildings
+                .0
+                .iter()
+                .filter_map(|b| balance.buildings.get(*b as usize))
+                .map(|b| b.name.as_str())
+                .collect();
+            lines.push(format!(
+                "Buildings ({}/{}): {}",
+                built.len(),
+                planet_balance.slots,
+                built.join(", ")
+            ));
+            if let Some(next) = next_planet_type(&planet.planet_type) {
+                lines.push(format!(
+                    "U upgrade to {}: {}",
+                    planet_type_name(next),
+                    planet_balance.upgrade_cost
+                ));
+            }
+        }
+        for (i, building) in balance.buildings.iter().enumerate().take(9) {
+            lines.push(format!(
+                "Alt+{} {}: {} ({})",
+                i + 1,
+                building.name,
+                building.cost,
+                building.effects()
+            ));
+        }
+        let player_research = local.and_then(|l| research.0.get(&l));
+        for (key, ship_type) in [("Y", ShipType::Trade), ("J", ShipType::Dreadnought)] {
+            let cost = match balance.ship_cost(player_research, ship_type) {
+                Some(cost) => cost,
+                None => continue,
+            };
+            lines.push(match balance.unlocked(player_research, ship_type) {
+                true => format!("{} buy {}: {}", key, ship_type_name(ship_type), cost),
+                false => format!("{} {}: needs research", key, ship_type_name(ship_type)),
+            });
+        }
+    }
+    let new_view = SelectedView { lines };
+    if *view != new_view {
+        *view = new_view;
+    }
+}
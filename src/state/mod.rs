@@ -1,11 +1,15 @@
 use bevy::prelude::*;
 use iyes_loopless::prelude::*;
 
+use crate::game::resources::log_res::ChatInput;
+use crate::net::components::NetMode;
+
 pub const STARTING_GAME_STATE: GameState = GameState::InGame;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum GameState {
     MainMenu,
+    Lobby,
     Options,
     InGame,
     Pause,
@@ -14,7 +18,12 @@ pub enum GameState {
 pub struct StatePlugin;
 impl Plugin for StatePlugin {
     fn build(&self, app: &mut App) {
-        app.add_loopless_state(STARTING_GAME_STATE)
+        // network games gather players in the lobby before the board is built
+        let starting_state = match app.world.get_resource::<NetMode>() {
+            Some(net_mode) if net_mode.is_networked() => GameState::Lobby,
+            _ => STARTING_GAME_STATE,
+        };
+        app.add_loopless_state(starting_state)
             .add_system(stage_key_bindings.run_not_in_state(GameState::MainMenu))
             .add_enter_system(GameState::InGame, setup_game);
     }
@@ -24,12 +33,19 @@ fn stage_key_bindings(
     mut commands: Commands,
     kb_input: Res<Input<KeyCode>>,
     cur_state: Res<CurrentState<GameState>>,
+    net_mode: Res<NetMode>,
+    chat: Res<ChatInput>,
 ) {
+    // Enter and Escape also open and close the chat
+    if chat.active || chat.is_changed() {
+        return;
+    }
     if kb_input.just_pressed(KeyCode::Escape) {
         commands.insert_resource(NextState(GameState::MainMenu));
         dbg!("ESC");
     }
-    if kb_input.just_pressed(KeyCode::Space) {
+    // pausing would stall every other peer in a network game
+    if kb_input.just_pressed(KeyCode::Space) && !net_mode.is_networked() {
         match cur_state.0 {
             GameState::InGame => commands.insert_resource(NextState(GameState::Pause)),
             GameState::Pause => commands.insert_resource(NextState(GameState::InGame)),
@@ -1,11 +1,11 @@
 use bevy::prelude::{Res, World};
 
 use kayak_ui::bevy::ImageManager;
-use kayak_ui::core::styles::{Corner, Edge, LayoutType};
+use kayak_ui::core::styles::{Corner, Edge, LayoutType, PositionType};
 use kayak_ui::core::{
-    rsx,
+    constructor, rsx,
     styles::{Style, StyleProp, Units},
-    widget, Bound, WidgetProps,
+    widget, Bound, VecTracker, WidgetProps,
 };
 use kayak_ui::core::{Binding, Color};
 use kayak_ui::widgets::{Background, Element, If, Text};
@@ -14,6 +14,9 @@ use super::generics::*;
 use super::styles::*;
 use crate::assets::ImageAssets;
 use crate::game;
+use crate::game::resources::game_status_res::ResearchView;
+use crate::minimap::components::MiniMapView;
+use crate::selection::components::{InfoView, SelectedView};
 use crate::state::GameState;
 
 #[widget]
@@ -60,10 +63,13 @@ pub fn InGameUI() {
     rsx! {
         <If condition={in_game}>
             <TopNavBar/>
-            // <MultiplayerAndLog/>
-            // <GroupsBar/>
-            // <MiniMap/>
-            // <ChatBar/>
+            <MultiplayerAndLog/>
+            <GroupsBar/>
+            <InfoPanel/>
+            <SelectedPanel/>
+            <ResearchPanel/>
+            <MiniMap/>
+            <ChatBar/>
         </If>
     }
 }
@@ -190,14 +196,293 @@ pub fn IconAndText() {
         </Element>
     }
 }
-// #[widget]
-// pub fn MultiplayerAndLog() {}
 
-// #[widget]
-// pub fn GroupsBar() {}
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LogLine {
+    pub text: String,
+    pub color: (f32, f32, f32, f32),
+}
+
+/// Visible part of the game log, after scrolling.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LogView {
+    pub lines: Vec<LogLine>,
+    pub scrolled: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChatView {
+    pub active: bool,
+    pub text: String,
+}
+
+#[widget]
+pub fn MultiplayerAndLog() {
+    let log_panel = Style {
+        position_type: StyleProp::Value(PositionType::SelfDirected),
+        layout_type: StyleProp::Value(LayoutType::Column),
+        top: StyleProp::Value(Units::Pixels(50.)),
+        right: StyleProp::Value(Units::Pixels(10.)),
+        left: StyleProp::Value(Units::Stretch(1.)),
+        width: StyleProp::Value(Units::Pixels(320.)),
+        height: StyleProp::Value(Units::Auto),
+        padding: StyleProp::Value(Edge::all(Units::Pixels(5.))),
+        border_radius: StyleProp::Value(Corner::all(5.0)),
+        ..Default::default()
+    };
+    let log = {
+        let log = context.query_world::<Res<Binding<LogView>>, _, _>(|log| log.clone());
+        context.bind(&log);
+        log.get()
+    };
+    let footer = match log.scrolled {
+        true => "PgDown to scroll back".to_string(),
+        false => "".to_string(),
+    };
+    rsx! {
+        <Background styles={Some(log_panel.with_style(bg_secondary()))}>
+            {VecTracker::from(log.lines.iter().map(|line| {
+                let (r, g, b, a) = line.color;
+                let line_style = Style {
+                    color: StyleProp::Value(Color::new(r, g, b, a)),
+                    ..Default::default()
+                };
+                constructor! {
+                    <Text size={14.0} content={line.text.clone()} styles={Some(line_style)} />
+                }
+            }))}
+            <Text size={12.0} content={footer} />
+        </Background>
+    }
+}
+
+/// Sizes of the non-empty control groups, by key number.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GroupsView {
+    pub groups: Vec<(usize, usize)>,
+}
+
+#[widget]
+pub fn GroupsBar() {
+    let groups_bar = Style {
+        position_type: StyleProp::Value(PositionType::SelfDirected),
+        layout_type: StyleProp::Value(LayoutType::Row),
+        top: StyleProp::Value(Units::Stretch(1.)),
+        bottom: StyleProp::Value(Units::Pixels(46.)),
+        left: StyleProp::Value(Units::Pixels(10.)),
+        width: StyleProp::Value(Units::Auto),
+        height: StyleProp::Value(Units::Pixels(26.)),
+        col_between: StyleProp::Value(Units::Pixels(5.)),
+        ..Default::default()
+    };
+    let group_box = Style {
+        width: StyleProp::Value(Units::Pixels(50.)),
+        height: StyleProp::Value(Units::Pixels(26.)),
+        padding_left: StyleProp::Value(Units::Pixels(5.)),
+        border_radius: StyleProp::Value(Corner::all(5.0)),
+        ..Default::default()
+    };
+    let groups = {
+        let groups = context.query_world::<Res<Binding<GroupsView>>, _, _>(|groups| groups.clone());
+        context.bind(&groups);
+        groups.get()
+    };
+    rsx! {
+        <Element styles={Some(groups_bar)}>
+            {VecTracker::from(groups.groups.iter().map(|(key, size)| {
+                let content = format!("{}: {}", key, size);
+                constructor! {
+                    <Background styles={Some(group_box.clone().with_style(bg_secondary()))}>
+                        <Text size={16.0} content={content} />
+                    </Background>
+                }
+            }))}
+        </Element>
+    }
+}
+
+#[widget]
+pub fn MiniMap() {
+    let view = {
+        let view = context.query_world::<Res<Binding<MiniMapView>>, _, _>(|view| view.clone());
+        context.bind(&view);
+        view.get()
+    };
+    let self_directed = |left: f32, top: f32, width: f32, height: f32| Style {
+        position_type: StyleProp::Value(PositionType::SelfDirected),
+        left: StyleProp::Value(Units::Pixels(left)),
+        top: StyleProp::Value(Units::Pixels(top)),
+        width: StyleProp::Value(Units::Pixels(width)),
+        height: StyleProp::Value(Units::Pixels(height)),
+        ..Default::default()
+    };
+    let minimap_frame = Style {
+        position_type: StyleProp::Value(PositionType::SelfDirected),
+        left: StyleProp::Value(Units::Stretch(1.)),
+        top: StyleProp::Value(Units::Stretch(1.)),
+        right: StyleProp::Value(Units::Pixels(10.)),
+        bottom: StyleProp::Value(Units::Pixels(10.)),
+        width: StyleProp::Value(Units::Pixels(view.size)),
+        height: StyleProp::Value(Units::Pixels(view.size)),
+        border_radius: StyleProp::Value(Corner::all(5.0)),
+        ..Default::default()
+    };
+    let (left, top, size) = view.galaxy;
+    let galaxy = Style {
+        background_color: StyleProp::Value(Color::new(0.05, 0.05, 0.15, 1.)),
+        border_radius: StyleProp::Value(Corner::all(size / 2.)),
+        ..self_directed(left, top, size, size)
+    };
+    let (left, top, width, height) = view.viewport;
+    let viewport = Style {
+        background_color: StyleProp::Value(Color::new(0., 0., 0., 0.)),
+        border_color: StyleProp::Value(Color::new(1., 1., 1., 1.)),
+        border: StyleProp::Value(Edge::all(1.0)),
+        ..self_directed(left, top, width, height)
+    };
+    rsx! {
+        <Background styles={Some(minimap_frame.with_style(bg_secondary()))}>
+            <Background styles={Some(galaxy)} />
+            {VecTracker::from(view.planets.iter().chain(view.fleets.iter()).map(|dot| {
+                let (r, g, b, a) = dot.color;
+                let dot_style = Style {
+                    background_color: StyleProp::Value(Color::new(r, g, b, a)),
+                    border_radius: StyleProp::Value(Corner::all(dot.size / 2.)),
+                    ..self_directed(dot.left, dot.top, dot.size, dot.size)
+                };
+                constructor! {
+                    <Background styles={Some(dot_style)} />
+                }
+            }))}
+            <Background styles={Some(viewport)} />
+        </Background>
+    }
+}
 
-// #[widget]
-// pub fn MiniMap() {}
+#[widget]
+pub fn InfoPanel() {
+    let info_panel = Style {
+        position_type: StyleProp::Value(PositionType::SelfDirected),
+        layout_type: StyleProp::Value(LayoutType::Column),
+        top: StyleProp::Value(Units::Pixels(50.)),
+        left: StyleProp::Value(Units::Pixels(10.)),
+        width: StyleProp::Value(Units::Pixels(220.)),
+        height: StyleProp::Value(Units::Auto),
+        padding: StyleProp::Value(Edge::all(Units::Pixels(5.))),
+        border_radius: StyleProp::Value(Corner::all(5.0)),
+        ..Default::default()
+    };
+    let info = {
+        let info = context.query_world::<Res<Binding<InfoView>>, _, _>(|info| info.clone());
+        context.bind(&info);
+        info.get()
+    };
+    let show_info = !info.title.is_empty();
+    rsx! {
+        <If condition={show_info}>
+            <Background styles={Some(info_panel.with_style(bg_secondary()))}>
+                <Text size={18.0} content={info.title.clone()} />
+                {VecTracker::from(info.lines.iter().map(|line| {
+                    constructor! {
+                        <Text size={14.0} content={line.clone()} />
+                    }
+                }))}
+            </Background>
+        </If>
+    }
+}
+
+#[widget]
+pub fn SelectedPanel() {
+    let selected_panel = Style {
+        position_type: StyleProp::Value(PositionType::SelfDirected),
+        layout_type: StyleProp::Value(LayoutType::Column),
+        top: StyleProp::Value(Units::Stretch(1.)),
+        bottom: StyleProp::Value(Units::Pixels(82.)),
+        left: StyleProp::Value(Units::Pixels(10.)),
+        width: StyleProp::Value(Units::Pixels(300.)),
+        height: StyleProp::Value(Units::Auto),
+        padding: StyleProp::Value(Edge::all(Units::Pixels(5.))),
+        border_radius: StyleProp::Value(Corner::all(5.0)),
+        ..Default::default()
+    };
+    let selected = {
+        let selected =
+            context.query_world::<Res<Binding<SelectedView>>, _, _>(|selected| selected.clone());
+        context.bind(&selected);
+        selected.get()
+    };
+    let show_selected = !selected.lines.is_empty();
+    rsx! {
+        <If condition={show_selected}>
+            <Background styles={Some(selected_panel.with_style(bg_secondary()))}>
+                {VecTracker::from(selected.lines.iter().map(|line| {
+                    constructor! {
+                        <Text size={14.0} content={line.clone()} />
+                    }
+                }))}
+            </Background>
+        </If>
+    }
+}
+
+#[widget]
+pub fn ResearchPanel() {
+    let research_panel = Style {
+        position_type: StyleProp::Value(PositionType::SelfDirected),
+        layout_type: StyleProp::Value(LayoutType::Column),
+        top: StyleProp::Value(Units::Pixels(50.)),
+        left: StyleProp::Value(Units::Pixels(240.)),
+        width: StyleProp::Value(Units::Pixels(300.)),
+        height: StyleProp::Value(Units::Auto),
+        padding: StyleProp::Value(Edge::all(Units::Pixels(5.))),
+        border_radius: StyleProp::Value(Corner::all(5.0)),
+        ..Default::default()
+    };
+    let research = {
+        let research =
+            context.query_world::<Res<Binding<ResearchView>>, _, _>(|research| research.clone());
+        context.bind(&research);
+        research.get()
+    };
+    rsx! {
+        <If condition={research.open}>
+            <Background styles={Some(research_panel.with_style(bg_secondary()))}>
+                {VecTracker::from(research.lines.iter().map(|line| {
+                    constructor! {
+                        <Text size={14.0} content={line.clone()} />
+                    }
+                }))}
+            </Background>
+        </If>
+    }
+}
 
-// #[widget]
-// pub fn ChatBar() {}
+#[widget]
+pub fn ChatBar() {
+    let chat_bar = Style {
+        position_type: StyleProp::Value(PositionType::SelfDirected),
+        top: StyleProp::Value(Units::Stretch(1.)),
+        bottom: StyleProp::Value(Units::Pixels(10.)),
+        left: StyleProp::Value(Units::Pixels(10.)),
+        width: StyleProp::Value(Units::Pixels(400.)),
+        height: StyleProp::Value(Units::Pixels(26.)),
+        padding_left: StyleProp::Value(Units::Pixels(5.)),
+        border_radius: StyleProp::Value(Corner::all(5.0)),
+        ..Default::default()
+    };
+    let chat = {
+        let chat = context.query_world::<Res<Binding<ChatView>>, _, _>(|chat| chat.clone());
+        context.bind(&chat);
+        chat.get()
+    };
+    let content = match chat.active {
+        true => format!("> {}_", chat.text),
+        false => "Enter to chat, /ally /accept /war <player>".to_string(),
+    };
+    rsx! {
+        <Background styles={Some(chat_bar.with_style(bg_secondary()))}>
+            <Text size={16.0} content={content} />
+        </Background>
+    }
+}
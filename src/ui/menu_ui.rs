@@ -6,12 +6,13 @@ use iyes_loopless::state::NextState;
 use kayak_ui::bevy::ImageManager;
 use kayak_ui::core::styles::Edge;
 use kayak_ui::core::Binding;
-use kayak_ui::core::{rsx, widget, Bound, EventType, OnEvent};
-use kayak_ui::widgets::{If, NinePatch, Text};
+use kayak_ui::core::{constructor, rsx, widget, Bound, EventType, OnEvent, VecTracker};
+use kayak_ui::widgets::{Background, If, NinePatch, Text};
 
 use super::generics as gen;
 use super::styles::*;
 use crate::assets::ImageAssets;
+use crate::net::components::LobbyView;
 use crate::state::GameState;
 
 #[widget]
@@ -90,3 +91,43 @@ pub fn GameMenu() {
         </If>
     }
 }
+
+#[widget]
+pub fn LobbyMenu() {
+    // CSS
+    let container_style = container_style()
+        .with_style(bg_primary())
+        .with_style(center());
+
+    // RESOURCES
+    let show_lobby = {
+        let gamestate = context.query_world::<Res<Binding<GameState>>, _, _>(|state| state.clone());
+        context.bind(&gamestate);
+        gamestate.get() == GameState::Lobby
+    };
+    let lobby = {
+        let lobby = context.query_world::<Res<Binding<LobbyView>>, _, _>(|lobby| lobby.clone());
+        context.bind(&lobby);
+        lobby.get()
+    };
+    let footer = match (&lobby.error, lobby.is_host) {
+        (Some(error), _) => error.clone(),
+        (None, true) => "Press Enter to start".to_string(),
+        (None, false) => "Waiting for host...".to_string(),
+    };
+
+    // RSX
+    rsx! {
+        <If condition={show_lobby}>
+            <Background styles={Some(container_style)}>
+                <Text size={24.0} content={"LOBBY".to_string()} />
+                {VecTracker::from(lobby.lines.iter().map(|line| {
+                    constructor! {
+                        <Text size={20.0} content={line.clone()} />
+                    }
+                }))}
+                <Text size={16.0} content={footer} />
+            </Background>
+        </If>
+    }
+}
@@ -11,7 +11,14 @@ use kayak_ui::core::{bind, Binding};
 use kayak_ui::core::{render, MutableBound};
 use kayak_ui::widgets::App as KApp;
 
-use crate::game::resources;
+use crate::game::resources::{
+    self,
+    game_status_res::ResearchView,
+    log_res::{ChatInput, GameLog},
+};
+use crate::minimap::components::MiniMapView;
+use crate::net::components::{Lobby, LobbyView, NetMode};
+use crate::selection::components::{ControlGroups, InfoView, SelectedView};
 use crate::state::{self, GameState};
 use ingame_ui::*;
 use menu_ui::*;
@@ -29,6 +36,7 @@ fn ui_startup(
         render! {
             <KApp>
                 <GameMenu/>
+                <LobbyMenu/>
                 <PauseMenu/>
                 <InGameUI/>
             </KApp>
@@ -59,6 +67,100 @@ pub fn bind_fighter_stored(
         binding.set(state.clone());
     }
 }
+/// Number of log lines the in-game panel shows at once.
+const LOG_LINES_SHOWN: usize = 8;
+
+pub fn bind_log_view(log: Res<GameLog>, binding: Res<Binding<LogView>>) {
+    if log.is_changed() {
+        let end = log.entries.len() - log.scroll.min(log.entries.len());
+        let start = end.saturating_sub(LOG_LINES_SHOWN);
+        let lines = log.entries[start..end]
+            .iter()
+            .map(|entry| {
+                let seconds = entry.seconds as u64;
+                LogLine {
+                    text: format!("[{:02}:{:02}] {}", seconds / 60, seconds % 60, entry.text),
+                    color: (entry.color.r(), entry.color.g(), entry.color.b(), 1.),
+                }
+            })
+            .collect();
+        binding.set(LogView {
+            lines,
+            scrolled: log.scroll > 0,
+        });
+    }
+}
+
+pub fn bind_chat_view(chat: Res<ChatInput>, binding: Res<Binding<ChatView>>) {
+    if chat.is_changed() {
+        binding.set(ChatView {
+            active: chat.active,
+            text: chat.text.clone(),
+        });
+    }
+}
+
+pub fn bind_groups_view(groups: Res<ControlGroups>, binding: Res<Binding<GroupsView>>) {
+    if groups.is_changed() {
+        let groups = groups
+            .groups
+            .iter()
+            .enumerate()
+            .filter(|(_, group)| !group.is_empty())
+            .map(|(idx, group)| (idx + 1, group.len()))
+            .collect();
+        binding.set(GroupsView { groups });
+    }
+}
+
+pub fn bind_info_view(view: Res<InfoView>, binding: Res<Binding<InfoView>>) {
+    if view.is_changed() {
+        binding.set(view.clone());
+    }
+}
+
+pub fn bind_selected_view(view: Res<SelectedView>, binding: Res<Binding<SelectedView>>) {
+    if view.is_changed() {
+        binding.set(view.clone());
+    }
+}
+
+pub fn bind_research_view(view: Res<ResearchView>, binding: Res<Binding<ResearchView>>) {
+    if view.is_changed() {
+        binding.set(view.clone());
+    }
+}
+
+pub fn bind_minimap_view(view: Res<MiniMapView>, binding: Res<Binding<MiniMapView>>) {
+    if view.is_changed() {
+        binding.set(view.clone());
+    }
+}
+
+pub fn bind_lobby_view(
+    net_mode: Res<NetMode>,
+    lobby: Option<Res<Lobby>>,
+    binding: Res<Binding<LobbyView>>,
+) {
+    if let Some(lobby) = lobby {
+        if lobby.is_changed() {
+            let mut lines: Vec<String> = lobby
+                .slots
+                .iter()
+                .map(|s| match lobby.local == Some(s.uuid) {
+                    true => format!("{}. {} (you)", s.slot + 1, s.name),
+                    false => format!("{}. {}", s.slot + 1, s.name),
+                })
+                .collect();
+            lines.sort();
+            binding.set(LobbyView {
+                lines,
+                is_host: net_mode.is_host(),
+                error: lobby.error.clone(),
+            });
+        }
+    }
+}
 
 pub struct UiPlugin;
 
@@ -71,9 +173,25 @@ impl Plugin for UiPlugin {
             .insert_resource(bind(resources::game_obj_res::TotalTraders(0)))
             .insert_resource(bind(resources::game_obj_res::TotalDreadnoughts(0)))
             .insert_resource(bind(resources::game_obj_res::TotalPlanets(0)))
+            .insert_resource(bind(LobbyView::default()))
+            .insert_resource(bind(LogView::default()))
+            .insert_resource(bind(ChatView::default()))
+            .insert_resource(bind(MiniMapView::default()))
+            .insert_resource(bind(GroupsView::default()))
+            .insert_resource(bind(InfoView::default()))
+            .insert_resource(bind(SelectedView::default()))
+            .insert_resource(bind(ResearchView::default()))
             .add_startup_system(ui_startup)
             .add_system(bind_gamestate)
             .add_system(bind_fighter_deployed)
-            .add_system(bind_fighter_stored);
+            .add_system(bind_fighter_stored)
+            .add_system(bind_lobby_view)
+            .add_system(bind_log_view)
+            .add_system(bind_chat_view)
+            .add_system(bind_minimap_view)
+            .add_system(bind_groups_view)
+            .add_system(bind_info_view)
+            .add_system(bind_selected_view)
+            .add_system(bind_research_view);
     }
 }